@@ -0,0 +1,228 @@
+#ifndef FWSIG_H
+#define FWSIG_H
+
+/* Generated with cbindgen, do not edit manually */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+#define FWSIG_ALIGNED(n) __attribute__((aligned(n)))
+
+// Status codes returned by C API functions
+//
+// `FWSIG_STATUS_OK` (zero) indicates success, [ManifestError] variants map
+// to codes from -16 and [VerifyError] variants to codes from -48.
+enum FwsigStatus
+#if defined(__cplusplus) || __STDC_VERSION__ >= 202311L
+  : int32_t
+#endif // defined(__cplusplus) || __STDC_VERSION__ >= 202311L
+ {
+  // Operation succeeded
+  FWSIG_STATUS_OK = 0,
+  // Null pointer or invalid argument
+  FWSIG_STATUS_INVALID_ARGUMENT = -1,
+  // [ManifestError::MissingAppChecksum]
+  FWSIG_STATUS_MISSING_APP_CHECKSUM = -16,
+  // [ManifestError::MissingMetaChecksum]
+  FWSIG_STATUS_MISSING_META_CHECKSUM = -17,
+  // [ManifestError::InvalidPublicKey]
+  FWSIG_STATUS_INVALID_PUBLIC_KEY = -18,
+  // [ManifestError::InvalidPrivateKey]
+  FWSIG_STATUS_INVALID_PRIVATE_KEY = -19,
+  // [ManifestError::InvalidHex]
+  FWSIG_STATUS_INVALID_HEX = -20,
+  // [ManifestError::SigningFailed]
+  FWSIG_STATUS_SIGNING_FAILED = -21,
+  // [ManifestError::NoMatchingKey]
+  FWSIG_STATUS_NO_MATCHING_KEY = -22,
+  // [ManifestError::InvalidSignature]
+  FWSIG_STATUS_INVALID_SIGNATURE = -23,
+  // [ManifestError::VerificationFailed]
+  FWSIG_STATUS_VERIFICATION_FAILED = -24,
+  // [ManifestError::StringTooLong]
+  FWSIG_STATUS_STRING_TOO_LONG = -25,
+  // [ManifestError::AppTooLarge]
+  FWSIG_STATUS_APP_TOO_LARGE = -26,
+  // [ManifestError::MetaTooLarge]
+  FWSIG_STATUS_META_TOO_LARGE = -27,
+  // [ManifestError::UnsupportedVersion]
+  FWSIG_STATUS_UNSUPPORTED_VERSION = -28,
+  // [ManifestError::UnsupportedSigMode]
+  FWSIG_STATUS_UNSUPPORTED_SIG_MODE = -29,
+  // [ManifestError::UnsupportedFlags]
+  FWSIG_STATUS_UNSUPPORTED_FLAGS = -30,
+  // [ManifestError::InvalidAlignment]
+  FWSIG_STATUS_INVALID_ALIGNMENT = -31,
+  // [ManifestError::PackageLength]
+  FWSIG_STATUS_PACKAGE_LENGTH = -32,
+  // [ManifestError::PackageTooLarge]
+  FWSIG_STATUS_PACKAGE_TOO_LARGE = -33,
+  // [ManifestError::Codec]
+  FWSIG_STATUS_CODEC = -34,
+  // [ManifestError::InvalidBlockSize]
+  FWSIG_STATUS_INVALID_BLOCK_SIZE = -35,
+  // [ManifestError::BlockSizeAfterApp]
+  FWSIG_STATUS_BLOCK_SIZE_AFTER_APP = -36,
+  // [ManifestError::InvalidReserved]
+  FWSIG_STATUS_INVALID_RESERVED = -37,
+  // [VerifyError::AppLengthMismatch]
+  FWSIG_STATUS_APP_LENGTH_MISMATCH = -48,
+  // [VerifyError::AppChecksumMismatch]
+  FWSIG_STATUS_APP_CHECKSUM_MISMATCH = -49,
+  // [VerifyError::MetaLengthMismatch]
+  FWSIG_STATUS_META_LENGTH_MISMATCH = -50,
+  // [VerifyError::MetaChecksumMismatch]
+  FWSIG_STATUS_META_CHECKSUM_MISMATCH = -51,
+  // [VerifyError::InvalidSignature]
+  FWSIG_STATUS_CHECK_INVALID_SIGNATURE = -52,
+  // [VerifyError::VerificationFailed]
+  FWSIG_STATUS_CHECK_VERIFICATION_FAILED = -53,
+  // [VerifyError::UnsupportedVersion]
+  FWSIG_STATUS_CHECK_UNSUPPORTED_VERSION = -54,
+  // [VerifyError::UnsupportedFlags]
+  FWSIG_STATUS_CHECK_UNSUPPORTED_FLAGS = -55,
+};
+#ifndef __cplusplus
+#if __STDC_VERSION__ >= 202311L
+typedef enum FwsigStatus FwsigStatus;
+#else
+typedef int32_t FwsigStatus;
+#endif // __STDC_VERSION__ >= 202311L
+#endif // __cplusplus
+
+// Decoded manifest, see [Manifest] for field descriptions
+//
+// Strings are utf8 and zero-padded, lengths are widened to 64 bits for all layouts.
+typedef struct FwsigManifest {
+  // Manifest version
+  uint16_t version;
+  // Manifest flags
+  uint16_t flags;
+  // Application name
+  uint8_t app_name[16];
+  // Application version
+  uint8_t app_version[24];
+  // Application length
+  uint64_t app_len;
+  // Application checksum
+  uint8_t app_csum[32];
+  // Metadata kind
+  uint16_t meta_kind;
+  // Metadata length
+  uint64_t meta_len;
+  // Metadata checksum
+  uint8_t meta_csum[32];
+  // Signing public key
+  uint8_t key[32];
+  // Manifest signature
+  uint8_t sig[64];
+} FwsigManifest;
+
+// Incremental app / metadata checksum state, opaque to callers
+//
+// Initialise with [fwsig_hash_init], then call [fwsig_hash_update] for each chunk
+// of data and [fwsig_hash_finish] to write the checksum.
+typedef struct FWSIG_ALIGNED(16) FwsigHasher {
+  uint8_t _state[256];
+} FwsigHasher;
+
+// Incremental application checksum state for a manifest, opaque to callers
+//
+// This computes the block root for manifests using block hashing ([crate::Flags::BLOCK_SIZE]),
+// and the whole-image checksum otherwise. Initialise with [fwsig_app_hash_init], then call
+// [fwsig_app_hash_update] for each chunk of data and [fwsig_app_hash_finish] to write the checksum.
+typedef struct FWSIG_ALIGNED(16) FwsigAppHasher {
+  uint8_t _state[2816];
+} FwsigAppHasher;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Decode a manifest from the start of `data` (ie. a detached or header manifest)
+//
+// # Safety
+// `data` must be valid for `len` bytes and `out` must be a valid pointer
+FwsigStatus fwsig_manifest_decode(const uint8_t *data, size_t len, struct FwsigManifest *out);
+
+// Decode a manifest trailing `data` (ie. from an attached package), writing the
+// offset of the manifest within the data to `offset` (if not null)
+//
+// # Safety
+// `data` must be valid for `len` bytes, `out` must be a valid pointer,
+// and `offset` must be valid or null
+FwsigStatus fwsig_manifest_decode_trailer(const uint8_t *data,
+                                          size_t len,
+                                          struct FwsigManifest *out,
+                                          size_t *offset);
+
+// Initialise a checksum state
+//
+// # Safety
+// `h` must be a valid pointer
+FwsigStatus fwsig_hash_init(struct FwsigHasher *h);
+
+// Update a checksum state with `len` bytes of `data`
+//
+// # Safety
+// `h` must be initialised with [fwsig_hash_init] and `data` must be valid for `len` bytes
+FwsigStatus fwsig_hash_update(struct FwsigHasher *h, const uint8_t *data, size_t len);
+
+// Finalise a checksum state, writing the checksum to `out`
+//
+// The state must be re-initialised with [fwsig_hash_init] before reuse.
+//
+// # Safety
+// `h` must be initialised with [fwsig_hash_init] and `out` must be a valid pointer
+FwsigStatus fwsig_hash_finish(struct FwsigHasher *h, uint8_t (*out)[32]);
+
+// Initialise an application checksum state for the provided manifest
+//
+// # Safety
+// `h` and `m` must be valid pointers
+FwsigStatus fwsig_app_hash_init(struct FwsigAppHasher *h, const struct FwsigManifest *m);
+
+// Update an application checksum state with `len` bytes of `data`
+//
+// # Safety
+// `h` must be initialised with [fwsig_app_hash_init] and `data` must be valid for `len` bytes
+FwsigStatus fwsig_app_hash_update(struct FwsigAppHasher *h, const uint8_t *data, size_t len);
+
+// Finalise an application checksum state, writing the checksum to `out`
+//
+// The state must be re-initialised with [fwsig_app_hash_init] before reuse.
+//
+// # Safety
+// `h` must be initialised with [fwsig_app_hash_init] and `out` must be a valid pointer
+FwsigStatus fwsig_app_hash_finish(struct FwsigAppHasher *h, uint8_t (*out)[32]);
+
+// Check app and metadata lengths and checksums against the manifest,
+// and that the manifest signature is valid
+//
+// The app checksum must be computed with [fwsig_app_hash_init] (or [fwsig_hash_init] where
+// the manifest does not use block hashing), and the metadata checksum with [fwsig_hash_init].
+// Note this does not check the signing key is trusted, see [fwsig_manifest_verify].
+//
+// # Safety
+// `m`, `app_csum` and `meta_csum` must be valid pointers
+FwsigStatus fwsig_manifest_check(const struct FwsigManifest *m,
+                                 const uint8_t (*app_csum)[32],
+                                 size_t app_len,
+                                 const uint8_t (*meta_csum)[32],
+                                 size_t meta_len);
+
+// Verify the manifest signature against an array of `num_keys` trusted public keys
+//
+// # Safety
+// `m` must be a valid pointer and `keys` must be valid for `num_keys` keys
+FwsigStatus fwsig_manifest_verify(const struct FwsigManifest *m,
+                                  const uint8_t (*keys)[32],
+                                  size_t num_keys);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* FWSIG_H */
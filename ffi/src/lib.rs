@@ -0,0 +1,15 @@
+//! C bindings for fwsig, building a `staticlib` / `cdylib` exporting the [fwsig::ffi] API
+//!
+//! For embedded targets build without default features, selecting the ed25519-dalek `u32_backend`
+//! (and `panic = "abort"`), for example:
+//! `cargo build -p fwsig-ffi --release --no-default-features --features ed25519-dalek/u32_backend --target thumbv7em-none-eabihf`
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+pub use fwsig::ffi::*;
+
+#[cfg(not(feature = "std"))]
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {}
+}
@@ -0,0 +1,354 @@
+//! Python bindings for fwsig, for host-side release tooling
+//!
+//! Build and install with `maturin develop` (or `maturin build --release` for wheels)
+//! from this directory. Errors are raised as `fwsig.ManifestError` or `fwsig.VerifyError`,
+//! with the error variant name available as the `kind` attribute.
+
+use std::str::FromStr;
+
+use encdec::{DecodeOwned, Encode};
+use pyo3::{
+    create_exception,
+    exceptions::{PyException, PyIOError, PyValueError},
+    prelude::*,
+    types::PyBytes,
+};
+use rand_core::OsRng;
+use strum::VariantNames;
+
+use fwsig::{Flags, Layout, MetadataFormat, SigMode};
+
+create_exception!(fwsig, ManifestError, PyException, "Manifest encoding, signing or verification error");
+create_exception!(fwsig, VerifyError, PyException, "Application or metadata verification error");
+
+/// Helper to raise a [fwsig::ManifestError] as a Python `ManifestError`
+fn manifest_err(e: fwsig::ManifestError) -> PyErr {
+    let kind = match e {
+        fwsig::ManifestError::Codec(_) => "Codec".to_string(),
+        _ => format!("{:?}", e),
+    };
+    new_err::<ManifestError>(e.to_string(), kind)
+}
+
+/// Helper to raise a [fwsig::VerifyError] as a Python `VerifyError`
+fn verify_err(e: fwsig::VerifyError) -> PyErr {
+    new_err::<VerifyError>(e.to_string(), format!("{:?}", e))
+}
+
+/// Helper to create an exception with the error message, setting the variant name as `kind`
+fn new_err<T: pyo3::PyTypeInfo>(msg: String, kind: String) -> PyErr {
+    Python::with_gil(|py| {
+        let err = PyErr::new::<T, _>(msg);
+        if let Err(e) = err.value(py).setattr("kind", kind) {
+            return e;
+        }
+        err
+    })
+}
+
+/// Helper to parse enumerations by (case-insensitive) name
+fn parse_enum<T: FromStr + VariantNames>(s: &str) -> PyResult<T> {
+    T::VARIANTS.iter()
+        .find(|v| v.eq_ignore_ascii_case(s))
+        .and_then(|v| T::from_str(v).ok())
+        .ok_or_else(|| PyValueError::new_err(format!("unrecognised value '{}', expected one of {:?}", s, T::VARIANTS)))
+}
+
+/// Ed25519 private (signing) key
+#[pyclass(module = "fwsig")]
+#[derive(Clone)]
+pub struct PrivateKey(fwsig::types::PrivateKey);
+
+#[pymethods]
+impl PrivateKey {
+    /// Generate a new random private key
+    #[staticmethod]
+    fn generate() -> Self {
+        Self(fwsig::types::PrivateKey::generate(&mut OsRng{}))
+    }
+
+    /// Load a private key from a hex string
+    #[staticmethod]
+    fn from_hex(s: &str) -> PyResult<Self> {
+        fwsig::types::PrivateKey::from_str(s.trim())
+            .map(Self)
+            .map_err(manifest_err)
+    }
+
+    /// Load a private key from a file containing a hex string
+    #[staticmethod]
+    fn load(path: &str) -> PyResult<Self> {
+        let s = std::fs::read_to_string(path)
+            .map_err(|e| PyIOError::new_err(e.to_string()))?;
+        Self::from_hex(&s)
+    }
+
+    /// Fetch the public key for this private key
+    fn public_key(&self) -> PublicKey {
+        PublicKey(fwsig::types::PublicKey::from(&self.0))
+    }
+
+    /// Encode the private key as a hex string
+    fn to_hex(&self) -> String {
+        self.0.to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("PrivateKey(public_key={})", self.public_key().0)
+    }
+}
+
+/// Ed25519 public (verifying) key
+#[pyclass(module = "fwsig", eq)]
+#[derive(Clone, PartialEq)]
+pub struct PublicKey(fwsig::types::PublicKey);
+
+#[pymethods]
+impl PublicKey {
+    /// Load a public key from a hex string
+    #[staticmethod]
+    fn from_hex(s: &str) -> PyResult<Self> {
+        fwsig::types::PublicKey::from_str(s.trim())
+            .map(Self)
+            .map_err(manifest_err)
+    }
+
+    /// Load a public key from a file containing a hex string
+    #[staticmethod]
+    fn load(path: &str) -> PyResult<Self> {
+        let s = std::fs::read_to_string(path)
+            .map_err(|e| PyIOError::new_err(e.to_string()))?;
+        Self::from_hex(&s)
+    }
+
+    /// Encode the public key as a hex string
+    fn to_hex(&self) -> String {
+        self.0.to_string()
+    }
+
+    /// Raw public key bytes
+    fn __bytes__<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, self.0.as_bytes())
+    }
+
+    fn __repr__(&self) -> String {
+        format!("PublicKey({})", self.0)
+    }
+}
+
+/// Builder for constructing signed manifests
+#[pyclass(module = "fwsig")]
+#[derive(Clone, Default)]
+pub struct ManifestBuilder(fwsig::ManifestBuilder);
+
+#[pymethods]
+impl ManifestBuilder {
+    #[new]
+    fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the application name
+    fn name<'a>(mut slf: PyRefMut<'a, Self>, name: &str) -> PyResult<PyRefMut<'a, Self>> {
+        slf.0.name(name).map_err(manifest_err)?;
+        Ok(slf)
+    }
+
+    /// Set the application version
+    fn version<'a>(mut slf: PyRefMut<'a, Self>, version: &str) -> PyResult<PyRefMut<'a, Self>> {
+        slf.0.version(version).map_err(manifest_err)?;
+        Ok(slf)
+    }
+
+    /// Set the manifest layout (`compact` or `large`)
+    fn layout<'a>(mut slf: PyRefMut<'a, Self>, layout: &str) -> PyResult<PyRefMut<'a, Self>> {
+        slf.0.layout(parse_enum::<Layout>(layout)?);
+        Ok(slf)
+    }
+
+    /// Set the signature mode (`legacy`, `ed25519ph` or `ed25519`)
+    fn sig_mode<'a>(mut slf: PyRefMut<'a, Self>, sig_mode: &str) -> PyResult<PyRefMut<'a, Self>> {
+        slf.0.sig_mode(parse_enum::<SigMode>(sig_mode)?);
+        Ok(slf)
+    }
+
+    /// Set manifest flags (see `fwsig::Flags`), the signature mode and metadata
+    /// alignment fields are set by `sig_mode` and `meta_align` when building
+    fn flags<'a>(mut slf: PyRefMut<'a, Self>, flags: u16) -> PyResult<PyRefMut<'a, Self>> {
+        let f = Flags::from_bits(flags)
+            .ok_or_else(|| PyValueError::new_err(format!("unrecognised flags 0x{:04x}", flags)))?;
+        slf.0.flags(f);
+        Ok(slf)
+    }
+
+    /// Set the metadata alignment (a power of two)
+    fn meta_align<'a>(mut slf: PyRefMut<'a, Self>, align: usize) -> PyResult<PyRefMut<'a, Self>> {
+        slf.0.meta_align(align).map_err(manifest_err)?;
+        Ok(slf)
+    }
+
+    /// Set the application binary
+    fn app_bin<'a>(mut slf: PyRefMut<'a, Self>, data: &[u8]) -> PyResult<PyRefMut<'a, Self>> {
+        slf.0.app_bin(data).map_err(manifest_err)?;
+        Ok(slf)
+    }
+
+    /// Set the application binary from a file
+    fn app_file<'a>(mut slf: PyRefMut<'a, Self>, path: &str) -> PyResult<PyRefMut<'a, Self>> {
+        slf.0.app_file(path).map_err(|e| PyIOError::new_err(e.to_string()))?;
+        Ok(slf)
+    }
+
+    /// Set the metadata kind (`binary`, `json`, `cbor` or `other`) and binary
+    fn meta_bin<'a>(mut slf: PyRefMut<'a, Self>, kind: &str, data: &[u8]) -> PyResult<PyRefMut<'a, Self>> {
+        slf.0.meta_bin(parse_enum::<MetadataFormat>(kind)?, data).map_err(manifest_err)?;
+        Ok(slf)
+    }
+
+    /// Set the metadata kind and binary from a file
+    fn meta_file<'a>(mut slf: PyRefMut<'a, Self>, kind: &str, path: &str) -> PyResult<PyRefMut<'a, Self>> {
+        slf.0.meta_file(parse_enum::<MetadataFormat>(kind)?, path).map_err(|e| PyIOError::new_err(e.to_string()))?;
+        Ok(slf)
+    }
+
+    /// Build and sign the manifest, using a transient key if none is provided
+    #[pyo3(signature = (key=None))]
+    fn build(&mut self, key: Option<PrivateKey>) -> PyResult<Manifest> {
+        self.0.build::<OsRng>(key.map(|k| k.0))
+            .map(Manifest)
+            .map_err(manifest_err)
+    }
+}
+
+/// Signed manifest
+#[pyclass(module = "fwsig", eq)]
+#[derive(Clone, PartialEq)]
+pub struct Manifest(fwsig::Manifest);
+
+#[pymethods]
+impl Manifest {
+    /// Decode a manifest from the start of the provided data
+    #[staticmethod]
+    fn decode(data: &[u8]) -> PyResult<Self> {
+        fwsig::Manifest::decode_owned(data)
+            .map(|(m, _n)| Self(m))
+            .map_err(manifest_err)
+    }
+
+    /// Decode a manifest trailing the provided data (ie. from an attached package),
+    /// returning the manifest and the offset of the manifest within the data
+    #[staticmethod]
+    fn decode_trailer(data: &[u8]) -> PyResult<(Self, usize)> {
+        fwsig::Manifest::decode_trailer(data)
+            .map(|(m, n)| (Self(m), n))
+            .map_err(manifest_err)
+    }
+
+    /// Encode the manifest
+    fn encode<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        let mut b = [0u8; fwsig::MANIFEST_MAX_LEN];
+        let n = self.0.encode(&mut b)
+            .map_err(manifest_err)?;
+        Ok(PyBytes::new(py, &b[..n]))
+    }
+
+    /// Check the application and metadata against the manifest, and that the manifest signature is valid
+    ///
+    /// Note this does not check the signing key is trusted, see `verify`
+    #[pyo3(signature = (app, meta=None))]
+    fn check(&self, app: &[u8], meta: Option<&[u8]>) -> PyResult<()> {
+        self.0.check(app, meta.unwrap_or_default()).map_err(verify_err)
+    }
+
+    /// Verify the manifest signature against a list of trusted public keys
+    fn verify(&self, keys: Vec<PublicKey>) -> PyResult<()> {
+        let keys: Vec<_> = keys.into_iter().map(|k| k.0).collect();
+        self.0.verify(&keys).map_err(manifest_err)
+    }
+
+    /// Manifest version
+    #[getter]
+    fn version(&self) -> u16 {
+        self.0.version
+    }
+
+    /// Manifest flags
+    #[getter]
+    fn flags(&self) -> u16 {
+        self.0.flags
+    }
+
+    /// Application name
+    #[getter]
+    fn app_name(&self) -> &str {
+        self.0.app_name()
+    }
+
+    /// Application version
+    #[getter]
+    fn app_version(&self) -> &str {
+        self.0.app_version()
+    }
+
+    /// Application length
+    #[getter]
+    fn app_len(&self) -> u64 {
+        self.0.app_len
+    }
+
+    /// Application checksum
+    #[getter]
+    fn app_csum<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.0.app_csum[..])
+    }
+
+    /// Metadata kind
+    #[getter]
+    fn meta_kind(&self) -> u16 {
+        self.0.meta_kind
+    }
+
+    /// Metadata length
+    #[getter]
+    fn meta_len(&self) -> u64 {
+        self.0.meta_len
+    }
+
+    /// Metadata checksum
+    #[getter]
+    fn meta_csum<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.0.meta_csum[..])
+    }
+
+    /// Signing public key
+    #[getter]
+    fn key(&self) -> PublicKey {
+        PublicKey(self.0.key.clone())
+    }
+
+    /// Manifest signature
+    #[getter]
+    fn sig<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.0.sig[..])
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Manifest(app_name='{}', app_version='{}', app_len={}, meta_len={}, key={})",
+            self.0.app_name(), self.0.app_version(), self.0.app_len, self.0.meta_len, self.0.key)
+    }
+}
+
+/// fwsig Python module
+#[pymodule]
+#[pyo3(name = "fwsig")]
+fn fwsig_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PrivateKey>()?;
+    m.add_class::<PublicKey>()?;
+    m.add_class::<ManifestBuilder>()?;
+    m.add_class::<Manifest>()?;
+
+    m.add("ManifestError", m.py().get_type::<ManifestError>())?;
+    m.add("VerifyError", m.py().get_type::<VerifyError>())?;
+
+    Ok(())
+}
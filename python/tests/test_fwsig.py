@@ -0,0 +1,85 @@
+"""Tests for the fwsig Python bindings
+
+Run with `python -m unittest discover tests` after `maturin develop`
+"""
+
+import unittest
+
+import fwsig
+
+
+APP = bytes(range(256)) * 4
+META = b'{"device": "test"}'
+
+
+def build(key=None, **kwargs):
+    b = fwsig.ManifestBuilder().name("test").version("1.2.3")
+    for k, v in kwargs.items():
+        getattr(b, k)(v)
+    return b.app_bin(APP).meta_bin("json", META).build(key)
+
+
+class TestKeys(unittest.TestCase):
+    def test_hex_round_trip(self):
+        key = fwsig.PrivateKey.generate()
+        self.assertEqual(fwsig.PrivateKey.from_hex(key.to_hex()).public_key(), key.public_key())
+
+        pub = key.public_key()
+        self.assertEqual(fwsig.PublicKey.from_hex(pub.to_hex()), pub)
+        self.assertEqual(len(bytes(pub)), 32)
+
+    def test_invalid_hex(self):
+        with self.assertRaises(fwsig.ManifestError) as e:
+            fwsig.PublicKey.from_hex("not hex")
+        self.assertEqual(e.exception.kind, "InvalidHex")
+        self.assertEqual(str(e.exception), "Hex encode/decode failed")
+
+
+class TestManifest(unittest.TestCase):
+    def test_build_encode_decode(self):
+        key = fwsig.PrivateKey.generate()
+        m = build(key)
+
+        self.assertEqual(m.app_name, "test")
+        self.assertEqual(m.app_version, "1.2.3")
+        self.assertEqual(m.app_len, len(APP))
+        self.assertEqual(m.meta_len, len(META))
+        self.assertEqual(m.key, key.public_key())
+
+        d = m.encode()
+        self.assertEqual(fwsig.Manifest.decode(d), m)
+        self.assertEqual(fwsig.Manifest.decode_trailer(APP + META + d), (m, len(APP) + len(META)))
+
+    def test_check(self):
+        m = build(sig_mode="ed25519", layout="large")
+        m.check(APP, META)
+
+        with self.assertRaises(fwsig.VerifyError) as e:
+            m.check(APP[1:], META)
+        self.assertEqual(e.exception.kind, "AppLengthMismatch")
+        self.assertEqual(str(e.exception), "app length mismatch")
+
+        with self.assertRaises(fwsig.VerifyError) as e:
+            m.check(APP, META[:-1] + b"!")
+        self.assertEqual(e.exception.kind, "MetaChecksumMismatch")
+
+    def test_verify(self):
+        key = fwsig.PrivateKey.generate()
+        m = build(key)
+        m.verify([key.public_key()])
+
+        with self.assertRaises(fwsig.ManifestError) as e:
+            m.verify([fwsig.PrivateKey.generate().public_key()])
+        self.assertEqual(e.exception.kind, "NoMatchingKey")
+
+    def test_invalid_arguments(self):
+        with self.assertRaises(ValueError):
+            build(layout="huge")
+
+        with self.assertRaises(fwsig.ManifestError) as e:
+            fwsig.ManifestBuilder().name("a very long application name")
+        self.assertEqual(e.exception.kind, "StringTooLong")
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,296 @@
+//! Offline multi-party approval using signing requests
+//!
+//! A [SigningRequest] holds an unsigned manifest (containing the app and metadata checksums)
+//! for the release signer key. Approvers review the request and add approvals offline
+//! ([SigningRequest::approve]), signatures over the manifest digest with the [APPROVAL_CONTEXT]
+//! so these cannot be used as manifest signatures.
+//!
+//! The release signer finalizes the request ([SigningRequest::finalize]), providing the approver
+//! keys and threshold so these are not taken from the (unsigned) request, with the manifest signed
+//! only once the quorum is met. As devices verify only the manifest signature the quorum is
+//! enforced when finalizing, with the completed request retained as a record of the approvals.
+//!
+//! Requests are encoded as JSON for passing between approvers.
+
+use encdec::{DecodeOwned, EncodeExt};
+use rand_core::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    types::{PrivateKey, PublicKey, Signature},
+    ApprovalError, Flags, Manifest, MANIFEST_MAX_LEN,
+};
+
+/// Approval signature context, separating approvals from manifest signatures
+pub const APPROVAL_CONTEXT: &[u8] = b"fwsig-approval-v1";
+
+/// Approval message length, [APPROVAL_CONTEXT] followed by the manifest digest
+pub const APPROVAL_MESSAGE_LEN: usize = APPROVAL_CONTEXT.len() + 64;
+
+/// Approval signature over a requested manifest
+#[derive(Clone, Debug, PartialEq)]
+pub struct Approval {
+    /// Approver key
+    pub key: PublicKey,
+    /// Signature over the approval message (see [Approval::message])
+    pub sig: Signature,
+}
+
+impl Approval {
+    /// Sign an approval for the manifest with the approver key
+    pub fn sign(m: &Manifest, key: &PrivateKey) -> Result<Self, ApprovalError> {
+        let public_key = ed25519_dalek::PublicKey::from(&**key);
+        let keys = ed25519_dalek::Keypair{ public: public_key, secret: key.clone().inner() };
+
+        let sig = ed25519_dalek::Signer::try_sign(&keys, &Self::message(m)?)
+            .map_err(|_e| ApprovalError::InvalidApproval)?;
+
+        Ok(Self { key: PublicKey::from(public_key), sig: Signature::from(sig) })
+    }
+
+    /// Fetch the approval message, [APPROVAL_CONTEXT] followed by the manifest digest ([Manifest::signed_digest])
+    pub fn message(m: &Manifest) -> Result<[u8; APPROVAL_MESSAGE_LEN], ApprovalError> {
+        let mut b = [0u8; APPROVAL_MESSAGE_LEN];
+        b[..APPROVAL_CONTEXT.len()].copy_from_slice(APPROVAL_CONTEXT);
+        b[APPROVAL_CONTEXT.len()..].copy_from_slice(&m.signed_digest()?);
+        Ok(b)
+    }
+
+    /// Verify the approval signature over the manifest
+    pub fn verify(&self, m: &Manifest) -> Result<(), ApprovalError> {
+        let sig = ed25519_dalek::Signature::try_from(&self.sig)
+            .map_err(|_e| ApprovalError::InvalidApproval)?;
+
+        ed25519_dalek::Verifier::verify(&*self.key, &Self::message(m)?, &sig)
+            .map_err(|_e| ApprovalError::InvalidApproval)
+    }
+}
+
+/// Signing request, an unsigned manifest awaiting approval
+#[derive(Clone, Debug, PartialEq)]
+pub struct SigningRequest {
+    manifest: Manifest,
+    approvals: Vec<Approval>,
+}
+
+/// Encoded signing request, app and metadata fields are informational for reviewers
+/// and must match the encoded manifest
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct RequestFile {
+    /// Encoded unsigned manifest (hex)
+    manifest: String,
+    app_name: String,
+    app_version: String,
+    app_len: u64,
+    app_csum: String,
+    meta_len: u64,
+    meta_csum: String,
+    /// Approvals received
+    approvals: Vec<ApprovalFile>,
+}
+
+/// Encoded approval
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct ApprovalFile {
+    /// Approver key (hex)
+    key: String,
+    /// Signature (hex)
+    sig: String,
+}
+
+impl SigningRequest {
+    /// Create a signing request for an unsigned manifest (see [crate::ManifestBuilder::build_unsigned]),
+    /// where the manifest key is the release signer
+    pub fn new(manifest: Manifest) -> Result<Self, ApprovalError> {
+        if Flags::from_bits(manifest.flags).is_none() {
+            return Err(ApprovalError::Manifest(crate::ManifestError::UnsupportedFlags));
+        }
+
+        Ok(Self { manifest, approvals: vec![] })
+    }
+
+    /// Fetch the requested (unsigned) manifest
+    pub fn manifest(&self) -> &Manifest {
+        &self.manifest
+    }
+
+    /// Fetch approvals received
+    pub fn approvals(&self) -> &[Approval] {
+        &self.approvals
+    }
+
+    /// Check whether the request has been approved by the provided key
+    pub fn is_approved_by(&self, key: &PublicKey) -> bool {
+        self.approvals.iter().any(|a| &a.key == key)
+    }
+
+    /// Approve the request, signing an approval with the approver key
+    ///
+    /// The release signer approves by finalizing the request, see [SigningRequest::finalize]
+    pub fn approve(&mut self, key: &PrivateKey) -> Result<&Approval, ApprovalError> {
+        self.add_approval(Approval::sign(&self.manifest, key)?)?;
+
+        Ok(&self.approvals[self.approvals.len() - 1])
+    }
+
+    /// Add an approval, checking the approval signature is valid
+    pub fn add_approval(&mut self, a: Approval) -> Result<(), ApprovalError> {
+        if a.key == self.manifest.key {
+            return Err(ApprovalError::SignerApproval);
+        }
+        if self.is_approved_by(&a.key) {
+            return Err(ApprovalError::AlreadyApproved);
+        }
+
+        a.verify(&self.manifest)?;
+
+        self.approvals.push(a);
+
+        Ok(())
+    }
+
+    /// Count approvals from the provided approver keys, including the release signer
+    /// where this is an approver
+    pub fn count_approvals(&self, approvers: &[PublicKey]) -> usize {
+        let signer = approvers.contains(&self.manifest.key) as usize;
+        signer + self.approvals.iter().filter(|a| approvers.contains(&a.key)).count()
+    }
+
+    /// Complete the request, signing the manifest with the release signer key once `threshold`
+    /// approvals have been received from `approvers`
+    ///
+    /// Approver keys and threshold are provided by the release signer rather than the request.
+    /// The release signer must be one of the approvers and counts towards the threshold.
+    pub fn finalize<RNG: CryptoRng + RngCore + Default>(&self, signer: PrivateKey, approvers: &[PublicKey], threshold: usize) -> Result<Manifest, ApprovalError> {
+        if threshold == 0 || threshold > approvers.len() {
+            return Err(ApprovalError::InvalidThreshold);
+        }
+        if approvers.iter().enumerate().any(|(i, k)| approvers[..i].contains(k)) {
+            return Err(ApprovalError::DuplicateApprover);
+        }
+        if PublicKey::from(&signer) != self.manifest.key {
+            return Err(ApprovalError::SignerMismatch);
+        }
+        if !approvers.contains(&self.manifest.key) {
+            return Err(ApprovalError::SignerNotApprover);
+        }
+
+        let n = self.count_approvals(approvers);
+        if n < threshold {
+            return Err(ApprovalError::QuorumNotMet(n, threshold));
+        }
+
+        let mut m = self.manifest.clone();
+        m.sign::<RNG>(signer)?;
+        m.verify(core::slice::from_ref(&self.manifest.key))?;
+
+        Ok(m)
+    }
+
+    /// Encode the request to JSON
+    pub fn encode(&self) -> Result<String, ApprovalError> {
+        let (b, n) = self.manifest.encode_buff::<MANIFEST_MAX_LEN>()?;
+        let m = &self.manifest;
+
+        let f = RequestFile {
+            manifest: hex::encode(&b[..n]),
+            app_name: m.app_name().to_string(),
+            app_version: m.app_version().to_string(),
+            app_len: m.app_len,
+            app_csum: m.app_csum.to_string(),
+            meta_len: m.meta_len,
+            meta_csum: m.meta_csum.to_string(),
+            approvals: self.approvals.iter()
+                .map(|a| ApprovalFile { key: a.key.to_string(), sig: a.sig.to_string() })
+                .collect(),
+        };
+
+        serde_json::to_string_pretty(&f)
+            .map_err(|_e| ApprovalError::InvalidRequest)
+    }
+
+    /// Decode a request from JSON, checking the request and any approvals
+    pub fn decode(s: &str) -> Result<Self, ApprovalError> {
+        let f: RequestFile = serde_json::from_str(s)
+            .map_err(|_e| ApprovalError::InvalidRequest)?;
+
+        // Decode manifest and check informational fields match
+        let b = hex::decode(&f.manifest)
+            .map_err(|_e| ApprovalError::InvalidRequest)?;
+        let (m, n) = Manifest::decode_owned(&b)?;
+        if n != b.len() || f.app_name != m.app_name() || f.app_version != m.app_version()
+                || f.app_len != m.app_len || f.app_csum != m.app_csum.to_string()
+                || f.meta_len != m.meta_len || f.meta_csum != m.meta_csum.to_string() {
+            return Err(ApprovalError::InvalidRequest);
+        }
+
+        let mut r = Self::new(m)?;
+
+        // Re-check approvals
+        for a in &f.approvals {
+            let mut sig = Signature::empty();
+            hex::decode_to_slice(&a.sig, &mut sig.0)
+                .map_err(|_e| ApprovalError::InvalidApproval)?;
+
+            r.add_approval(Approval { key: a.key.parse()?, sig })?;
+        }
+
+        Ok(r)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::OsRng;
+
+    use crate::test_utils::{builder, APP};
+
+    use super::*;
+
+    #[test]
+    fn approve_finalize() {
+        let keys: Vec<_> = (0..3).map(|_| PrivateKey::generate(&mut OsRng{})).collect();
+        let approvers: Vec<_> = keys.iter().map(PublicKey::from).collect();
+        let other = PrivateKey::generate(&mut OsRng{});
+
+        let m = builder(&[]).name("app").unwrap()
+            .build_unsigned(approvers[0].clone()).unwrap();
+
+        let mut r = SigningRequest::new(m).unwrap();
+
+        // Approvals are once per key, the release signer approves when finalizing
+        r.approve(&keys[1]).unwrap();
+        assert_eq!(r.approve(&keys[1]).unwrap_err(), ApprovalError::AlreadyApproved);
+        assert_eq!(r.approve(&keys[0]).unwrap_err(), ApprovalError::SignerApproval);
+
+        // Approvals are not valid manifest signatures
+        let mut m1 = r.manifest().clone();
+        m1.sig = r.approvals()[0].sig.clone();
+        assert!(m1.verify_signature(&approvers[1], &m1.sig).is_err());
+
+        // Approver keys and threshold are checked when finalizing
+        assert_eq!(r.finalize::<OsRng>(keys[0].clone(), &approvers[1..], 1), Err(ApprovalError::SignerNotApprover));
+        assert_eq!(r.finalize::<OsRng>(keys[0].clone(), &approvers, 4), Err(ApprovalError::InvalidThreshold));
+        assert_eq!(r.finalize::<OsRng>(keys[0].clone(), &[approvers[0].clone(), approvers[0].clone()], 1), Err(ApprovalError::DuplicateApprover));
+        assert_eq!(r.finalize::<OsRng>(keys[1].clone(), &approvers, 2), Err(ApprovalError::SignerMismatch));
+        assert_eq!(r.finalize::<OsRng>(keys[0].clone(), &approvers, 3), Err(ApprovalError::QuorumNotMet(2, 3)));
+
+        // Approvals from keys outside the approver set are not counted
+        r.approve(&other).unwrap();
+        assert_eq!(r.count_approvals(&approvers), 2);
+        assert_eq!(r.finalize::<OsRng>(keys[0].clone(), &[approvers[0].clone(), approvers[2].clone()], 2), Err(ApprovalError::QuorumNotMet(1, 2)));
+
+        // Approvals are retained through encoding
+        let r = SigningRequest::decode(&r.encode().unwrap()).unwrap();
+        assert_eq!(r.approvals().len(), 2);
+
+        let m = r.finalize::<OsRng>(keys[0].clone(), &approvers, 2).unwrap();
+        m.verify(&approvers[..1]).unwrap();
+        assert!(m.check(&APP, &[]).is_ok());
+
+        // Invalid approvals are rejected on decode
+        let s = r.encode().unwrap();
+        let bad = s.replace(&r.approvals()[0].sig.to_string(), &r.approvals()[1].sig.to_string());
+        assert_eq!(SigningRequest::decode(&bad), Err(ApprovalError::InvalidApproval));
+    }
+}
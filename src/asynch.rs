@@ -0,0 +1,240 @@
+//! Async streaming verification over [embedded_io_async::Read]
+//!
+//! This is `no_std` compatible and does not allocate, with data streamed through a
+//! caller-provided buffer, for firmware receiving updates asynchronously (eg. under Embassy).
+//! Empty buffers fail with [ManifestError::InvalidAlignment].
+//! Host applications using tokio may wrap readers with [FromTokio] (`async-tokio` feature).
+//!
+//! As readers cannot seek, attached packages must use the header layout ([Flags::HEADER])
+//! so the manifest is available before the app and metadata. Packages with a trailing
+//! manifest may be checked with [check] once the manifest has been obtained.
+
+use embedded_io_async::{Read, ReadExactError};
+use encdec::DecodeOwned;
+use sha2::{Digest, Sha512Trunc256};
+
+use crate::{
+    blocks::AppHasher,
+    types::Checksum,
+    Flags, Layout, Manifest, ManifestError, StreamError, VerifyError, MANIFEST_LEN, MANIFEST_MAX_LEN,
+};
+
+/// Check application and metadata against the manifest, reading exactly the manifest
+/// lengths from each reader through the provided buffer
+///
+/// Readers ending early fail with [VerifyError::AppLengthMismatch] or [VerifyError::MetaLengthMismatch],
+/// any following data is not read.
+pub async fn check<A: Read, M: Read<Error = A::Error>>(m: &Manifest, app: &mut A, meta: &mut M, buff: &mut [u8]) -> Result<(), StreamError<A::Error>> {
+    // Check the signature before streaming
+    m.check_sig()?;
+
+    let app_csum = hash_app(m, app, buff).await?;
+    let meta_csum = hash_meta(m, meta, buff).await?;
+
+    Ok(m.check_precomputed(&app_csum, m.app_len(), &meta_csum, m.meta_len())?)
+}
+
+/// Read a manifest from the start of a reader, returning the manifest and encoded length
+pub async fn read_manifest<R: Read>(r: &mut R) -> Result<(Manifest, usize), StreamError<R::Error>> {
+    let mut b = [0u8; MANIFEST_MAX_LEN];
+
+    // Read the compact length, then the remainder for larger layouts
+    read_exact(r, &mut b[..MANIFEST_LEN], ManifestError::Codec(encdec::Error::Length)).await?;
+
+    let n = Layout::from_version(u16::from_le_bytes([b[0], b[1]]))?.encoded_len();
+    read_exact(r, &mut b[MANIFEST_LEN..n], ManifestError::Codec(encdec::Error::Length)).await?;
+
+    Ok(Manifest::decode_owned(&b[..n])?)
+}
+
+/// Check an attached package using the header layout ([Flags::HEADER]), reading the manifest
+/// then streaming the app and metadata through the provided buffer
+///
+/// Packages without a header manifest fail with [ManifestError::UnsupportedFlags], padding or
+/// other data following the metadata is not read. Note this does not check the signing key
+/// is trusted, see [Manifest::verify]
+pub async fn check_package<R: Read>(r: &mut R, buff: &mut [u8]) -> Result<Manifest, StreamError<R::Error>> {
+    let (m, _n) = read_manifest(r).await?;
+    if !m.flags().contains(Flags::HEADER) {
+        return Err(ManifestError::UnsupportedFlags.into());
+    }
+    m.check_sig()?;
+
+    let app_csum = hash_app(&m, r, buff).await?;
+
+    // Skip metadata alignment padding
+    let pad = m.checked_meta_offset()? as u64 - m.app_len;
+    stream(r, pad, buff, VerifyError::MetaLengthMismatch, |_d| ()).await?;
+
+    let meta_csum = hash_meta(&m, r, buff).await?;
+
+    m.check_precomputed(&app_csum, m.app_len(), &meta_csum, m.meta_len())?;
+
+    Ok(m)
+}
+
+/// Internal helper to compute the app checksum over `app_len` bytes
+async fn hash_app<R: Read>(m: &Manifest, r: &mut R, buff: &mut [u8]) -> Result<Checksum, StreamError<R::Error>> {
+    let mut h = AppHasher::new(m);
+    stream(r, m.app_len, buff, VerifyError::AppLengthMismatch, |d| h.update(d)).await?;
+
+    Ok(h.finalize())
+}
+
+/// Internal helper to compute the metadata checksum over `meta_len` bytes
+async fn hash_meta<R: Read>(m: &Manifest, r: &mut R, buff: &mut [u8]) -> Result<Checksum, StreamError<R::Error>> {
+    let mut h = Sha512Trunc256::new();
+    stream(r, m.meta_len, buff, VerifyError::MetaLengthMismatch, |d| h.update(d)).await?;
+
+    Ok(Checksum::from(h.finalize()))
+}
+
+/// Internal helper to stream exactly `n` bytes through the buffer
+async fn stream<R: Read>(r: &mut R, mut n: u64, buff: &mut [u8], eof: VerifyError, mut f: impl FnMut(&[u8])) -> Result<(), StreamError<R::Error>> {
+    // Empty buffers are a caller error, distinct from truncated data
+    if buff.is_empty() && n > 0 {
+        return Err(ManifestError::InvalidAlignment.into());
+    }
+
+    while n > 0 {
+        let len = (buff.len() as u64).min(n) as usize;
+        read_exact(r, &mut buff[..len], eof).await?;
+
+        f(&buff[..len]);
+        n -= len as u64;
+    }
+
+    Ok(())
+}
+
+/// Internal helper to read exactly the buffer length, mapping EOF to the provided error
+async fn read_exact<R: Read, E: Into<StreamError<R::Error>>>(r: &mut R, b: &mut [u8], eof: E) -> Result<(), StreamError<R::Error>> {
+    match r.read_exact(b).await {
+        Ok(()) => Ok(()),
+        Err(ReadExactError::UnexpectedEof) => Err(eof.into()),
+        Err(ReadExactError::Other(e)) => Err(StreamError::Io(e)),
+    }
+}
+
+#[cfg(feature = "async-tokio")]
+pub use self::tokio_io::FromTokio;
+
+#[cfg(feature = "async-tokio")]
+mod tokio_io {
+    use tokio::io::{AsyncRead, AsyncReadExt};
+
+    /// Adapter implementing [embedded_io_async::Read] over a tokio [AsyncRead]
+    #[derive(Debug)]
+    pub struct FromTokio<R>(R);
+
+    impl<R: AsyncRead + Unpin> FromTokio<R> {
+        /// Wrap a tokio reader
+        pub fn new(inner: R) -> Self {
+            Self(inner)
+        }
+
+        /// Fetch the inner reader
+        pub fn into_inner(self) -> R {
+            self.0
+        }
+    }
+
+    impl<R> embedded_io_async::ErrorType for FromTokio<R> {
+        type Error = std::io::Error;
+    }
+
+    impl<R: AsyncRead + Unpin> embedded_io_async::Read for FromTokio<R> {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            self.0.read(buf).await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::OsRng;
+
+    use crate::{ManifestBuilder, MetadataFormat, Package, PackageOpts};
+
+    use super::*;
+
+    /// Reader over a slice returning short reads, as from a network or serial stream
+    struct Chunked<'a>(&'a [u8]);
+
+    impl embedded_io_async::ErrorType for Chunked<'_> {
+        type Error = core::convert::Infallible;
+    }
+
+    impl Read for Chunked<'_> {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            let n = buf.len().min(self.0.len()).min(300);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    #[tokio::test]
+    async fn check_stream() {
+        let app: Vec<u8> = (0..10_000u32).map(|i| (i * 5) as u8).collect();
+        let meta = [0xcd; 10];
+        let mut buff = [0u8; 512];
+
+        for block_size in [None, Some(1024)] {
+            let mut b = ManifestBuilder::new();
+            b.flags(Flags::HEADER).meta_align(64).unwrap();
+            if let Some(n) = block_size {
+                b.block_size(n).unwrap();
+            }
+            let m = b.app_bin(&app).unwrap()
+                .meta_bin(MetadataFormat::Binary, &meta).unwrap()
+                .build::<OsRng>(None).unwrap();
+
+            // Detached components
+            check(&m, &mut Chunked(&app), &mut Chunked(&meta), &mut buff).await.unwrap();
+            assert_eq!(check(&m, &mut Chunked(&app[1..]), &mut Chunked(&meta), &mut buff).await,
+                Err(StreamError::Verify(VerifyError::AppLengthMismatch)));
+            assert_eq!(check(&m, &mut Chunked(&app), &mut Chunked(&app[..10]), &mut buff).await,
+                Err(StreamError::Verify(VerifyError::MetaChecksumMismatch)));
+            assert_eq!(check(&m, &mut Chunked(&app), &mut Chunked(&meta), &mut []).await,
+                Err(StreamError::Manifest(ManifestError::InvalidAlignment)));
+
+            // Header packages, including slot padding
+            let opts = PackageOpts{ fill: 0xFF, slot_size: Some(16 * 1024) };
+            let p = Package::encode(&app, &meta, &m, &opts).unwrap();
+            assert_eq!(check_package(&mut Chunked(&p), &mut buff).await, Ok(m.clone()));
+
+            let mut p1 = p.clone();
+            p1[5000] ^= 0x01;
+            assert_eq!(check_package(&mut Chunked(&p1), &mut buff).await,
+                Err(StreamError::Verify(VerifyError::AppChecksumMismatch)));
+            assert_eq!(check_package(&mut Chunked(&p[..1000]), &mut buff).await,
+                Err(StreamError::Verify(VerifyError::AppLengthMismatch)));
+        }
+
+        // Trailing manifests are not supported
+        let m = ManifestBuilder::new()
+            .app_bin(&app).unwrap()
+            .meta_bin(MetadataFormat::Binary, &meta).unwrap()
+            .build::<OsRng>(None).unwrap();
+        let p = Package::encode(&app, &meta, &m, &PackageOpts::default()).unwrap();
+        assert!(check_package(&mut Chunked(&p), &mut buff).await.is_err());
+    }
+
+    #[cfg(feature = "async-tokio")]
+    #[tokio::test]
+    async fn check_tokio() {
+        let app = [0xab; 3000];
+        let m = ManifestBuilder::new()
+            .flags(Flags::HEADER)
+            .app_bin(&app).unwrap()
+            .meta_bin(MetadataFormat::Binary, &[]).unwrap()
+            .build::<OsRng>(None).unwrap();
+
+        let p = Package::encode(&app, &[], &m, &PackageOpts::default()).unwrap();
+        let mut buff = [0u8; 1024];
+
+        let mut r = FromTokio::new(&p[..]);
+        assert_eq!(check_package(&mut r, &mut buff).await.unwrap(), m);
+    }
+}
@@ -0,0 +1,248 @@
+//! Tamper-evident signing audit log
+//!
+//! Each signature is recorded as a JSON line holding the signing time, key ID,
+//! application name and version, checksums, manifest digest, and the hash of the
+//! previous line, so edits or removals break the chain from that point.
+//!
+//! As truncating the end of the log leaves a valid chain, the head hash reported by
+//! [verify] should be recorded externally (eg. in release notes or CI output) and provided
+//! to later verifications to check the log still contains it.
+//!
+//! Logs should have a single writer, entries appended concurrently will break the chain.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use rand_core::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512Trunc256};
+
+use crate::{
+    types::{PrivateKey, PublicKey},
+    AuditError, Manifest, ManifestError,
+};
+
+/// Previous entry hash for the first entry in a log
+pub const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Audit log entry
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    /// Entry sequence number, from zero
+    pub seq: u64,
+    /// Signing time (seconds since the unix epoch)
+    pub timestamp: u64,
+    /// Signing key ID (public key, hex)
+    pub key_id: String,
+    /// Application name
+    pub app_name: String,
+    /// Application version
+    pub app_version: String,
+    /// Application checksum (hex)
+    pub app_csum: String,
+    /// Metadata checksum (hex)
+    pub meta_csum: String,
+    /// Manifest digest, over all fields other than the signature (hex, see [Manifest::signed_digest])
+    pub digest: String,
+    /// Hash of the previous entry line (hex, see [entry_hash])
+    pub prev: String,
+}
+
+impl Entry {
+    /// Create an entry for a signed manifest
+    pub fn new(m: &Manifest, seq: u64, timestamp: u64, prev: &str) -> Result<Self, AuditError> {
+        Ok(Self {
+            seq,
+            timestamp,
+            key_id: m.key.to_string(),
+            app_name: m.app_name().to_string(),
+            app_version: m.app_version().to_string(),
+            app_csum: m.app_csum.to_string(),
+            meta_csum: m.meta_csum.to_string(),
+            digest: hex::encode(m.signed_digest()?),
+            prev: prev.to_string(),
+        })
+    }
+}
+
+/// Compute the hash of an encoded entry line (excluding the line ending)
+pub fn entry_hash(line: &str) -> String {
+    hex::encode(Sha512Trunc256::digest(line.as_bytes()))
+}
+
+/// Audit log summary
+#[derive(Clone, Debug, PartialEq)]
+pub struct Summary {
+    /// Number of entries
+    pub entries: u64,
+    /// Hash of the last entry line ([GENESIS] for empty logs)
+    pub head: String,
+}
+
+/// Append-only audit log file
+#[derive(Debug)]
+pub struct AuditLog {
+    path: PathBuf,
+    summary: Summary,
+}
+
+impl AuditLog {
+    /// Open an audit log, creating this if it does not exist
+    ///
+    /// Existing logs are verified before use, so entries are not appended to broken chains
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, AuditError> {
+        let path = path.as_ref().to_path_buf();
+
+        let summary = match path.exists() {
+            true => verify(&path, None)?,
+            false => Summary { entries: 0, head: GENESIS.to_string() },
+        };
+
+        Ok(Self { path, summary })
+    }
+
+    /// Fetch the current log summary
+    pub fn summary(&self) -> &Summary {
+        &self.summary
+    }
+
+    /// Append an entry for a signed manifest, checking the signature against the manifest key
+    pub fn append(&mut self, m: &Manifest) -> Result<Entry, AuditError> {
+        m.verify(core::slice::from_ref(&m.key))?;
+
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        let e = Entry::new(m, self.summary.entries, timestamp, &self.summary.head)?;
+        let line = serde_json::to_string(&e)
+            .map_err(|_e| AuditError::Io)?;
+
+        let mut f = OpenOptions::new().create(true).append(true).open(&self.path)
+            .map_err(|_e| AuditError::Io)?;
+        writeln!(f, "{}", line)
+            .and_then(|_| f.sync_data())
+            .map_err(|_e| AuditError::Io)?;
+
+        self.summary = Summary { entries: e.seq + 1, head: entry_hash(&line) };
+
+        Ok(e)
+    }
+
+    /// Sign a manifest (see [Manifest::sign]) and append an entry for this
+    ///
+    /// The signing key must match the manifest key, otherwise this fails with [ManifestError::NoMatchingKey]
+    pub fn sign<RNG: CryptoRng + RngCore + Default>(&mut self, m: &mut Manifest, key: PrivateKey) -> Result<Entry, AuditError> {
+        if PublicKey::from(&key) != m.key {
+            return Err(ManifestError::NoMatchingKey.into());
+        }
+
+        m.sign::<RNG>(key)?;
+        self.append(m)
+    }
+}
+
+/// Verify an audit log file, see [verify_reader]
+pub fn verify(path: impl AsRef<Path>, head: Option<&str>) -> Result<Summary, AuditError> {
+    let f = File::open(path)
+        .map_err(|_e| AuditError::Io)?;
+
+    verify_reader(BufReader::new(f), head)
+}
+
+/// Verify an audit log, checking entry sequence numbers and the hash chain,
+/// and (if provided) that the log contains an entry with the expected `head` hash
+pub fn verify_reader(r: impl BufRead, head: Option<&str>) -> Result<Summary, AuditError> {
+    let mut summary = Summary { entries: 0, head: GENESIS.to_string() };
+    let mut found = head.is_none() || head == Some(GENESIS);
+
+    for (i, line) in r.lines().enumerate() {
+        let n = i + 1;
+        let line = line.map_err(|_e| AuditError::Io)?;
+
+        let e: Entry = serde_json::from_str(&line)
+            .map_err(|_e| AuditError::InvalidEntry(n))?;
+
+        if e.seq != summary.entries {
+            return Err(AuditError::SequenceMismatch(n));
+        }
+        if e.prev != summary.head {
+            return Err(AuditError::ChainMismatch(n));
+        }
+
+        summary = Summary { entries: e.seq + 1, head: entry_hash(&line) };
+        found |= head == Some(summary.head.as_str());
+    }
+
+    match found {
+        true => Ok(summary),
+        false => Err(AuditError::HeadNotFound),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::OsRng;
+
+    use crate::{test_utils::{manifest, temp_path}, ManifestBuilder, MetadataFormat};
+
+    use super::*;
+
+    #[test]
+    fn append_verify() {
+        let path = temp_path("audit");
+        let _ = std::fs::remove_file(&path);
+
+        let key = PrivateKey::generate(&mut OsRng{});
+        let mut log = AuditLog::open(&path).unwrap();
+        let mut heads = vec![];
+
+        for i in 0..3u8 {
+            let mut m = ManifestBuilder::new()
+                .name("app").unwrap()
+                .app_bin(&[i; 100]).unwrap()
+                .meta_bin(MetadataFormat::Binary, &[]).unwrap()
+                .build_unsigned(PublicKey::from(&key)).unwrap();
+
+            let e = log.sign::<OsRng>(&mut m, key.clone()).unwrap();
+            m.verify(&[PublicKey::from(&key)]).unwrap();
+            assert_eq!(e.seq, i as u64);
+            assert_eq!(e.key_id, m.key.to_string());
+            assert_eq!(e.digest, hex::encode(m.signed_digest().unwrap()));
+            heads.push(log.summary().head.clone());
+        }
+
+        // Signing keys must match the manifest key, and entries must be validly signed
+        let mut m = manifest(None, &[]);
+        assert_eq!(log.sign::<OsRng>(&mut m.clone(), key.clone()), Err(AuditError::Manifest(ManifestError::NoMatchingKey)));
+        m.sig = crate::types::Signature::empty();
+        assert!(log.append(&m).is_err());
+
+        // Re-opening continues the chain
+        let log = AuditLog::open(&path).unwrap();
+        assert_eq!(log.summary(), &Summary { entries: 3, head: heads[2].clone() });
+        assert_eq!(verify(&path, Some(&heads[1])).unwrap().entries, 3);
+
+        let data = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<_> = data.lines().collect();
+        let check = |lines: &[&str], head: Option<&str>| verify_reader(lines.join("\n").as_bytes(), head);
+
+        // Edits break the chain
+        let edited = lines[1].replace("\"app\"", "\"other\"");
+        assert_eq!(check(&[lines[0], &edited, lines[2]], None), Err(AuditError::ChainMismatch(3)));
+
+        // Removals break the sequence
+        assert_eq!(check(&[lines[0], lines[2]], None), Err(AuditError::SequenceMismatch(2)));
+        assert_eq!(check(&[lines[1], lines[2]], None), Err(AuditError::SequenceMismatch(1)));
+
+        // Truncation is detected against a recorded head
+        assert_eq!(check(&lines[..2], None).unwrap().entries, 2);
+        assert_eq!(check(&lines[..2], Some(&heads[2])), Err(AuditError::HeadNotFound));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
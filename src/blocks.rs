@@ -0,0 +1,386 @@
+//! Block hashing, for partial and resumable application verification
+//!
+//! Where the manifest [Flags::BLOCK_SIZE](crate::Flags::BLOCK_SIZE) field is set the
+//! application checksum is a Merkle root over fixed-size application blocks (the final
+//! block may be shorter), with leaf and node hashes as in [crate::tlog].
+//!
+//! Packages carry a [BlockTable] of block hashes immediately after the metadata, which
+//! is checked against the signed root so individual blocks may then be verified as they
+//! arrive ([BlockVerifier]), and corrupt blocks re-fetched rather than the whole image.
+//! The table is not signed, but the block size is committed by the root as leaves
+//! cover the whole application.
+
+use core::ops::Range;
+
+use sha2::{Digest, Sha512Trunc256};
+
+use crate::{
+    tlog::{leaf_hash, node_hash, tree_root, Hash},
+    types::Checksum,
+    BlockError, Manifest, Package,
+};
+
+/// Magic identifying an encoded [BlockTable]
+pub const BLOCK_TABLE_MAGIC: [u8; 4] = *b"FWBT";
+
+/// Encoded [BlockTable] header length (magic and block count)
+pub const BLOCK_TABLE_HEADER_LEN: usize = 4 + 4;
+
+/// Compute the number of blocks for an application
+pub fn num_blocks(app_len: usize, block_size: usize) -> usize {
+    app_len.div_ceil(block_size)
+}
+
+/// Compute the block root over an application
+pub fn block_root(app: &[u8], block_size: usize) -> Hash {
+    let n = num_blocks(app.len(), block_size);
+    tree_root(n, &|i| leaf_hash(&app[i * block_size..][..block_size.min(app.len() - i * block_size)]))
+}
+
+/// Maximum block tree depth supported by [AppHasher]
+const MAX_TREE_DEPTH: usize = 64;
+
+/// Incremental application checksum, computing the block root where block
+/// hashing is enabled (see [Manifest::app_checksum])
+///
+/// This does not allocate, block roots are computed over a stack of complete
+/// subtree hashes so applications may be streamed in arbitrary chunks
+#[derive(Clone)]
+pub struct AppHasher {
+    block_size: Option<usize>,
+    hasher: Sha512Trunc256,
+    block_len: usize,
+    len: u64,
+    stack: [(Hash, u32); MAX_TREE_DEPTH],
+    depth: usize,
+}
+
+impl AppHasher {
+    /// Create an application hasher for the provided manifest
+    pub fn new(m: &Manifest) -> Self {
+        Self::with_block_size(m.block_size())
+    }
+
+    /// Create an application hasher with the provided block size (`None` for whole-image checksums)
+    pub fn with_block_size(block_size: Option<usize>) -> Self {
+        Self {
+            block_size,
+            hasher: Self::block_hasher(block_size),
+            block_len: 0,
+            len: 0,
+            stack: [([0u8; 32], 0); MAX_TREE_DEPTH],
+            depth: 0,
+        }
+    }
+
+    /// Fetch the number of bytes hashed
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Check whether no data has been hashed
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Add application data to the checksum
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.len += data.len() as u64;
+
+        let block_size = match self.block_size {
+            Some(n) => n,
+            None => {
+                self.hasher.update(data);
+                return;
+            },
+        };
+
+        while !data.is_empty() {
+            let n = data.len().min(block_size - self.block_len);
+            self.hasher.update(&data[..n]);
+            self.block_len += n;
+            data = &data[n..];
+
+            if self.block_len == block_size {
+                self.push_block();
+            }
+        }
+    }
+
+    /// Complete the checksum computation
+    pub fn finalize(mut self) -> Checksum {
+        if self.block_size.is_none() {
+            return Checksum(self.hasher.finalize().into());
+        }
+
+        if self.block_len > 0 {
+            self.push_block();
+        }
+
+        // Fold subtrees from the right, matching the split in [tree_root]
+        let mut root = match self.depth {
+            0 => Sha512Trunc256::digest(&[]).into(),
+            n => self.stack[n - 1].0,
+        };
+        for (h, _height) in self.stack[..self.depth.saturating_sub(1)].iter().rev() {
+            root = node_hash(h, &root);
+        }
+
+        Checksum(root)
+    }
+
+    /// Internal helper to create a hasher for the next block (leaf)
+    fn block_hasher(block_size: Option<usize>) -> Sha512Trunc256 {
+        match block_size {
+            Some(_) => Sha512Trunc256::new().chain([0x00]),
+            None => Sha512Trunc256::new(),
+        }
+    }
+
+    /// Internal helper to complete a block, merging equal height subtrees
+    fn push_block(&mut self) {
+        let h = core::mem::replace(&mut self.hasher, Self::block_hasher(self.block_size));
+        self.block_len = 0;
+
+        let mut node = (h.finalize().into(), 0);
+        while self.depth > 0 && self.stack[self.depth - 1].1 == node.1 {
+            self.depth -= 1;
+            node = (node_hash(&self.stack[self.depth].0, &node.0), node.1 + 1);
+        }
+
+        self.stack[self.depth] = node;
+        self.depth += 1;
+    }
+}
+
+/// Encode the block table for an application
+#[cfg(feature = "std")]
+pub fn encode_table(app: &[u8], block_size: usize) -> Vec<u8> {
+    let n = num_blocks(app.len(), block_size);
+
+    let mut b = Vec::with_capacity(BLOCK_TABLE_HEADER_LEN + n * 32);
+    b.extend_from_slice(&BLOCK_TABLE_MAGIC);
+    b.extend_from_slice(&(n as u32).to_le_bytes());
+    for c in app.chunks(block_size) {
+        b.extend_from_slice(&leaf_hash(c));
+    }
+
+    b
+}
+
+/// Block hash table, borrowed from an encoded table
+///
+/// Encoding: [BLOCK_TABLE_MAGIC], block count (u32, LE), block (leaf) hashes
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockTable<'a> {
+    hashes: &'a [u8],
+}
+
+impl<'a> BlockTable<'a> {
+    /// Decode a block table, returning the table and encoded length
+    pub fn decode(data: &'a [u8]) -> Result<(Self, usize), BlockError> {
+        if data.len() < BLOCK_TABLE_HEADER_LEN || data[..4] != BLOCK_TABLE_MAGIC {
+            return Err(BlockError::InvalidTable);
+        }
+
+        let n = u32::from_le_bytes(data[4..8].try_into().unwrap()) as usize;
+        let end = n.checked_mul(32)
+            .and_then(|l| l.checked_add(BLOCK_TABLE_HEADER_LEN))
+            .filter(|l| *l <= data.len())
+            .ok_or(BlockError::InvalidTable)?;
+
+        Ok((Self { hashes: &data[BLOCK_TABLE_HEADER_LEN..end] }, end))
+    }
+
+    /// Fetch the number of blocks
+    pub fn len(&self) -> usize {
+        self.hashes.len() / 32
+    }
+
+    /// Check whether the table is empty
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+
+    /// Fetch the hash for a block
+    pub fn hash(&self, index: usize) -> Option<Hash> {
+        let h = self.hashes.get(index * 32..)?.get(..32)?;
+        Some(h.try_into().unwrap())
+    }
+
+    /// Compute the root over the block hashes
+    pub fn root(&self) -> Hash {
+        tree_root(self.len(), &|i| self.hash(i).unwrap())
+    }
+}
+
+/// Verifier for individual application blocks against a signed manifest
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockVerifier<'a> {
+    table: BlockTable<'a>,
+    block_size: usize,
+    app_len: usize,
+}
+
+impl<'a> BlockVerifier<'a> {
+    /// Create a block verifier, checking the block table against the manifest application checksum
+    ///
+    /// The manifest signature should be verified prior to use
+    pub fn new(m: &Manifest, table: BlockTable<'a>) -> Result<Self, BlockError> {
+        let block_size = m.block_size().ok_or(BlockError::NotBlockHashed)?;
+        let app_len = usize::try_from(m.app_len).map_err(|_e| BlockError::LengthMismatch)?;
+
+        if table.len() != num_blocks(app_len, block_size) {
+            return Err(BlockError::InvalidTable);
+        }
+        if table.root() != *m.app_csum {
+            return Err(BlockError::RootMismatch);
+        }
+
+        Ok(Self { table, block_size, app_len })
+    }
+
+    /// Fetch the number of blocks
+    pub fn num_blocks(&self) -> usize {
+        self.table.len()
+    }
+
+    /// Fetch the block size
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Fetch the application byte range for a block
+    pub fn block_range(&self, index: usize) -> Result<Range<usize>, BlockError> {
+        if index >= self.num_blocks() {
+            return Err(BlockError::InvalidIndex);
+        }
+
+        let start = index * self.block_size;
+        Ok(start..self.app_len.min(start + self.block_size))
+    }
+
+    /// Verify an individual application block
+    pub fn verify_block(&self, index: usize, data: &[u8]) -> Result<(), BlockError> {
+        if data.len() != self.block_range(index)?.len() {
+            return Err(BlockError::LengthMismatch);
+        }
+
+        match self.table.hash(index) == Some(leaf_hash(data)) {
+            true => Ok(()),
+            false => Err(BlockError::HashMismatch),
+        }
+    }
+}
+
+impl<'a> Package<'a> {
+    /// Fetch the block table carried in the package (following the metadata), if present
+    pub fn block_table(&self) -> Result<Option<BlockTable<'a>>, BlockError> {
+        if !self.extra.starts_with(&BLOCK_TABLE_MAGIC) {
+            return Ok(None);
+        }
+
+        let (t, _n) = BlockTable::decode(self.extra)?;
+
+        Ok(Some(t))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::OsRng;
+
+    use crate::{Flags, ManifestBuilder, MetadataFormat, PackageOpts, VerifyError};
+
+    use super::*;
+
+    #[test]
+    fn block_root_tree() {
+        let app: Vec<u8> = (0..2500u32).map(|i| i as u8).collect();
+
+        // Three blocks, the last shorter
+        let (l0, l1, l2) = (leaf_hash(&app[..1024]), leaf_hash(&app[1024..2048]), leaf_hash(&app[2048..]));
+        assert_eq!(block_root(&app, 1024), node_hash(&node_hash(&l0, &l1), &l2));
+
+        // Single block
+        assert_eq!(block_root(&app, 4096), leaf_hash(&app));
+    }
+
+    #[test]
+    fn app_hasher() {
+        let app: Vec<u8> = (0..40_000u32).map(|i| (i * 13) as u8).collect();
+
+        for len in [0, 1, 1024, 1025, 3 * 1024, 5000, 16 * 1024, 40_000] {
+            for block_size in [None, Some(1024), Some(4096)] {
+                let app = &app[..len];
+                let expected = match block_size {
+                    Some(n) => Checksum(block_root(app, n)),
+                    None => Checksum::compute(app),
+                };
+
+                // Stream in uneven chunks
+                let mut h = AppHasher::with_block_size(block_size);
+                for c in app.chunks(700) {
+                    h.update(c);
+                }
+                assert_eq!(h.len(), len as u64);
+                assert_eq!(h.finalize(), expected, "len: {} block size: {:?}", len, block_size);
+            }
+        }
+    }
+
+    #[test]
+    fn verify_blocks() {
+        let app: Vec<u8> = (0..10_000u32).map(|i| (i * 7) as u8).collect();
+
+        let m = ManifestBuilder::new()
+            .block_size(1024).unwrap()
+            .app_bin(&app).unwrap()
+            .meta_bin(MetadataFormat::Binary, &[]).unwrap()
+            .build::<OsRng>(None).unwrap();
+        assert_eq!(m.block_size(), Some(1024));
+        assert!(m.flags().contains(Flags::BLOCK_SIZE & Flags::from_bits_truncate(1 << 6)));
+
+        // Whole package checks use the block root
+        let table = encode_table(&app, 1024);
+        let p = Package::encode_extra(&app, &[], &m, &table, &PackageOpts::default()).unwrap();
+        let p = Package::parse(&p).unwrap();
+        p.check().unwrap();
+
+        let mut bad = app.clone();
+        bad[5000] ^= 0x01;
+        assert_eq!(m.check(&bad, &[]), Err(VerifyError::AppChecksumMismatch));
+
+        // Blocks verify individually
+        let v = BlockVerifier::new(&m, p.block_table().unwrap().unwrap()).unwrap();
+        assert_eq!(v.num_blocks(), 10);
+        assert_eq!(v.block_range(9), Ok(9216..10_000));
+        for i in 0..v.num_blocks() {
+            let r = v.block_range(i).unwrap();
+            v.verify_block(i, &app[r.clone()]).unwrap();
+
+            let e = match r.contains(&5000) {
+                true => Err(BlockError::HashMismatch),
+                false => Ok(()),
+            };
+            assert_eq!(v.verify_block(i, &bad[r]), e);
+        }
+        assert_eq!(v.verify_block(1, &app[..100]), Err(BlockError::LengthMismatch));
+        assert_eq!(v.verify_block(10, &app[..100]), Err(BlockError::InvalidIndex));
+
+        // Tables must match the signed root
+        let mut t1 = table.clone();
+        t1[BLOCK_TABLE_HEADER_LEN + 40] ^= 0x01;
+        assert_eq!(BlockVerifier::new(&m, BlockTable::decode(&t1).unwrap().0), Err(BlockError::RootMismatch));
+
+        let t2 = encode_table(&app, 4096);
+        assert_eq!(BlockVerifier::new(&m, BlockTable::decode(&t2).unwrap().0), Err(BlockError::InvalidTable));
+
+        // Manifests without block hashing
+        let m1 = ManifestBuilder::new()
+            .app_bin(&app).unwrap()
+            .meta_bin(MetadataFormat::Binary, &[]).unwrap()
+            .build::<OsRng>(None).unwrap();
+        assert_eq!(BlockVerifier::new(&m1, BlockTable::decode(&table).unwrap().0), Err(BlockError::NotBlockHashed));
+    }
+}
@@ -0,0 +1,352 @@
+//! Boot slot selection for bootloaders
+//!
+//! [select] verifies a set of (A/B or N) [Slot]s against the trusted keys and a [BootPolicy],
+//! selecting the preferred slot where valid, otherwise the valid slot with the highest version,
+//! and returns a [BootDecision] recording the reason and the status of each slot.
+//!
+//! Slots are provided by implementing [Slot], with [MemorySlot] for memory-mapped images
+//! and [FlashSlot] for images in flash (`flash` feature).
+
+use core::{cmp::Ordering, fmt::Display, str::FromStr};
+
+use crate::{types::PublicKey, BootError, Manifest, Package};
+
+/// Application version (`major.minor.patch`), parsed from the manifest app version
+///
+/// Omitted components are zero (so an empty version is `0.0.0`) and build (`+build`) suffixes
+/// are ignored. Pre-releases (`-pre`) are ordered below the matching release, though
+/// pre-release identifiers are not kept so pre-releases of the same version compare equal.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+    /// Version is a pre-release
+    pub pre: bool,
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.major, self.minor, self.patch, !self.pre).cmp(&(other.major, other.minor, other.patch, !other.pre))
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl FromStr for Version {
+    type Err = BootError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Versions are unset for packages signed without a version
+        if s.is_empty() {
+            return Ok(Self::default());
+        }
+
+        let v = s.split('+').next().unwrap_or(s);
+        let (v, pre) = match v.split_once('-') {
+            Some((v, _pre)) => (v, true),
+            None => (v, false),
+        };
+
+        let mut p = v.split('.');
+        let mut next = || p.next().unwrap_or("0").parse().map_err(|_| BootError::InvalidVersion);
+
+        let (major, minor, patch) = (next()?, next()?, next()?);
+        if p.next().is_some() {
+            return Err(BootError::InvalidVersion);
+        }
+
+        Ok(Self { major, minor, patch, pre })
+    }
+}
+
+impl Display for Version {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if self.pre {
+            write!(f, "-pre")?;
+        }
+        Ok(())
+    }
+}
+
+/// Boot slot containing a candidate image
+pub trait Slot {
+    /// Verify the slot image, checking the application and metadata and that the
+    /// signing key is one of the trusted `keys`, returning the image manifest
+    fn verify(&mut self, keys: &[PublicKey]) -> Result<Manifest, BootError>;
+}
+
+impl<S: Slot + ?Sized> Slot for &mut S {
+    fn verify(&mut self, keys: &[PublicKey]) -> Result<Manifest, BootError> {
+        (**self).verify(keys)
+    }
+}
+
+/// Slot over a memory-mapped (or in-memory) attached package
+#[derive(Clone, Debug, PartialEq)]
+pub struct MemorySlot<'a>(pub &'a [u8]);
+
+impl Slot for MemorySlot<'_> {
+    fn verify(&mut self, keys: &[PublicKey]) -> Result<Manifest, BootError> {
+        let p = Package::parse(self.0)?;
+        p.manifest.verify(keys)?;
+        p.check()?;
+
+        Ok(p.manifest)
+    }
+}
+
+#[cfg(feature = "flash")]
+pub use self::flash::FlashSlot;
+
+#[cfg(feature = "flash")]
+mod flash {
+    use embedded_storage::nor_flash::ReadNorFlash;
+
+    use crate::{types::PublicKey, BootError, Manifest, StreamError};
+
+    use super::Slot;
+
+    /// Slot in flash, verified via [crate::flash::verify] using the provided buffer
+    pub struct FlashSlot<'a, F> {
+        flash: &'a mut F,
+        offset: u32,
+        size: u32,
+        buff: &'a mut [u8],
+    }
+
+    impl<'a, F: ReadNorFlash> FlashSlot<'a, F> {
+        /// Create a flash slot at `offset` of length `size`
+        pub fn new(flash: &'a mut F, offset: u32, size: u32, buff: &'a mut [u8]) -> Self {
+            Self { flash, offset, size, buff }
+        }
+    }
+
+    impl<F: ReadNorFlash> Slot for FlashSlot<'_, F> {
+        fn verify(&mut self, keys: &[PublicKey]) -> Result<Manifest, BootError> {
+            match crate::flash::verify(self.flash, self.offset, self.size, keys, self.buff) {
+                Ok(i) => Ok(i.manifest),
+                Err(StreamError::Io(_e)) => Err(BootError::Read),
+                Err(StreamError::Manifest(e)) => Err(e.into()),
+                Err(StreamError::Verify(e)) => Err(e.into()),
+            }
+        }
+    }
+}
+
+/// Boot policy, applied to verified slot images
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct BootPolicy<'a> {
+    /// Trusted signing keys
+    pub keys: &'a [PublicKey],
+    /// Required application name, if set
+    pub app_name: Option<&'a str>,
+    /// Minimum application version (eg. a rollback floor), if set
+    pub min_version: Option<Version>,
+    /// Preferred slot (eg. a newly installed update), booted where valid regardless of version
+    pub preferred: Option<usize>,
+}
+
+/// Slot status following verification
+///
+/// Manifests are held inline as boxing is not available in `no_std`
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Debug, PartialEq)]
+pub enum SlotStatus {
+    /// Slot is valid under the policy
+    Valid(Manifest, Version),
+    /// Slot is invalid, with the reason
+    Invalid(BootError),
+}
+
+/// Reason for a [BootDecision]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BootReason {
+    /// Preferred slot is valid
+    Preferred,
+    /// Valid slot with the highest version, no slot was preferred
+    HighestVersion,
+    /// Preferred slot is invalid, falling back to the valid slot with the highest version
+    Fallback,
+    /// No slot is valid
+    NoValidSlot,
+}
+
+/// Boot decision, the selected slot and reason along with the status of each slot
+#[derive(Clone, Debug, PartialEq)]
+pub struct BootDecision<const N: usize> {
+    /// Selected slot index, if any slot is valid
+    pub slot: Option<usize>,
+    /// Reason for the selection
+    pub reason: BootReason,
+    /// Status for each slot
+    pub slots: [SlotStatus; N],
+}
+
+impl<const N: usize> BootDecision<N> {
+    /// Fetch the manifest for the selected slot
+    pub fn manifest(&self) -> Option<&Manifest> {
+        match self.slot.map(|i| &self.slots[i]) {
+            Some(SlotStatus::Valid(m, _v)) => Some(m),
+            _ => None,
+        }
+    }
+}
+
+/// Check a slot against the boot policy
+pub fn check_slot<S: Slot>(slot: &mut S, policy: &BootPolicy) -> SlotStatus {
+    let mut check = || {
+        let m = slot.verify(policy.keys)?;
+
+        if policy.app_name.is_some_and(|n| n != m.app_name()) {
+            return Err(BootError::AppMismatch);
+        }
+
+        let v = Version::from_str(m.app_version())?;
+        if policy.min_version.is_some_and(|min| v < min) {
+            return Err(BootError::Downgrade);
+        }
+
+        Ok((m, v))
+    };
+
+    match check() {
+        Ok((m, v)) => SlotStatus::Valid(m, v),
+        Err(e) => SlotStatus::Invalid(e),
+    }
+}
+
+/// Select a slot to boot, verifying each slot against the boot policy
+///
+/// The preferred slot is selected where valid, otherwise the valid slot with the highest
+/// version (the lowest index where versions are equal)
+pub fn select<S: Slot, const N: usize>(slots: &mut [S; N], policy: &BootPolicy) -> BootDecision<N> {
+    let mut i = 0;
+    let slots = [(); N].map(|_| {
+        let s = check_slot(&mut slots[i], policy);
+        i += 1;
+        s
+    });
+
+    // Prefer the selected slot where valid
+    let preferred = policy.preferred.filter(|i| matches!(slots.get(*i), Some(SlotStatus::Valid(..))));
+    if preferred.is_some() {
+        return BootDecision { slot: preferred, reason: BootReason::Preferred, slots };
+    }
+
+    // Otherwise select the highest valid version
+    let mut slot: Option<(usize, Version)> = None;
+    for (i, s) in slots.iter().enumerate() {
+        if let SlotStatus::Valid(_m, v) = s {
+            if slot.is_none_or(|(_i, best)| *v > best) {
+                slot = Some((i, *v));
+            }
+        }
+    }
+
+    let reason = match (slot, policy.preferred) {
+        (None, _) => BootReason::NoValidSlot,
+        (Some(_), Some(_)) => BootReason::Fallback,
+        (Some(_), None) => BootReason::HighestVersion,
+    };
+
+    BootDecision { slot: slot.map(|(i, _v)| i), reason, slots }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::OsRng;
+
+    use crate::{test_utils::{builder, APP}, types::PrivateKey, ManifestError, PackageOpts, VerifyError};
+
+    use super::*;
+
+    /// Build a signed package with the provided name and version
+    fn package(key: &PrivateKey, name: &str, version: &str) -> Vec<u8> {
+        let m = builder(&[]).name(name).unwrap()
+            .version(version).unwrap()
+            .build::<OsRng>(Some(key.clone())).unwrap();
+
+        Package::encode(&APP, &[], &m, &PackageOpts::default()).unwrap()
+    }
+
+    #[test]
+    fn version() {
+        let v = Version::from_str("1.2.3+4").unwrap();
+        assert_eq!(v, Version{ major: 1, minor: 2, patch: 3, pre: false });
+        assert_eq!(Version::from_str("2.1"), Ok(Version{ major: 2, minor: 1, patch: 0, pre: false }));
+        assert!(Version::from_str("1.10.0").unwrap() > Version::from_str("1.9.9").unwrap());
+
+        // Empty versions (eg. from `fwsig sign`) are zero, pre-releases are below the release
+        assert_eq!(Version::from_str(""), Ok(Version::default()));
+        assert_eq!(Version::from_str("1.2.3-rc1"), Ok(Version{ major: 1, minor: 2, patch: 3, pre: true }));
+        assert_eq!(Version::from_str("1.2-rc.1+5"), Ok(Version{ major: 1, minor: 2, patch: 0, pre: true }));
+        assert_eq!(Version::from_str("1.2.3+build-5"), Ok(Version{ major: 1, minor: 2, patch: 3, pre: false }));
+        assert!(Version::from_str("1.2.3-rc1").unwrap() < Version::from_str("1.2.3").unwrap());
+        assert!(Version::from_str("1.2.3-rc1").unwrap() > Version::from_str("1.2.2").unwrap());
+
+        assert_eq!(Version::from_str("v1.2"), Err(BootError::InvalidVersion));
+        assert_eq!(Version::from_str("1..2"), Err(BootError::InvalidVersion));
+        assert_eq!(Version::from_str("-rc1"), Err(BootError::InvalidVersion));
+        assert_eq!(Version::from_str("1.2.3.4"), Err(BootError::InvalidVersion));
+    }
+
+    #[test]
+    fn select_slots() {
+        let key = PrivateKey::generate(&mut OsRng{});
+        let other = PrivateKey::generate(&mut OsRng{});
+        let keys = [PublicKey::from(&key)];
+
+        let (a, b) = (package(&key, "app", "1.2.0"), package(&key, "app", "1.3.0"));
+        let policy = BootPolicy{ keys: &keys, ..Default::default() };
+
+        // Highest version is selected
+        let d = select(&mut [MemorySlot(&a), MemorySlot(&b)], &policy);
+        assert_eq!((d.slot, d.reason), (Some(1), BootReason::HighestVersion));
+        assert_eq!(d.manifest().unwrap().app_version(), "1.3.0");
+
+        // Preferred slot is selected where valid, regardless of version
+        let preferred = BootPolicy{ preferred: Some(0), ..policy.clone() };
+        let d = select(&mut [MemorySlot(&a), MemorySlot(&b)], &preferred);
+        assert_eq!((d.slot, d.reason), (Some(0), BootReason::Preferred));
+
+        // Falling back where the preferred slot is corrupt
+        let mut a1 = a.clone();
+        a1[10] ^= 0x01;
+        let d = select(&mut [MemorySlot(&a1), MemorySlot(&b)], &preferred);
+        assert_eq!((d.slot, d.reason), (Some(1), BootReason::Fallback));
+        assert_eq!(d.slots[0], SlotStatus::Invalid(BootError::Verify(VerifyError::AppChecksumMismatch)));
+
+        // Untrusted keys, app names, and versions below the minimum are rejected
+        let c = package(&other, "app", "2.0.0");
+        let d = package(&key, "other", "2.0.0");
+        let policy = BootPolicy{ app_name: Some("app"), min_version: Some(Version{ major: 1, minor: 3, patch: 0, pre: false }), ..policy };
+        let r = select(&mut [MemorySlot(&a), MemorySlot(&b), MemorySlot(&c), MemorySlot(&d)], &policy);
+        assert_eq!((r.slot, r.reason), (Some(1), BootReason::HighestVersion));
+        assert_eq!(r.slots[0], SlotStatus::Invalid(BootError::Downgrade));
+        assert_eq!(r.slots[2], SlotStatus::Invalid(BootError::Manifest(ManifestError::NoMatchingKey)));
+        assert_eq!(r.slots[3], SlotStatus::Invalid(BootError::AppMismatch));
+
+        // Slots without versions are valid where no minimum version is set
+        let e = package(&key, "app", "");
+        let d = select(&mut [MemorySlot(&e), MemorySlot(&a1)], &BootPolicy{ keys: &keys, ..Default::default() });
+        assert_eq!((d.slot, d.reason), (Some(0), BootReason::HighestVersion));
+
+        // Releases are selected over pre-releases of the same version, which are below a matching minimum
+        let (rc, rel) = (package(&key, "app", "1.3.0-rc1"), package(&key, "app", "1.3.0"));
+        let d = select(&mut [MemorySlot(&rc), MemorySlot(&rel)], &BootPolicy{ keys: &keys, ..Default::default() });
+        assert_eq!((d.slot, d.reason), (Some(1), BootReason::HighestVersion));
+        let d = select(&mut [MemorySlot(&rc), MemorySlot(&a)], &policy);
+        assert_eq!(d.slots[0], SlotStatus::Invalid(BootError::Downgrade));
+
+        // No valid slots
+        let d = select(&mut [MemorySlot(&[0xff; 512]), MemorySlot(&a1)], &policy);
+        assert_eq!((d.slot, d.reason), (None, BootReason::NoValidSlot));
+        assert!(d.manifest().is_none());
+    }
+}
@@ -1,15 +1,15 @@
 //! [ManifestBuilder] for constructing [Manifest] objects
 
-use core::str::FromStr;
+use core::{ops::Deref, str::FromStr};
 
-use ed25519_dalek::{PUBLIC_KEY_LENGTH, SIGNATURE_LENGTH, SecretKey};
+use ed25519_dalek::SIGNATURE_LENGTH;
 use rand_core::{CryptoRng, RngCore};
-use sha2::{Digest, Sha512};
+use sha2::{Digest, Sha512Trunc256};
 
 use crate::{
-    MetadataFormat, ManifestError, 
+    MetadataFormat, ManifestError, Layout, SigMode, BLOCK_SIZE_SHIFT, META_ALIGN_SHIFT, NEXT_KEY_LEN, SIG_MODE_SHIFT,
     types::{Checksum, PublicKey, PrivateKey, Signature, Stringish},
-    MANIFEST_VERSION};
+};
 
 use super::{Manifest, Flags};
 
@@ -21,16 +21,25 @@ pub struct ManifestBuilder {
     name: Stringish<16>,
     version: Stringish<24>,
 
-    app: Option<(u32, Checksum)>,
-    meta: Option<(u16, MetadataFormat, Checksum)>,
+    app: Option<(u64, Checksum, u16)>,
+    meta: Option<(u64, MetadataFormat, Checksum, bool)>,
     key: Option<PublicKey>,
 }
 
 /// Manifest object info
 #[derive(Clone, PartialEq, Debug)]
 struct Info {
-    version: u16,
+    layout: Layout,
     flags: Flags,
+    sig_mode: SigMode,
+    meta_align: u16,
+    block_size: u16,
+}
+
+impl Default for ManifestBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ManifestBuilder {
@@ -38,8 +47,11 @@ impl ManifestBuilder {
     pub fn new() -> Self {
         Self{
             info: Info{
-                version: MANIFEST_VERSION,
+                layout: Layout::Compact,
                 flags: Flags::empty(),
+                sig_mode: SigMode::Ed25519ph,
+                meta_align: 0,
+                block_size: 0,
             },
             name: Stringish::default(),
             version: Stringish::default(),
@@ -49,6 +61,13 @@ impl ManifestBuilder {
         }
     }
 
+    /// Set manifest [Layout], defaults to [Layout::Compact]
+    pub fn layout(&mut self, layout: Layout) -> &mut Self {
+        self.info.layout = layout;
+
+        self
+    }
+
     /// Set manifest [Flags]
     pub fn flags(&mut self, flags: Flags) -> &mut Self {
         self.info.flags = flags;
@@ -56,51 +75,141 @@ impl ManifestBuilder {
         self
     }
 
+    /// Set signature [SigMode], defaults to [SigMode::Ed25519ph] (with context)
+    pub fn sig_mode(&mut self, sig_mode: SigMode) -> &mut Self {
+        self.info.sig_mode = sig_mode;
+
+        self
+    }
+
+    /// Set metadata alignment in bytes for attached packages (eg. to a flash page),
+    /// this must be a power of two and is recorded in the manifest [Flags]
+    pub fn meta_align(&mut self, align: usize) -> Result<&mut Self, ManifestError> {
+        if !align.is_power_of_two() || align.trailing_zeros() > (Flags::META_ALIGN.bits() >> META_ALIGN_SHIFT) as u32 {
+            return Err(ManifestError::InvalidAlignment);
+        }
+        self.info.meta_align = align.trailing_zeros() as u16;
+
+        Ok(self)
+    }
+
+    /// Enable block hashing with the provided block size in bytes (1024, 4096 or 16384),
+    /// this is recorded in the manifest [Flags]
+    ///
+    /// The application checksum is then a Merkle root over application blocks (see [crate::blocks]),
+    /// so this fails with [ManifestError::BlockSizeAfterApp] once the application has been added
+    pub fn block_size(&mut self, block_size: usize) -> Result<&mut Self, ManifestError> {
+        if self.app.is_some() {
+            return Err(ManifestError::BlockSizeAfterApp);
+        }
+
+        self.info.block_size = match block_size {
+            1024 => 1,
+            4096 => 2,
+            16384 => 3,
+            _ => return Err(ManifestError::InvalidBlockSize),
+        };
+
+        Ok(self)
+    }
+
     /// Set application name
-    pub fn name(&mut self, app_name: &str) -> Result<&mut Self, ()> {
-        self.name = Stringish::from_str(app_name)?;
+    pub fn name(&mut self, app_name: &str) -> Result<&mut Self, ManifestError> {
+        self.name = Stringish::from_str(app_name)
+            .map_err(|_| ManifestError::StringTooLong)?;
         Ok(self)
     }
 
     /// Set application version string
-    pub fn version(&mut self, app_version: &str) -> Result<&mut Self, ()> {
-        self.version = Stringish::from_str(app_version)?;
+    pub fn version(&mut self, app_version: &str) -> Result<&mut Self, ManifestError> {
+        self.version = Stringish::from_str(app_version)
+            .map_err(|_| ManifestError::StringTooLong)?;
         Ok(self)
     }
 
     /// Add app binary to manifest as bytes
-    pub fn app_bin(&mut self, d: &[u8]) -> &mut Self {
+    /// 
+    /// Fails with [ManifestError::AppTooLarge] if the binary exceeds the length
+    /// supported by the selected [Layout]
+    pub fn app_bin(&mut self, d: &[u8]) -> Result<&mut Self, ManifestError> {
+        let n = d.len() as u64;
+        if n > self.info.layout.max_app_len() {
+            return Err(ManifestError::AppTooLarge);
+        }
+
+        let csum = match self.info.block_size {
+            0 => Checksum::compute(d),
+            b => Checksum(crate::blocks::block_root(d, 1 << (8 + 2 * b))),
+        };
+
         self.app = Some((
-            d.len() as u32,
-            Checksum::compute(d),
+            n,
+            csum,
+            self.info.block_size,
         ));
 
-        self
+        Ok(self)
     }
 
     /// Add app binary to manifest via file
     #[cfg(feature = "std")]
     pub fn app_file(&mut self, f: &str) -> Result<&mut Self, std::io::Error> {
         let d = std::fs::read(f)?;
-        Ok(self.app_bin(&d))
+        self.app_bin(&d)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
     }
 
     /// Add metadata binary to manifest as bytes
-    pub fn meta_bin(&mut self, k: MetadataFormat, d: &[u8]) -> &mut Self {
+    /// 
+    /// Fails with [ManifestError::MetaTooLarge] if the metadata exceeds the length
+    /// supported by the selected [Layout]
+    pub fn meta_bin(&mut self, k: MetadataFormat, d: &[u8]) -> Result<&mut Self, ManifestError> {
+        let n = d.len() as u64;
+        if n > self.info.layout.max_meta_len() {
+            return Err(ManifestError::MetaTooLarge);
+        }
+
         self.meta = Some((
-            d.len() as u16,
+            n,
             k,
             Checksum::compute(d),
+            false,
         ));
 
-        self
+        Ok(self)
+    }
+
+    /// Add metadata binary to manifest as bytes, followed by a commitment to the
+    /// next signing key (setting [Flags::NEXT_KEY])
+    /// 
+    /// The commitment ([PublicKey::commitment]) must be appended to the metadata
+    /// when packaging, and is included in the metadata length and checksum
+    pub fn meta_bin_next_key(&mut self, k: MetadataFormat, d: &[u8], next_key: &PublicKey) -> Result<&mut Self, ManifestError> {
+        let n = d.len() as u64 + NEXT_KEY_LEN as u64;
+        if n > self.info.layout.max_meta_len() {
+            return Err(ManifestError::MetaTooLarge);
+        }
+
+        let h = Sha512Trunc256::new()
+            .chain(d)
+            .chain(next_key.commitment().deref());
+
+        self.meta = Some((
+            n,
+            k,
+            Checksum::from(h.finalize()),
+            true,
+        ));
+
+        Ok(self)
     }
 
     /// Add metadata binary to manifest via file
     #[cfg(feature = "std")]
     pub fn meta_file(&mut self, k: MetadataFormat, f: &str) -> Result<&mut Self, std::io::Error> {
         let d = std::fs::read(f)?;
-        Ok(self.meta_bin(k, &d))
+        self.meta_bin(k, &d)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
     }
 
     /// Complete manifest construction
@@ -113,9 +222,32 @@ impl ManifestBuilder {
         };
         self.info.flags.set(Flags::TRANSIENT_KEY, transient);
 
-        // Set public key and flags
-        let public_key = PublicKey::from(&secret_key);
-        
+        // Build manifest with public key
+        let mut m = self.prepare(PublicKey::from(&secret_key))?;
+
+        // Sign completed manifest
+        m.sign::<RNG>(secret_key)?;
+
+        Ok(m)
+    }
+
+    /// Complete manifest construction without signing, for signing by an external
+    /// signer holding the private key for `public_key` (see [Manifest::sign])
+    pub fn build_unsigned(&mut self, public_key: PublicKey) -> Result<Manifest, ManifestError> {
+        self.info.flags.remove(Flags::TRANSIENT_KEY);
+
+        self.prepare(public_key)
+    }
+
+    /// Internal helper to construct the manifest (with an empty signature)
+    fn prepare(&mut self, public_key: PublicKey) -> Result<Manifest, ManifestError> {
+        // Record signature mode
+        self.info.flags.remove(Flags::SIG_MODE);
+        self.info.flags.insert(Flags::from_bits_truncate((self.info.sig_mode as u16) << SIG_MODE_SHIFT));
+
+        // Record metadata alignment
+        self.info.flags.remove(Flags::META_ALIGN);
+        self.info.flags.insert(Flags::from_bits_truncate(self.info.meta_align << META_ALIGN_SHIFT));
 
         // Retrieve app and meta info
         let app = match &self.app {
@@ -127,10 +259,26 @@ impl ManifestBuilder {
             Some(v) => v,
             None => return Err(ManifestError::MissingMetaChecksum),
         };
+
+        // Record block size used for the app checksum
+        self.info.flags.remove(Flags::BLOCK_SIZE);
+        self.info.flags.insert(Flags::from_bits_truncate(app.2 << BLOCK_SIZE_SHIFT));
+
+        // Record next key commitment
+        self.info.flags.set(Flags::NEXT_KEY, meta.3);
+
+        // Re-check lengths in case the layout was changed after adding components
+        let layout = self.info.layout;
+        if app.0 > layout.max_app_len() {
+            return Err(ManifestError::AppTooLarge);
+        }
+        if meta.0 > layout.max_meta_len() {
+            return Err(ManifestError::MetaTooLarge);
+        }
         
         // Build manifest
-        let mut m = Manifest {
-            version: self.info.version,
+        let m = Manifest {
+            version: layout.version(),
             flags: self.info.flags.bits(),
 
             app_name: self.name.clone(),
@@ -143,29 +291,87 @@ impl ManifestBuilder {
             meta_kind: meta.1 as u16,
             meta_csum: meta.2.clone(),
 
-            key: PublicKey::from(public_key),
+            key: public_key,
             sig: Signature([0u8; SIGNATURE_LENGTH]),
         };
 
-        // Sign completed manifest
-        m.sign::<RNG>(secret_key)?;
-
         Ok(m)
     }
-
-    pub fn validate(&self) -> Result<(), ()> {
-        todo!()
-    }
-
-    pub fn sign(self) -> Result<Manifest, ()> {
-        todo!()
-    }
 }
 
 #[cfg(test)]
 mod tests {
+    use encdec::Encode;
+    use rand::rngs::OsRng;
+
+    use crate::{MANIFEST_LEN_LARGE, MANIFEST_VERSION_LARGE};
+
     use super::*;
 
+    #[test]
+    fn compact_length_limits() {
+        let mut b = ManifestBuilder::new();
 
+        // Metadata over 64KiB must be rejected rather than truncated
+        let meta = vec![0xab; u16::MAX as usize + 1];
+        assert_eq!(b.meta_bin(MetadataFormat::Binary, &meta).err(), Some(ManifestError::MetaTooLarge));
 
+        // Switching layout after adding components is caught at build
+        b.layout(Layout::Large)
+            .meta_bin(MetadataFormat::Binary, &meta).unwrap()
+            .app_bin(&[0xcd; 128]).unwrap();
+        b.layout(Layout::Compact);
+        assert_eq!(b.build::<OsRng>(None).err(), Some(ManifestError::MetaTooLarge));
+    }
+
+    #[test]
+    fn meta_align() {
+        let mut b = ManifestBuilder::new();
+
+        assert_eq!(b.meta_align(48).err(), Some(ManifestError::InvalidAlignment));
+
+        let m = b.meta_align(4096).unwrap()
+            .flags(Flags::empty())
+            .app_bin(&[0xcd; 128]).unwrap()
+            .meta_bin(MetadataFormat::Binary, &[]).unwrap()
+            .build::<OsRng>(None).unwrap();
+        assert_eq!(m.meta_align(), 4096);
+        assert_eq!(m.checked_meta_offset(), Ok(4096));
+    }
+
+    #[test]
+    fn block_size() {
+        let mut b = ManifestBuilder::new();
+
+        assert_eq!(b.block_size(2048).err(), Some(ManifestError::InvalidBlockSize));
+
+        let m = b.block_size(16384).unwrap()
+            .app_bin(&[0xcd; 128]).unwrap()
+            .meta_bin(MetadataFormat::Binary, &[]).unwrap()
+            .build::<OsRng>(None).unwrap();
+        assert_eq!(m.block_size(), Some(16384));
+        assert!(m.check(&[0xcd; 128], &[]).is_ok());
+
+        // Block size must be set before the application checksum is computed
+        let mut b = ManifestBuilder::new();
+        b.app_bin(&[0xcd; 128]).unwrap();
+        assert_eq!(b.block_size(1024).err(), Some(ManifestError::BlockSizeAfterApp));
+    }
+
+    #[test]
+    fn build_large() {
+        let meta = vec![0xab; u16::MAX as usize + 1];
+
+        let m = ManifestBuilder::new()
+            .layout(Layout::Large)
+            .app_bin(&[0xcd; 128]).unwrap()
+            .meta_bin(MetadataFormat::Binary, &meta).unwrap()
+            .build::<OsRng>(None).unwrap();
+
+        assert_eq!(m.version, MANIFEST_VERSION_LARGE);
+        assert_eq!(m.meta_len(), meta.len());
+        assert_eq!(m.encode_len().unwrap(), MANIFEST_LEN_LARGE);
+
+        m.check(&[0xcd; 128], &meta).unwrap();
+    }
 }
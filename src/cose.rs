@@ -0,0 +1,180 @@
+//! [COSE_Sign1](https://www.rfc-editor.org/rfc/rfc9052) signature support
+//!
+//! Allows exporting a package signature as a `COSE_Sign1` object (EdDSA, with the
+//! signing public key as the key ID) over the encoded [Manifest], for backends that
+//! already validate COSE, and verifying `COSE_Sign1` objects from other producers.
+//!
+//! Note COSE EdDSA signatures are pure Ed25519 over the COSE `Sig_structure`, so these
+//! are generated with the signing key rather than derived from the (Ed25519ph) manifest signature.
+
+use core::ops::Deref;
+
+use coset::{
+    iana, CborSerializable, CoseSign1, CoseSign1Builder, HeaderBuilder,
+    RegisteredLabelWithPrivate, TaggedCborSerializable,
+};
+use ed25519_dalek::{Signer, Verifier};
+use encdec::Encode;
+
+use crate::{
+    types::{PrivateKey, PublicKey},
+    CoseError, Manifest, MANIFEST_MAX_LEN,
+};
+
+/// `COSE_Sign1` object
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sign1 {
+    inner: CoseSign1,
+}
+
+impl Sign1 {
+    /// Sign a payload using EdDSA with the provided key, setting the key ID to the public key.
+    ///
+    /// If `detached` is set the payload is not included in the encoded object
+    pub fn sign(payload: &[u8], key: &PrivateKey, detached: bool) -> Self {
+        let public_key = ed25519_dalek::PublicKey::from(key.deref());
+        let keys = ed25519_dalek::Keypair{ public: public_key, secret: key.clone().inner() };
+
+        let b = CoseSign1Builder::new()
+            .protected(HeaderBuilder::new().algorithm(iana::Algorithm::EdDSA).build())
+            .unprotected(HeaderBuilder::new().key_id(public_key.as_bytes().to_vec()).build());
+
+        let inner = match detached {
+            true => b.create_detached_signature(payload, &[], |d| keys.sign(d).to_bytes().to_vec()),
+            false => b.payload(payload.to_vec())
+                .create_signature(&[], |d| keys.sign(d).to_bytes().to_vec()),
+        }.build();
+
+        Self { inner }
+    }
+
+    /// Decode a tagged or untagged `COSE_Sign1` object
+    pub fn decode(data: &[u8]) -> Result<Self, CoseError> {
+        let inner = CoseSign1::from_tagged_slice(data)
+            .or_else(|_| CoseSign1::from_slice(data))
+            .map_err(|_| CoseError::Cbor)?;
+
+        Ok(Self { inner })
+    }
+
+    /// Encode as a tagged `COSE_Sign1` object
+    pub fn encode(&self) -> Result<Vec<u8>, CoseError> {
+        self.inner.clone().to_tagged_vec()
+            .map_err(|_| CoseError::Cbor)
+    }
+
+    /// Fetch the key ID, from the protected or unprotected header
+    pub fn key_id(&self) -> &[u8] {
+        match self.inner.protected.header.key_id.is_empty() {
+            true => &self.inner.unprotected.key_id,
+            false => &self.inner.protected.header.key_id,
+        }
+    }
+
+    /// Fetch the attached payload (if present)
+    pub fn payload(&self) -> Option<&[u8]> {
+        self.inner.payload.as_deref()
+    }
+
+    /// Verify the signature against the provided key, using the `detached` payload
+    /// where this is not included in the object
+    pub fn verify(&self, key: &PublicKey, detached: Option<&[u8]>) -> Result<(), CoseError> {
+        // Check algorithm is EdDSA, preferring the protected header
+        let alg = self.inner.protected.header.alg.as_ref()
+            .or(self.inner.unprotected.alg.as_ref());
+        if alg != Some(&RegisteredLabelWithPrivate::Assigned(iana::Algorithm::EdDSA)) {
+            return Err(CoseError::UnsupportedAlgorithm);
+        }
+
+        // Reject critical headers we do not understand
+        if !self.inner.protected.header.crit.is_empty() {
+            return Err(CoseError::UnsupportedHeader);
+        }
+
+        let verifier = |s: &[u8], d: &[u8]| {
+            let s = ed25519_dalek::Signature::from_bytes(s)
+                .map_err(|_| CoseError::InvalidSignature)?;
+            key.verify(d, &s).map_err(|_| CoseError::InvalidSignature)
+        };
+
+        match (&self.inner.payload, detached) {
+            (Some(_), None) => self.inner.verify_signature(&[], verifier),
+            (None, Some(p)) => self.inner.verify_detached_signature(p, &[], verifier),
+            (Some(_), Some(_)) => Err(CoseError::UnexpectedPayload),
+            (None, None) => Err(CoseError::MissingPayload),
+        }
+    }
+}
+
+/// Export a detached `COSE_Sign1` signature over the encoded [Manifest]
+pub fn sign_manifest(m: &Manifest, key: &PrivateKey) -> Result<Vec<u8>, CoseError> {
+    let mut b = [0u8; MANIFEST_MAX_LEN];
+    let n = m.encode(&mut b)?;
+
+    Sign1::sign(&b[..n], key, true).encode()
+}
+
+/// Verify a detached `COSE_Sign1` signature over the encoded [Manifest]
+pub fn verify_manifest(data: &[u8], m: &Manifest, key: &PublicKey) -> Result<(), CoseError> {
+    let mut b = [0u8; MANIFEST_MAX_LEN];
+    let n = m.encode(&mut b)?;
+
+    Sign1::decode(data)?.verify(key, Some(&b[..n]))
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::OsRng;
+
+    use crate::test_utils::{manifest, META};
+
+    use super::*;
+
+    #[test]
+    fn sign_verify_manifest() {
+        let key = PrivateKey::generate(&mut OsRng{});
+        let public_key = PublicKey::from(&key);
+
+        let m = manifest(Some(&key), &META);
+
+        let s = sign_manifest(&m, &key).unwrap();
+        assert_eq!(Sign1::decode(&s).unwrap().key_id(), public_key.as_bytes());
+        verify_manifest(&s, &m, &public_key).unwrap();
+
+        // Verification fails for other manifests or keys
+        let mut m1 = m.clone();
+        m1.app_len = 99;
+        assert_eq!(verify_manifest(&s, &m1, &public_key), Err(CoseError::InvalidSignature));
+
+        let other = PublicKey::from(&PrivateKey::generate(&mut OsRng{}));
+        assert_eq!(verify_manifest(&s, &m, &other), Err(CoseError::InvalidSignature));
+    }
+
+    #[test]
+    fn verify_external() {
+        let key = PrivateKey::generate(&mut OsRng{});
+        let public_key = PublicKey::from(&key);
+        let keys = ed25519_dalek::Keypair{ public: *public_key, secret: key.clone().inner() };
+
+        // Untagged object with attached payload and alg in the unprotected header
+        let s = CoseSign1Builder::new()
+            .unprotected(HeaderBuilder::new().algorithm(iana::Algorithm::EdDSA).key_id(b"other".to_vec()).build())
+            .payload(b"hello".to_vec())
+            .create_signature(&[], |d| keys.sign(d).to_bytes().to_vec())
+            .build()
+            .to_vec().unwrap();
+
+        let s = Sign1::decode(&s).unwrap();
+        assert_eq!(s.key_id(), b"other");
+        assert_eq!(s.payload(), Some(&b"hello"[..]));
+        s.verify(&public_key, None).unwrap();
+        assert_eq!(s.verify(&public_key, Some(b"hello")), Err(CoseError::UnexpectedPayload));
+
+        // Other algorithms are rejected
+        let s = CoseSign1Builder::new()
+            .protected(HeaderBuilder::new().algorithm(iana::Algorithm::ES256).build())
+            .payload(b"hello".to_vec())
+            .build();
+        assert_eq!(Sign1{ inner: s }.verify(&public_key, None), Err(CoseError::UnsupportedAlgorithm));
+    }
+}
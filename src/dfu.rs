@@ -0,0 +1,308 @@
+//! DFU file support, both plain binaries with a DFU suffix and
+//! [DfuSe](https://www.st.com/resource/en/user_manual/um0391.pdf) files (as used by STM32 devices)
+
+use crate::ContainerError;
+
+/// DFU suffix length
+pub const DFU_SUFFIX_LEN: usize = 16;
+
+/// DFU specification version for plain DFU files
+const BCD_DFU: u16 = 0x0100;
+/// DFU specification version for DfuSe files
+const BCD_DFUSE: u16 = 0x011A;
+
+const DFUSE_PREFIX_LEN: usize = 11;
+const DFUSE_TARGET_LEN: usize = 274;
+const DFUSE_ELEMENT_LEN: usize = 8;
+
+/// USB device information recorded in the DFU suffix
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DfuInfo {
+    /// USB vendor ID (`0xFFFF` matches any vendor)
+    pub vendor_id: u16,
+    /// USB product ID (`0xFFFF` matches any product)
+    pub product_id: u16,
+    /// Device release number (`0xFFFF` matches any release)
+    pub device: u16,
+}
+
+impl Default for DfuInfo {
+    fn default() -> Self {
+        Self { vendor_id: 0xFFFF, product_id: 0xFFFF, device: 0xFFFF }
+    }
+}
+
+/// Decoded DFU file
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dfu {
+    /// USB device information from the DFU suffix
+    pub info: DfuInfo,
+    /// Target address (DfuSe files only)
+    pub address: Option<u32>,
+    /// Target alternate setting (DfuSe files only)
+    pub alt: Option<u8>,
+    /// File contents, DfuSe elements are reassembled with `0xFF` filled gaps
+    pub data: Vec<u8>,
+}
+
+/// Check whether data ends with a valid DFU suffix
+pub fn is_dfu(data: &[u8]) -> bool {
+    data.len() >= DFU_SUFFIX_LEN
+        && &data[data.len() - 8..][..3] == b"UFD"
+        && dfu_crc(&data[..data.len() - 4]) == read_u32(data, data.len() - 4)
+}
+
+/// Encode data as a plain binary with a DFU suffix
+pub fn encode(data: &[u8], info: &DfuInfo) -> Vec<u8> {
+    let mut out = data.to_vec();
+    write_suffix(&mut out, info, BCD_DFU);
+    out
+}
+
+/// Encode data as a single-target, single-element DfuSe file at the provided address
+pub fn encode_dfuse(data: &[u8], address: u32, alt: u8, info: &DfuInfo) -> Result<Vec<u8>, ContainerError> {
+    let element_len = u32::try_from(data.len())
+        .map_err(|_| ContainerError::AddressOverflow)?;
+    if address as u64 + element_len as u64 > u32::MAX as u64 + 1 {
+        return Err(ContainerError::AddressOverflow);
+    }
+
+    let target_len = DFUSE_ELEMENT_LEN + data.len();
+    let image_len = DFUSE_PREFIX_LEN + DFUSE_TARGET_LEN + target_len;
+    let mut out = Vec::with_capacity(image_len + DFU_SUFFIX_LEN);
+
+    // DfuSe prefix
+    out.extend_from_slice(b"DfuSe");
+    out.push(0x01);
+    out.extend_from_slice(&(image_len as u32).to_le_bytes());
+    out.push(1);
+
+    // Target prefix (unnamed)
+    let mut t = [0u8; DFUSE_TARGET_LEN];
+    t[..6].copy_from_slice(b"Target");
+    t[6] = alt;
+    t[266..270].copy_from_slice(&(target_len as u32).to_le_bytes());
+    t[270..274].copy_from_slice(&1u32.to_le_bytes());
+    out.extend_from_slice(&t);
+
+    // Image element
+    out.extend_from_slice(&address.to_le_bytes());
+    out.extend_from_slice(&element_len.to_le_bytes());
+    out.extend_from_slice(data);
+
+    write_suffix(&mut out, info, BCD_DFUSE);
+
+    Ok(out)
+}
+
+/// Decode a DFU or DfuSe file, checking the suffix CRC
+///
+/// DfuSe files must contain a single target (alternate setting), otherwise this fails with
+/// [ContainerError::MultipleTargets], and fail with [ContainerError::SpanTooLarge] where the
+/// reassembled image (including gaps between elements) would exceed `max_span` bytes
+pub fn decode(data: &[u8], max_span: usize) -> Result<Dfu, ContainerError> {
+    if data.len() < DFU_SUFFIX_LEN {
+        return Err(ContainerError::InvalidLength);
+    }
+
+    // Parse and check suffix
+    let s = &data[data.len() - DFU_SUFFIX_LEN..];
+    if &s[8..11] != b"UFD" || (s[11] as usize) < DFU_SUFFIX_LEN || s[11] as usize > data.len() {
+        return Err(ContainerError::InvalidMagic);
+    }
+    if dfu_crc(&data[..data.len() - 4]) != read_u32(s, 12) {
+        return Err(ContainerError::InvalidCrc);
+    }
+
+    let info = DfuInfo {
+        device: read_u16(s, 0),
+        product_id: read_u16(s, 2),
+        vendor_id: read_u16(s, 4),
+    };
+    let body = &data[..data.len() - s[11] as usize];
+
+    // Plain DFU files contain the binary directly
+    if !body.starts_with(b"DfuSe") {
+        return Ok(Dfu { info, address: None, alt: None, data: body.to_vec() });
+    }
+
+    // Parse the DfuSe target, images for other alternate settings (eg. option bytes
+    // or external flash) are not reassembled into a single image
+    let num_targets = *body.get(10).ok_or(ContainerError::InvalidLength)?;
+    if num_targets != 1 {
+        return Err(match num_targets {
+            0 => ContainerError::InvalidBlock,
+            _ => ContainerError::MultipleTargets,
+        });
+    }
+
+    let mut index = DFUSE_PREFIX_LEN;
+    let t = body.get(index..index + DFUSE_TARGET_LEN)
+        .ok_or(ContainerError::InvalidLength)?;
+    if &t[..6] != b"Target" {
+        return Err(ContainerError::InvalidBlock);
+    }
+    let alt = t[6];
+    let num_elements = read_u32(t, 270);
+    index += DFUSE_TARGET_LEN;
+
+    // Parse DfuSe elements
+    let mut elements = Vec::new();
+    for _ in 0..num_elements {
+        let e = body.get(index..index + DFUSE_ELEMENT_LEN)
+            .ok_or(ContainerError::InvalidLength)?;
+        let (addr, len) = (read_u32(e, 0), read_u32(e, 4) as usize);
+        index += DFUSE_ELEMENT_LEN;
+
+        let d = index.checked_add(len)
+            .and_then(|end| body.get(index..end))
+            .ok_or(ContainerError::InvalidLength)?;
+        elements.push((addr, d));
+        index += len;
+    }
+
+    // Reassemble elements by address
+    elements.sort_by_key(|(a, _d)| *a);
+    let address = match elements.first() {
+        Some((a, _d)) => *a,
+        None => return Err(ContainerError::InvalidBlock),
+    };
+
+    let mut out = Vec::new();
+    for (addr, d) in elements {
+        let offset = (addr - address) as usize;
+        if offset.saturating_add(d.len()) > max_span {
+            return Err(ContainerError::SpanTooLarge);
+        }
+        if offset < out.len() {
+            return Err(ContainerError::InvalidBlock);
+        }
+        out.resize(offset, 0xFF);
+        out.extend_from_slice(d);
+    }
+
+    Ok(Dfu { info, address: Some(address), alt: Some(alt), data: out })
+}
+
+/// Append a DFU suffix (including CRC) to the provided data
+fn write_suffix(out: &mut Vec<u8>, info: &DfuInfo, bcd_dfu: u16) {
+    out.extend_from_slice(&info.device.to_le_bytes());
+    out.extend_from_slice(&info.product_id.to_le_bytes());
+    out.extend_from_slice(&info.vendor_id.to_le_bytes());
+    out.extend_from_slice(&bcd_dfu.to_le_bytes());
+    out.extend_from_slice(b"UFD");
+    out.push(DFU_SUFFIX_LEN as u8);
+
+    let crc = dfu_crc(out);
+    out.extend_from_slice(&crc.to_le_bytes());
+}
+
+/// Compute DFU suffix CRC (CRC-32 without the final inversion)
+fn dfu_crc(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for b in data {
+        crc ^= *b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    crc
+}
+
+fn read_u16(b: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(b[offset..][..2].try_into().unwrap())
+}
+
+fn read_u32(b: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(b[offset..][..4].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INFO: DfuInfo = DfuInfo { vendor_id: 0x0483, product_id: 0xdf11, device: 0x0200 };
+
+    #[test]
+    fn crc() {
+        // CRC-32 check value (0xCBF43926) prior to final inversion
+        assert_eq!(dfu_crc(b"123456789"), !0xCBF4_3926);
+    }
+
+    #[test]
+    fn encode_decode_suffix() {
+        let e = encode(&[0xab; 100], &INFO);
+        assert_eq!(e.len(), 100 + DFU_SUFFIX_LEN);
+        assert!(is_dfu(&e));
+
+        let d = decode(&e, 100).unwrap();
+        assert_eq!(d, Dfu { info: INFO, address: None, alt: None, data: vec![0xab; 100] });
+    }
+
+    #[test]
+    fn encode_decode_dfuse() {
+        let e = encode_dfuse(&[0xcd; 100], 0x0800_4000, 2, &INFO).unwrap();
+        assert!(is_dfu(&e));
+
+        let d = decode(&e, 100).unwrap();
+        assert_eq!(d, Dfu { info: INFO, address: Some(0x0800_4000), alt: Some(2), data: vec![0xcd; 100] });
+    }
+
+    /// DfuSe target alternate setting and `(address, data)` elements
+    type Target<'a> = (u8, &'a [(u32, &'a [u8])]);
+
+    /// Build a DfuSe file with the provided targets
+    fn dfuse(targets: &[Target]) -> Vec<u8> {
+        let mut out = b"DfuSe\x01\0\0\0\0".to_vec();
+        out.push(targets.len() as u8);
+
+        for (alt, elements) in targets {
+            let mut t = [0u8; DFUSE_TARGET_LEN];
+            t[..6].copy_from_slice(b"Target");
+            t[6] = *alt;
+            t[270..274].copy_from_slice(&(elements.len() as u32).to_le_bytes());
+            out.extend_from_slice(&t);
+
+            for (addr, d) in elements.iter() {
+                out.extend_from_slice(&addr.to_le_bytes());
+                out.extend_from_slice(&(d.len() as u32).to_le_bytes());
+                out.extend_from_slice(d);
+            }
+        }
+
+        write_suffix(&mut out, &INFO, BCD_DFUSE);
+        out
+    }
+
+    #[test]
+    fn decode_dfuse_elements() {
+        let e = dfuse(&[(0, &[(0x0800_0010, &[0x02; 4]), (0x0800_0000, &[0x01; 4])])]);
+        let d = decode(&e, 20).unwrap();
+        assert_eq!(d.address, Some(0x0800_0000));
+        assert_eq!(&d.data[..4], &[0x01; 4]);
+        assert_eq!(&d.data[4..16], &[0xff; 12]);
+        assert_eq!(&d.data[16..], &[0x02; 4]);
+
+        // Reassembled span is bounded, including gaps
+        assert_eq!(decode(&e, 19), Err(ContainerError::SpanTooLarge));
+        let e = dfuse(&[(0, &[(0x0000_0000, &[0x01; 4]), (0xf000_0000, &[0x02; 4])])]);
+        assert_eq!(decode(&e, 1 << 20), Err(ContainerError::SpanTooLarge));
+    }
+
+    #[test]
+    fn decode_dfuse_targets() {
+        let e = dfuse(&[(0, &[(0x0800_0000, &[0x01; 4])]), (1, &[(0x1fff_7800, &[0x02; 4])])]);
+        assert_eq!(decode(&e, 1 << 20), Err(ContainerError::MultipleTargets));
+
+        let e = dfuse(&[]);
+        assert_eq!(decode(&e, 1 << 20), Err(ContainerError::InvalidBlock));
+    }
+
+    #[test]
+    fn decode_bad_crc() {
+        let mut e = encode_dfuse(&[0xcd; 100], 0x0800_4000, 0, &INFO).unwrap();
+        e[300] ^= 0x01;
+        assert!(!is_dfu(&e));
+        assert_eq!(decode(&e, 100), Err(ContainerError::InvalidCrc));
+    }
+}
@@ -22,6 +22,38 @@ pub enum ManifestError {
     InvalidSignature,
     #[cfg_attr(feature = "thiserror", error("Signature verification failed"))]
     VerificationFailed,
+    #[cfg_attr(feature = "thiserror", error("String exceeds available length"))]
+    StringTooLong,
+    #[cfg_attr(feature = "thiserror", error("Application exceeds maximum length for manifest layout"))]
+    AppTooLarge,
+    #[cfg_attr(feature = "thiserror", error("Metadata exceeds maximum length for manifest layout"))]
+    MetaTooLarge,
+    #[cfg_attr(feature = "thiserror", error("Unsupported manifest version"))]
+    UnsupportedVersion,
+    #[cfg_attr(feature = "thiserror", error("Unsupported signature mode"))]
+    UnsupportedSigMode,
+    #[cfg_attr(feature = "thiserror", error("Unsupported manifest flags"))]
+    UnsupportedFlags,
+    #[cfg_attr(feature = "thiserror", error("Alignment must be a power of two"))]
+    InvalidAlignment,
+    #[cfg_attr(feature = "thiserror", error("Package length does not match manifest"))]
+    PackageLength,
+    #[cfg_attr(feature = "thiserror", error("Package exceeds slot size"))]
+    PackageTooLarge,
+    #[cfg_attr(feature = "thiserror", error("Manifest encode/decode failed: {0:?}"))]
+    Codec(encdec::Error),
+    #[cfg_attr(feature = "thiserror", error("Unsupported block size"))]
+    InvalidBlockSize,
+    #[cfg_attr(feature = "thiserror", error("Block size must be set before adding the application"))]
+    BlockSizeAfterApp,
+    #[cfg_attr(feature = "thiserror", error("Reserved manifest bytes must be zero"))]
+    InvalidReserved,
+}
+
+impl From<encdec::Error> for ManifestError {
+    fn from(value: encdec::Error) -> Self {
+        Self::Codec(value)
+    }
 }
 
 /// Verification error enumeration
@@ -40,4 +72,397 @@ pub enum VerifyError {
     InvalidSignature,
     #[cfg_attr(feature = "thiserror", error("signature verification failed"))]
     VerificationFailed,
+    #[cfg_attr(feature = "thiserror", error("unsupported manifest version"))]
+    UnsupportedVersion,
+    #[cfg_attr(feature = "thiserror", error("unsupported manifest flags"))]
+    UnsupportedFlags,
+}
+
+/// Image loading error enumeration
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "thiserror", derive(thiserror::Error))]
+pub enum ImageError {
+    #[cfg_attr(feature = "thiserror", error("failed to read image file"))]
+    Io,
+    #[cfg_attr(feature = "thiserror", error("invalid ELF file"))]
+    InvalidElf,
+    #[cfg_attr(feature = "thiserror", error("invalid Intel HEX file"))]
+    InvalidHex,
+    #[cfg_attr(feature = "thiserror", error("invalid Motorola S-record file"))]
+    InvalidSrec,
+    #[cfg_attr(feature = "thiserror", error("image contains no loadable data"))]
+    NoSegments,
+    #[cfg_attr(feature = "thiserror", error("image segment below base address"))]
+    SegmentBelowBase,
+    #[cfg_attr(feature = "thiserror", error("image segments overlap"))]
+    OverlappingSegments,
+    #[cfg_attr(feature = "thiserror", error("image span exceeds maximum"))]
+    SpanTooLarge,
+    #[cfg_attr(feature = "thiserror", error("ELF section not found or has no file data"))]
+    SectionNotFound,
+    #[cfg_attr(feature = "thiserror", error("ELF section too small for manifest"))]
+    SectionTooSmall,
+    #[cfg_attr(feature = "thiserror", error("ELF section not within a loadable segment"))]
+    SectionNotLoaded,
+    #[cfg_attr(feature = "thiserror", error("manifest error: {0}"))]
+    Manifest(ManifestError),
+}
+
+impl From<ManifestError> for ImageError {
+    fn from(value: ManifestError) -> Self {
+        Self::Manifest(value)
+    }
+}
+
+/// Container (UF2 / DFU) encoding error enumeration
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "thiserror", derive(thiserror::Error))]
+pub enum ContainerError {
+    #[cfg_attr(feature = "thiserror", error("invalid container length"))]
+    InvalidLength,
+    #[cfg_attr(feature = "thiserror", error("invalid container magic"))]
+    InvalidMagic,
+    #[cfg_attr(feature = "thiserror", error("invalid container block"))]
+    InvalidBlock,
+    #[cfg_attr(feature = "thiserror", error("image exceeds container address space"))]
+    AddressOverflow,
+    #[cfg_attr(feature = "thiserror", error("container CRC mismatch"))]
+    InvalidCrc,
+    #[cfg_attr(feature = "thiserror", error("container image span exceeds maximum"))]
+    SpanTooLarge,
+    #[cfg_attr(feature = "thiserror", error("container holds data for multiple targets"))]
+    MultipleTargets,
+}
+
+/// MCUboot image conversion / verification error enumeration
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "thiserror", derive(thiserror::Error))]
+pub enum McubootError {
+    #[cfg_attr(feature = "thiserror", error("invalid image length"))]
+    InvalidLength,
+    #[cfg_attr(feature = "thiserror", error("invalid image magic"))]
+    InvalidMagic,
+    #[cfg_attr(feature = "thiserror", error("invalid TLV area"))]
+    InvalidTlv,
+    #[cfg_attr(feature = "thiserror", error("invalid image version"))]
+    InvalidVersion,
+    #[cfg_attr(feature = "thiserror", error("required TLV not found"))]
+    MissingTlv,
+    #[cfg_attr(feature = "thiserror", error("image hash mismatch"))]
+    HashMismatch,
+    #[cfg_attr(feature = "thiserror", error("image key (or key hash) does not match provided key"))]
+    KeyMismatch,
+    #[cfg_attr(feature = "thiserror", error("invalid image signature"))]
+    InvalidSignature,
+    #[cfg_attr(feature = "thiserror", error("embedded fwsig manifest does not match image"))]
+    InvalidManifest,
+    #[cfg_attr(feature = "thiserror", error("manifest error: {0}"))]
+    Manifest(ManifestError),
+}
+
+impl From<ManifestError> for McubootError {
+    fn from(value: ManifestError) -> Self {
+        Self::Manifest(value)
+    }
+}
+
+/// SUIT envelope encoding / verification error enumeration
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "thiserror", derive(thiserror::Error))]
+pub enum SuitError {
+    #[cfg_attr(feature = "thiserror", error("CBOR encode/decode failed"))]
+    Cbor,
+    #[cfg_attr(feature = "thiserror", error("invalid SUIT envelope"))]
+    InvalidEnvelope,
+    #[cfg_attr(feature = "thiserror", error("invalid SUIT manifest"))]
+    InvalidManifest,
+    #[cfg_attr(feature = "thiserror", error("unsupported SUIT manifest version"))]
+    UnsupportedVersion,
+    #[cfg_attr(feature = "thiserror", error("unsupported digest or signature algorithm"))]
+    UnsupportedAlgorithm,
+    #[cfg_attr(feature = "thiserror", error("invalid vendor or class identifier"))]
+    InvalidIdentifier,
+    #[cfg_attr(feature = "thiserror", error("manifest digest mismatch"))]
+    DigestMismatch,
+    #[cfg_attr(feature = "thiserror", error("no matching key for envelope verification"))]
+    NoMatchingKey,
+    #[cfg_attr(feature = "thiserror", error("invalid envelope signature"))]
+    InvalidSignature,
+}
+
+impl From<CoseError> for SuitError {
+    fn from(value: CoseError) -> Self {
+        match value {
+            CoseError::Cbor => Self::Cbor,
+            CoseError::UnsupportedAlgorithm => Self::UnsupportedAlgorithm,
+            CoseError::InvalidSignature => Self::InvalidSignature,
+            _ => Self::InvalidEnvelope,
+        }
+    }
+}
+
+/// COSE signature error enumeration
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "thiserror", derive(thiserror::Error))]
+pub enum CoseError {
+    #[cfg_attr(feature = "thiserror", error("CBOR encode/decode failed"))]
+    Cbor,
+    #[cfg_attr(feature = "thiserror", error("unsupported signature algorithm"))]
+    UnsupportedAlgorithm,
+    #[cfg_attr(feature = "thiserror", error("unsupported critical header"))]
+    UnsupportedHeader,
+    #[cfg_attr(feature = "thiserror", error("detached payload required"))]
+    MissingPayload,
+    #[cfg_attr(feature = "thiserror", error("detached payload provided for object with attached payload"))]
+    UnexpectedPayload,
+    #[cfg_attr(feature = "thiserror", error("invalid signature"))]
+    InvalidSignature,
+    #[cfg_attr(feature = "thiserror", error("manifest error: {0}"))]
+    Manifest(ManifestError),
+}
+
+impl From<ManifestError> for CoseError {
+    fn from(value: ManifestError) -> Self {
+        Self::Manifest(value)
+    }
+}
+
+/// Remote signing error enumeration
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "thiserror", derive(thiserror::Error))]
+pub enum RemoteError {
+    #[cfg_attr(feature = "thiserror", error("failed to bind or connect"))]
+    Io,
+    #[cfg_attr(feature = "thiserror", error("missing or invalid bearer token"))]
+    Unauthorized,
+    #[cfg_attr(feature = "thiserror", error("application not allowed by signing policy"))]
+    AppNotAllowed,
+    #[cfg_attr(feature = "thiserror", error("manifest key does not match signing key"))]
+    KeyMismatch,
+    #[cfg_attr(feature = "thiserror", error("invalid signing request"))]
+    InvalidRequest,
+    #[cfg_attr(feature = "thiserror", error("invalid response from signing server"))]
+    InvalidResponse,
+    #[cfg_attr(feature = "thiserror", error("failed to record signature to audit log"))]
+    AuditFailed,
+    #[cfg_attr(feature = "thiserror", error("unexpected HTTP status: {0}"))]
+    Status(u16),
+    #[cfg_attr(feature = "thiserror", error("manifest error: {0}"))]
+    Manifest(ManifestError),
+}
+
+impl From<ManifestError> for RemoteError {
+    fn from(value: ManifestError) -> Self {
+        Self::Manifest(value)
+    }
+}
+
+/// Audit log error enumeration
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "thiserror", derive(thiserror::Error))]
+pub enum AuditError {
+    #[cfg_attr(feature = "thiserror", error("failed to read or write audit log"))]
+    Io,
+    #[cfg_attr(feature = "thiserror", error("invalid audit entry at line {0}"))]
+    InvalidEntry(usize),
+    #[cfg_attr(feature = "thiserror", error("audit entry sequence mismatch at line {0} (entries removed or reordered)"))]
+    SequenceMismatch(usize),
+    #[cfg_attr(feature = "thiserror", error("audit entry hash chain mismatch at line {0} (entries edited or removed)"))]
+    ChainMismatch(usize),
+    #[cfg_attr(feature = "thiserror", error("expected head not found in audit log (log truncated or edited)"))]
+    HeadNotFound,
+    #[cfg_attr(feature = "thiserror", error("manifest error: {0}"))]
+    Manifest(ManifestError),
+}
+
+impl From<ManifestError> for AuditError {
+    fn from(value: ManifestError) -> Self {
+        Self::Manifest(value)
+    }
+}
+
+/// Signing request / approval error enumeration
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "thiserror", derive(thiserror::Error))]
+pub enum ApprovalError {
+    #[cfg_attr(feature = "thiserror", error("invalid signing request"))]
+    InvalidRequest,
+    #[cfg_attr(feature = "thiserror", error("threshold must be between one and the number of approvers"))]
+    InvalidThreshold,
+    #[cfg_attr(feature = "thiserror", error("duplicate approver key"))]
+    DuplicateApprover,
+    #[cfg_attr(feature = "thiserror", error("manifest signing key is not an approver"))]
+    SignerNotApprover,
+    #[cfg_attr(feature = "thiserror", error("release signer approves by finalizing the request"))]
+    SignerApproval,
+    #[cfg_attr(feature = "thiserror", error("signing key does not match manifest key"))]
+    SignerMismatch,
+    #[cfg_attr(feature = "thiserror", error("request already approved with this key"))]
+    AlreadyApproved,
+    #[cfg_attr(feature = "thiserror", error("invalid approval signature"))]
+    InvalidApproval,
+    #[cfg_attr(feature = "thiserror", error("quorum not met ({0} of {1} approvals)"))]
+    QuorumNotMet(usize, usize),
+    #[cfg_attr(feature = "thiserror", error("manifest error: {0}"))]
+    Manifest(ManifestError),
+}
+
+impl From<ManifestError> for ApprovalError {
+    fn from(value: ManifestError) -> Self {
+        Self::Manifest(value)
+    }
+}
+
+/// Key rotation error enumeration
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "thiserror", derive(thiserror::Error))]
+pub enum RotationError {
+    #[cfg_attr(feature = "thiserror", error("failed to load or store rotation state"))]
+    Store,
+    #[cfg_attr(feature = "thiserror", error("manifest error: {0}"))]
+    Manifest(ManifestError),
+    #[cfg_attr(feature = "thiserror", error("verification error: {0}"))]
+    Verify(VerifyError),
+}
+
+impl From<ManifestError> for RotationError {
+    fn from(value: ManifestError) -> Self {
+        Self::Manifest(value)
+    }
+}
+
+impl From<VerifyError> for RotationError {
+    fn from(value: VerifyError) -> Self {
+        Self::Verify(value)
+    }
+}
+
+/// Transparency log error enumeration
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "thiserror", derive(thiserror::Error))]
+pub enum LogError {
+    #[cfg_attr(feature = "thiserror", error("failed to read or write log"))]
+    Io,
+    #[cfg_attr(feature = "thiserror", error("log contents do not match signed tree head"))]
+    InvalidLog,
+    #[cfg_attr(feature = "thiserror", error("log key does not match existing log"))]
+    KeyMismatch,
+    #[cfg_attr(feature = "thiserror", error("manifest already present in log"))]
+    Duplicate,
+    #[cfg_attr(feature = "thiserror", error("manifest not found in log"))]
+    NotFound,
+    #[cfg_attr(feature = "thiserror", error("invalid tree head signature"))]
+    InvalidHead,
+    #[cfg_attr(feature = "thiserror", error("invalid inclusion proof"))]
+    InvalidProof,
+    #[cfg_attr(feature = "thiserror", error("proof encode/decode failed: {0:?}"))]
+    Codec(encdec::Error),
+    #[cfg_attr(feature = "thiserror", error("manifest error: {0}"))]
+    Manifest(ManifestError),
+}
+
+impl From<encdec::Error> for LogError {
+    fn from(value: encdec::Error) -> Self {
+        Self::Codec(value)
+    }
+}
+
+impl From<ManifestError> for LogError {
+    fn from(value: ManifestError) -> Self {
+        Self::Manifest(value)
+    }
+}
+
+/// Block hashing error enumeration
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "thiserror", derive(thiserror::Error))]
+pub enum BlockError {
+    #[cfg_attr(feature = "thiserror", error("invalid block table"))]
+    InvalidTable,
+    #[cfg_attr(feature = "thiserror", error("manifest does not use block hashing"))]
+    NotBlockHashed,
+    #[cfg_attr(feature = "thiserror", error("block table does not match manifest app checksum"))]
+    RootMismatch,
+    #[cfg_attr(feature = "thiserror", error("block index out of range"))]
+    InvalidIndex,
+    #[cfg_attr(feature = "thiserror", error("block length mismatch"))]
+    LengthMismatch,
+    #[cfg_attr(feature = "thiserror", error("block hash mismatch"))]
+    HashMismatch,
+}
+
+/// Streaming verification error enumeration, generic over the reader error type
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "thiserror", derive(thiserror::Error))]
+pub enum StreamError<E> {
+    #[cfg_attr(feature = "thiserror", error("read failed: {0:?}"))]
+    Io(E),
+    #[cfg_attr(feature = "thiserror", error("manifest error: {0}"))]
+    Manifest(ManifestError),
+    #[cfg_attr(feature = "thiserror", error("verification error: {0}"))]
+    Verify(VerifyError),
+}
+
+impl<E> From<ManifestError> for StreamError<E> {
+    fn from(value: ManifestError) -> Self {
+        Self::Manifest(value)
+    }
+}
+
+impl<E> From<VerifyError> for StreamError<E> {
+    fn from(value: VerifyError) -> Self {
+        Self::Verify(value)
+    }
+}
+
+/// Boot slot error enumeration, the reason a slot is not bootable
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "thiserror", derive(thiserror::Error))]
+pub enum BootError {
+    #[cfg_attr(feature = "thiserror", error("failed to read slot"))]
+    Read,
+    #[cfg_attr(feature = "thiserror", error("invalid application version"))]
+    InvalidVersion,
+    #[cfg_attr(feature = "thiserror", error("application version below minimum"))]
+    Downgrade,
+    #[cfg_attr(feature = "thiserror", error("application name mismatch"))]
+    AppMismatch,
+    #[cfg_attr(feature = "thiserror", error("manifest error: {0}"))]
+    Manifest(ManifestError),
+    #[cfg_attr(feature = "thiserror", error("verification error: {0}"))]
+    Verify(VerifyError),
+}
+
+impl From<ManifestError> for BootError {
+    fn from(value: ManifestError) -> Self {
+        Self::Manifest(value)
+    }
+}
+
+impl From<VerifyError> for BootError {
+    fn from(value: VerifyError) -> Self {
+        Self::Verify(value)
+    }
+}
+
+/// Update state error enumeration
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "thiserror", derive(thiserror::Error))]
+pub enum UpdateError {
+    #[cfg_attr(feature = "thiserror", error("failed to load or store update status"))]
+    Store,
+    #[cfg_attr(feature = "thiserror", error("invalid stored update status"))]
+    InvalidStatus,
+    #[cfg_attr(feature = "thiserror", error("invalid update state for operation"))]
+    InvalidState,
+    #[cfg_attr(feature = "thiserror", error("image does not match staged update"))]
+    UnknownImage,
+    #[cfg_attr(feature = "thiserror", error("manifest error: {0}"))]
+    Manifest(ManifestError),
+}
+
+impl From<ManifestError> for UpdateError {
+    fn from(value: ManifestError) -> Self {
+        Self::Manifest(value)
+    }
 }
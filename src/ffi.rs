@@ -0,0 +1,584 @@
+//! C API for manifest decoding and verification
+//!
+//! These functions are `no_std` compatible and do not allocate, for use from
+//! C bootloaders via the `fwsig-ffi` staticlib / cdylib (see `ffi/include/fwsig.h`).
+//!
+//! All functions return a [FwsigStatus], zero on success and negative codes
+//! mirroring [ManifestError] and [VerifyError] variants on failure.
+
+use core::{mem::MaybeUninit, slice};
+
+use encdec::DecodeOwned;
+use sha2::{Digest, Sha512Trunc256};
+
+use crate::{
+    blocks::AppHasher,
+    types::{Checksum, PublicKey, Signature, Stringish},
+    Manifest, ManifestError, VerifyError,
+};
+
+/// Status codes returned by C API functions
+///
+/// `FWSIG_STATUS_OK` (zero) indicates success, [ManifestError] variants map
+/// to codes from -16 and [VerifyError] variants to codes from -48.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(i32)]
+pub enum FwsigStatus {
+    /// Operation succeeded
+    Ok = 0,
+    /// Null pointer or invalid argument
+    InvalidArgument = -1,
+
+    /// [ManifestError::MissingAppChecksum]
+    MissingAppChecksum = -16,
+    /// [ManifestError::MissingMetaChecksum]
+    MissingMetaChecksum = -17,
+    /// [ManifestError::InvalidPublicKey]
+    InvalidPublicKey = -18,
+    /// [ManifestError::InvalidPrivateKey]
+    InvalidPrivateKey = -19,
+    /// [ManifestError::InvalidHex]
+    InvalidHex = -20,
+    /// [ManifestError::SigningFailed]
+    SigningFailed = -21,
+    /// [ManifestError::NoMatchingKey]
+    NoMatchingKey = -22,
+    /// [ManifestError::InvalidSignature]
+    InvalidSignature = -23,
+    /// [ManifestError::VerificationFailed]
+    VerificationFailed = -24,
+    /// [ManifestError::StringTooLong]
+    StringTooLong = -25,
+    /// [ManifestError::AppTooLarge]
+    AppTooLarge = -26,
+    /// [ManifestError::MetaTooLarge]
+    MetaTooLarge = -27,
+    /// [ManifestError::UnsupportedVersion]
+    UnsupportedVersion = -28,
+    /// [ManifestError::UnsupportedSigMode]
+    UnsupportedSigMode = -29,
+    /// [ManifestError::UnsupportedFlags]
+    UnsupportedFlags = -30,
+    /// [ManifestError::InvalidAlignment]
+    InvalidAlignment = -31,
+    /// [ManifestError::PackageLength]
+    PackageLength = -32,
+    /// [ManifestError::PackageTooLarge]
+    PackageTooLarge = -33,
+    /// [ManifestError::Codec]
+    Codec = -34,
+    /// [ManifestError::InvalidBlockSize]
+    InvalidBlockSize = -35,
+    /// [ManifestError::BlockSizeAfterApp]
+    BlockSizeAfterApp = -36,
+    /// [ManifestError::InvalidReserved]
+    InvalidReserved = -37,
+
+    /// [VerifyError::AppLengthMismatch]
+    AppLengthMismatch = -48,
+    /// [VerifyError::AppChecksumMismatch]
+    AppChecksumMismatch = -49,
+    /// [VerifyError::MetaLengthMismatch]
+    MetaLengthMismatch = -50,
+    /// [VerifyError::MetaChecksumMismatch]
+    MetaChecksumMismatch = -51,
+    /// [VerifyError::InvalidSignature]
+    CheckInvalidSignature = -52,
+    /// [VerifyError::VerificationFailed]
+    CheckVerificationFailed = -53,
+    /// [VerifyError::UnsupportedVersion]
+    CheckUnsupportedVersion = -54,
+    /// [VerifyError::UnsupportedFlags]
+    CheckUnsupportedFlags = -55,
+}
+
+impl From<ManifestError> for FwsigStatus {
+    fn from(value: ManifestError) -> Self {
+        match value {
+            ManifestError::MissingAppChecksum => Self::MissingAppChecksum,
+            ManifestError::MissingMetaChecksum => Self::MissingMetaChecksum,
+            ManifestError::InvalidPublicKey => Self::InvalidPublicKey,
+            ManifestError::InvalidPrivateKey => Self::InvalidPrivateKey,
+            ManifestError::InvalidHex => Self::InvalidHex,
+            ManifestError::SigningFailed => Self::SigningFailed,
+            ManifestError::NoMatchingKey => Self::NoMatchingKey,
+            ManifestError::InvalidSignature => Self::InvalidSignature,
+            ManifestError::VerificationFailed => Self::VerificationFailed,
+            ManifestError::StringTooLong => Self::StringTooLong,
+            ManifestError::AppTooLarge => Self::AppTooLarge,
+            ManifestError::MetaTooLarge => Self::MetaTooLarge,
+            ManifestError::UnsupportedVersion => Self::UnsupportedVersion,
+            ManifestError::UnsupportedSigMode => Self::UnsupportedSigMode,
+            ManifestError::UnsupportedFlags => Self::UnsupportedFlags,
+            ManifestError::InvalidAlignment => Self::InvalidAlignment,
+            ManifestError::PackageLength => Self::PackageLength,
+            ManifestError::PackageTooLarge => Self::PackageTooLarge,
+            ManifestError::Codec(_) => Self::Codec,
+            ManifestError::InvalidBlockSize => Self::InvalidBlockSize,
+            ManifestError::BlockSizeAfterApp => Self::BlockSizeAfterApp,
+            ManifestError::InvalidReserved => Self::InvalidReserved,
+        }
+    }
+}
+
+impl From<VerifyError> for FwsigStatus {
+    fn from(value: VerifyError) -> Self {
+        match value {
+            VerifyError::AppLengthMismatch => Self::AppLengthMismatch,
+            VerifyError::AppChecksumMismatch => Self::AppChecksumMismatch,
+            VerifyError::MetaLengthMismatch => Self::MetaLengthMismatch,
+            VerifyError::MetaChecksumMismatch => Self::MetaChecksumMismatch,
+            VerifyError::InvalidSignature => Self::CheckInvalidSignature,
+            VerifyError::VerificationFailed => Self::CheckVerificationFailed,
+            VerifyError::UnsupportedVersion => Self::CheckUnsupportedVersion,
+            VerifyError::UnsupportedFlags => Self::CheckUnsupportedFlags,
+        }
+    }
+}
+
+impl<E: Into<FwsigStatus>> From<Result<(), E>> for FwsigStatus {
+    fn from(value: Result<(), E>) -> Self {
+        match value {
+            Ok(_) => Self::Ok,
+            Err(e) => e.into(),
+        }
+    }
+}
+
+/// Decoded manifest, see [Manifest] for field descriptions
+///
+/// Strings are utf8 and zero-padded, lengths are widened to 64 bits for all layouts.
+#[derive(Clone, Debug, PartialEq)]
+#[repr(C)]
+pub struct FwsigManifest {
+    /// Manifest version
+    pub version: u16,
+    /// Manifest flags
+    pub flags: u16,
+    /// Application name
+    pub app_name: [u8; 16],
+    /// Application version
+    pub app_version: [u8; 24],
+    /// Application length
+    pub app_len: u64,
+    /// Application checksum
+    pub app_csum: [u8; 32],
+    /// Metadata kind
+    pub meta_kind: u16,
+    /// Metadata length
+    pub meta_len: u64,
+    /// Metadata checksum
+    pub meta_csum: [u8; 32],
+    /// Signing public key
+    pub key: [u8; 32],
+    /// Manifest signature
+    pub sig: [u8; 64],
+}
+
+impl From<&Manifest> for FwsigManifest {
+    fn from(m: &Manifest) -> Self {
+        Self {
+            version: m.version,
+            flags: m.flags,
+            app_name: m.app_name.0,
+            app_version: m.app_version.0,
+            app_len: m.app_len,
+            app_csum: m.app_csum.0,
+            meta_kind: m.meta_kind,
+            meta_len: m.meta_len,
+            meta_csum: m.meta_csum.0,
+            key: *m.key.as_bytes(),
+            sig: m.sig.0,
+        }
+    }
+}
+
+impl TryFrom<&FwsigManifest> for Manifest {
+    type Error = ManifestError;
+
+    fn try_from(m: &FwsigManifest) -> Result<Self, Self::Error> {
+        let key = ed25519_dalek::PublicKey::from_bytes(&m.key)
+            .map_err(|_| ManifestError::InvalidPublicKey)?;
+
+        Ok(Self {
+            version: m.version,
+            flags: m.flags,
+            app_name: Stringish(m.app_name),
+            app_version: Stringish(m.app_version),
+            app_len: m.app_len,
+            app_csum: Checksum(m.app_csum),
+            meta_kind: m.meta_kind,
+            meta_len: m.meta_len,
+            meta_csum: Checksum(m.meta_csum),
+            key: PublicKey(key),
+            sig: Signature(m.sig),
+        })
+    }
+}
+
+/// Incremental app / metadata checksum state, opaque to callers
+///
+/// Initialise with [fwsig_hash_init], then call [fwsig_hash_update] for each chunk
+/// of data and [fwsig_hash_finish] to write the checksum.
+#[repr(C, align(16))]
+pub struct FwsigHasher {
+    _state: [u8; 256],
+}
+
+// Ensure hasher state fits the opaque storage
+const _: () = assert!(core::mem::size_of::<Sha512Trunc256>() <= core::mem::size_of::<FwsigHasher>());
+const _: () = assert!(core::mem::align_of::<Sha512Trunc256>() <= core::mem::align_of::<FwsigHasher>());
+
+impl FwsigHasher {
+    fn inner(&mut self) -> &mut Sha512Trunc256 {
+        // SAFETY: storage is size and alignment checked above and initialised by [fwsig_hash_init]
+        unsafe { &mut *(self as *mut Self as *mut Sha512Trunc256) }
+    }
+}
+
+/// Incremental application checksum state for a manifest, opaque to callers
+///
+/// This computes the block root for manifests using block hashing ([crate::Flags::BLOCK_SIZE]),
+/// and the whole-image checksum otherwise. Initialise with [fwsig_app_hash_init], then call
+/// [fwsig_app_hash_update] for each chunk of data and [fwsig_app_hash_finish] to write the checksum.
+#[repr(C, align(16))]
+pub struct FwsigAppHasher {
+    _state: [u8; 2816],
+}
+
+// Ensure app hasher state fits the opaque storage
+const _: () = assert!(core::mem::size_of::<AppHasher>() <= core::mem::size_of::<FwsigAppHasher>());
+const _: () = assert!(core::mem::align_of::<AppHasher>() <= core::mem::align_of::<FwsigAppHasher>());
+
+impl FwsigAppHasher {
+    fn inner(&mut self) -> &mut AppHasher {
+        // SAFETY: storage is size and alignment checked above and initialised by [fwsig_app_hash_init]
+        unsafe { &mut *(self as *mut Self as *mut AppHasher) }
+    }
+}
+
+/// Helper to build slices from C pointers, allowing null pointers for empty slices
+unsafe fn from_raw<'a, T>(p: *const T, n: usize) -> Option<&'a [T]> {
+    match (p.is_null(), n) {
+        (_, 0) => Some(&[]),
+        (true, _) => None,
+        (false, _) => Some(slice::from_raw_parts(p, n)),
+    }
+}
+
+/// Decode a manifest from the start of `data` (ie. a detached or header manifest)
+///
+/// # Safety
+/// `data` must be valid for `len` bytes and `out` must be a valid pointer
+#[no_mangle]
+pub unsafe extern "C" fn fwsig_manifest_decode(data: *const u8, len: usize, out: *mut FwsigManifest) -> FwsigStatus {
+    let (d, out) = match (from_raw(data, len), out.as_mut()) {
+        (Some(d), Some(o)) => (d, o),
+        _ => return FwsigStatus::InvalidArgument,
+    };
+
+    match Manifest::decode_owned(d) {
+        Ok((m, _n)) => {
+            *out = FwsigManifest::from(&m);
+            FwsigStatus::Ok
+        },
+        Err(e) => e.into(),
+    }
+}
+
+/// Decode a manifest trailing `data` (ie. from an attached package), writing the
+/// offset of the manifest within the data to `offset` (if not null)
+///
+/// # Safety
+/// `data` must be valid for `len` bytes, `out` must be a valid pointer,
+/// and `offset` must be valid or null
+#[no_mangle]
+pub unsafe extern "C" fn fwsig_manifest_decode_trailer(data: *const u8, len: usize, out: *mut FwsigManifest, offset: *mut usize) -> FwsigStatus {
+    let (d, out) = match (from_raw(data, len), out.as_mut()) {
+        (Some(d), Some(o)) => (d, o),
+        _ => return FwsigStatus::InvalidArgument,
+    };
+
+    match Manifest::decode_trailer(d) {
+        Ok((m, n)) => {
+            *out = FwsigManifest::from(&m);
+            if let Some(o) = offset.as_mut() {
+                *o = n;
+            }
+            FwsigStatus::Ok
+        },
+        Err(e) => e.into(),
+    }
+}
+
+/// Initialise a checksum state
+///
+/// # Safety
+/// `h` must be a valid pointer
+#[no_mangle]
+pub unsafe extern "C" fn fwsig_hash_init(h: *mut FwsigHasher) -> FwsigStatus {
+    if h.is_null() {
+        return FwsigStatus::InvalidArgument;
+    }
+
+    (h as *mut MaybeUninit<Sha512Trunc256>).write(MaybeUninit::new(Sha512Trunc256::new()));
+
+    FwsigStatus::Ok
+}
+
+/// Update a checksum state with `len` bytes of `data`
+///
+/// # Safety
+/// `h` must be initialised with [fwsig_hash_init] and `data` must be valid for `len` bytes
+#[no_mangle]
+pub unsafe extern "C" fn fwsig_hash_update(h: *mut FwsigHasher, data: *const u8, len: usize) -> FwsigStatus {
+    let (h, d) = match (h.as_mut(), from_raw(data, len)) {
+        (Some(h), Some(d)) => (h, d),
+        _ => return FwsigStatus::InvalidArgument,
+    };
+
+    h.inner().update(d);
+
+    FwsigStatus::Ok
+}
+
+/// Finalise a checksum state, writing the checksum to `out`
+///
+/// The state must be re-initialised with [fwsig_hash_init] before reuse.
+///
+/// # Safety
+/// `h` must be initialised with [fwsig_hash_init] and `out` must be a valid pointer
+#[no_mangle]
+pub unsafe extern "C" fn fwsig_hash_finish(h: *mut FwsigHasher, out: *mut [u8; 32]) -> FwsigStatus {
+    let (h, out) = match (h.as_mut(), out.as_mut()) {
+        (Some(h), Some(o)) => (h, o),
+        _ => return FwsigStatus::InvalidArgument,
+    };
+
+    let d = h.inner().finalize_reset();
+    out.copy_from_slice(&d);
+
+    FwsigStatus::Ok
+}
+
+/// Initialise an application checksum state for the provided manifest
+///
+/// # Safety
+/// `h` and `m` must be valid pointers
+#[no_mangle]
+pub unsafe extern "C" fn fwsig_app_hash_init(h: *mut FwsigAppHasher, m: *const FwsigManifest) -> FwsigStatus {
+    let m = match (h.is_null(), m.as_ref()) {
+        (false, Some(m)) => m,
+        _ => return FwsigStatus::InvalidArgument,
+    };
+
+    let m = match Manifest::try_from(m) {
+        Ok(m) => m,
+        Err(e) => return e.into(),
+    };
+
+    (h as *mut MaybeUninit<AppHasher>).write(MaybeUninit::new(AppHasher::new(&m)));
+
+    FwsigStatus::Ok
+}
+
+/// Update an application checksum state with `len` bytes of `data`
+///
+/// # Safety
+/// `h` must be initialised with [fwsig_app_hash_init] and `data` must be valid for `len` bytes
+#[no_mangle]
+pub unsafe extern "C" fn fwsig_app_hash_update(h: *mut FwsigAppHasher, data: *const u8, len: usize) -> FwsigStatus {
+    let (h, d) = match (h.as_mut(), from_raw(data, len)) {
+        (Some(h), Some(d)) => (h, d),
+        _ => return FwsigStatus::InvalidArgument,
+    };
+
+    h.inner().update(d);
+
+    FwsigStatus::Ok
+}
+
+/// Finalise an application checksum state, writing the checksum to `out`
+///
+/// The state must be re-initialised with [fwsig_app_hash_init] before reuse.
+///
+/// # Safety
+/// `h` must be initialised with [fwsig_app_hash_init] and `out` must be a valid pointer
+#[no_mangle]
+pub unsafe extern "C" fn fwsig_app_hash_finish(h: *mut FwsigAppHasher, out: *mut [u8; 32]) -> FwsigStatus {
+    let (h, out) = match (h.as_mut(), out.as_mut()) {
+        (Some(h), Some(o)) => (h, o),
+        _ => return FwsigStatus::InvalidArgument,
+    };
+
+    let c = h.inner().clone().finalize();
+    out.copy_from_slice(&c.0);
+
+    FwsigStatus::Ok
+}
+
+/// Check app and metadata lengths and checksums against the manifest,
+/// and that the manifest signature is valid
+///
+/// The app checksum must be computed with [fwsig_app_hash_init] (or [fwsig_hash_init] where
+/// the manifest does not use block hashing), and the metadata checksum with [fwsig_hash_init].
+/// Note this does not check the signing key is trusted, see [fwsig_manifest_verify].
+///
+/// # Safety
+/// `m`, `app_csum` and `meta_csum` must be valid pointers
+#[no_mangle]
+pub unsafe extern "C" fn fwsig_manifest_check(m: *const FwsigManifest, app_csum: *const [u8; 32], app_len: usize, meta_csum: *const [u8; 32], meta_len: usize) -> FwsigStatus {
+    let (m, app_csum, meta_csum) = match (m.as_ref(), app_csum.as_ref(), meta_csum.as_ref()) {
+        (Some(m), Some(a), Some(c)) => (m, a, c),
+        _ => return FwsigStatus::InvalidArgument,
+    };
+
+    let m = match Manifest::try_from(m) {
+        Ok(m) => m,
+        Err(e) => return e.into(),
+    };
+
+    m.check_precomputed(&Checksum(*app_csum), app_len, &Checksum(*meta_csum), meta_len).into()
+}
+
+/// Verify the manifest signature against an array of `num_keys` trusted public keys
+///
+/// # Safety
+/// `m` must be a valid pointer and `keys` must be valid for `num_keys` keys
+#[no_mangle]
+pub unsafe extern "C" fn fwsig_manifest_verify(m: *const FwsigManifest, keys: *const [u8; 32], num_keys: usize) -> FwsigStatus {
+    let (m, keys) = match (m.as_ref(), from_raw(keys, num_keys)) {
+        (Some(m), Some(k)) => (m, k),
+        _ => return FwsigStatus::InvalidArgument,
+    };
+
+    let m = match Manifest::try_from(m) {
+        Ok(m) => m,
+        Err(e) => return e.into(),
+    };
+
+    // Match on key bytes so invalid trusted keys are skipped rather than failing
+    let key = match keys.iter().find(|k| *k == m.key.as_bytes()) {
+        Some(_) => m.key.clone(),
+        None => return FwsigStatus::NoMatchingKey,
+    };
+
+    m.verify(core::slice::from_ref(&key)).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use encdec::Encode;
+    use rand::rngs::OsRng;
+
+    use crate::{types::PrivateKey, ManifestBuilder, MetadataFormat, Package, PackageOpts};
+
+    use super::*;
+
+    fn hash(d: &[u8]) -> [u8; 32] {
+        let mut h = MaybeUninit::<FwsigHasher>::uninit();
+        let mut out = [0u8; 32];
+
+        unsafe {
+            assert_eq!(fwsig_hash_init(h.as_mut_ptr()), FwsigStatus::Ok);
+            for c in d.chunks(7) {
+                assert_eq!(fwsig_hash_update(h.as_mut_ptr(), c.as_ptr(), c.len()), FwsigStatus::Ok);
+            }
+            assert_eq!(fwsig_hash_finish(h.as_mut_ptr(), &mut out), FwsigStatus::Ok);
+        }
+
+        out
+    }
+
+    #[test]
+    fn decode_check_verify() {
+        let key = PrivateKey::generate(&mut OsRng{});
+        let public_key = PublicKey::from(&key);
+        let (app, meta) = ([0xab; 100], [0xcd; 10]);
+
+        let m = ManifestBuilder::new()
+            .app_bin(&app).unwrap()
+            .meta_bin(MetadataFormat::Binary, &meta).unwrap()
+            .build::<OsRng>(Some(key)).unwrap();
+        let p = Package::encode(&app, &meta, &m, &PackageOpts::default()).unwrap();
+
+        let mut fm = MaybeUninit::<FwsigManifest>::uninit();
+        let mut offset = 0;
+        let (app_csum, meta_csum) = (hash(&app), hash(&meta));
+        assert_eq!(&app_csum, &*m.app_csum);
+
+        unsafe {
+            assert_eq!(fwsig_manifest_decode_trailer(p.as_ptr(), p.len(), fm.as_mut_ptr(), &mut offset), FwsigStatus::Ok);
+            assert_eq!(offset, p.len() - m.encode_len().unwrap());
+
+            let fm = fm.assume_init();
+            assert_eq!(Manifest::try_from(&fm).unwrap(), m);
+
+            assert_eq!(fwsig_manifest_check(&fm, &app_csum, app.len(), &meta_csum, meta.len()), FwsigStatus::Ok);
+            assert_eq!(fwsig_manifest_check(&fm, &app_csum, app.len() - 1, &meta_csum, meta.len()), FwsigStatus::AppLengthMismatch);
+            assert_eq!(fwsig_manifest_check(&fm, &meta_csum, app.len(), &meta_csum, meta.len()), FwsigStatus::AppChecksumMismatch);
+
+            let keys = [[0u8; 32], *public_key.as_bytes()];
+            assert_eq!(fwsig_manifest_verify(&fm, keys.as_ptr(), keys.len()), FwsigStatus::Ok);
+            assert_eq!(fwsig_manifest_verify(&fm, keys.as_ptr(), 1), FwsigStatus::NoMatchingKey);
+
+            let mut bad = fm.clone();
+            bad.sig[0] ^= 0x01;
+            assert_eq!(fwsig_manifest_verify(&bad, keys.as_ptr(), keys.len()), FwsigStatus::VerificationFailed);
+            assert_eq!(fwsig_manifest_check(&bad, &app_csum, app.len(), &meta_csum, meta.len()), FwsigStatus::CheckInvalidSignature);
+        }
+    }
+
+    fn app_hash(m: &Manifest, d: &[u8]) -> [u8; 32] {
+        let mut h = MaybeUninit::<FwsigAppHasher>::uninit();
+        let fm = FwsigManifest::from(m);
+        let mut out = [0u8; 32];
+
+        unsafe {
+            assert_eq!(fwsig_app_hash_init(h.as_mut_ptr(), &fm), FwsigStatus::Ok);
+            for c in d.chunks(700) {
+                assert_eq!(fwsig_app_hash_update(h.as_mut_ptr(), c.as_ptr(), c.len()), FwsigStatus::Ok);
+            }
+            assert_eq!(fwsig_app_hash_finish(h.as_mut_ptr(), &mut out), FwsigStatus::Ok);
+        }
+
+        out
+    }
+
+    #[test]
+    fn check_block_hashed() {
+        let app: Vec<u8> = (0..5000u32).map(|v| v as u8).collect();
+        let meta = [0xcd; 10];
+
+        for block_size in [None, Some(1024)] {
+            let mut b = ManifestBuilder::new();
+            if let Some(n) = block_size {
+                b.block_size(n).unwrap();
+            }
+            let m = b.app_bin(&app).unwrap()
+                .meta_bin(MetadataFormat::Binary, &meta).unwrap()
+                .build::<OsRng>(None).unwrap();
+
+            let fm = FwsigManifest::from(&m);
+            let (app_csum, meta_csum) = (app_hash(&m, &app), hash(&meta));
+            assert_eq!(&app_csum, &*m.app_csum);
+
+            unsafe {
+                assert_eq!(fwsig_manifest_check(&fm, &app_csum, app.len(), &meta_csum, meta.len()), FwsigStatus::Ok);
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        let mut fm = MaybeUninit::<FwsigManifest>::uninit();
+
+        unsafe {
+            assert_eq!(fwsig_manifest_decode(core::ptr::null(), 10, fm.as_mut_ptr()), FwsigStatus::InvalidArgument);
+            assert_eq!(fwsig_manifest_decode([0u8; 4].as_ptr(), 4, core::ptr::null_mut()), FwsigStatus::InvalidArgument);
+            assert_eq!(fwsig_manifest_decode([0u8; 4].as_ptr(), 4, fm.as_mut_ptr()), FwsigStatus::UnsupportedVersion);
+            assert_eq!(fwsig_hash_init(core::ptr::null_mut()), FwsigStatus::InvalidArgument);
+            assert_eq!(fwsig_app_hash_init(core::ptr::null_mut(), core::ptr::null()), FwsigStatus::InvalidArgument);
+        }
+    }
+}
@@ -0,0 +1,244 @@
+//! Verification of images in flash via [embedded_storage::nor_flash::ReadNorFlash]
+//!
+//! This is `no_std` compatible and does not allocate, for bootloaders verifying images in
+//! internal or external flash. Slots are read through a caller-provided buffer (at least
+//! [ReadNorFlash::READ_SIZE] bytes), with reads aligned to the flash read size.
+//!
+//! Manifests are located as in [Package::parse](crate::Package::parse), preferring a valid
+//! header manifest ([Flags::HEADER]) and otherwise reading the manifest from the end of the slot.
+
+use embedded_storage::nor_flash::ReadNorFlash;
+use encdec::DecodeOwned;
+use sha2::{Digest, Sha512Trunc256};
+
+use crate::{
+    blocks::AppHasher,
+    types::{Checksum, PublicKey},
+    Flags, Manifest, ManifestError, StreamError, MANIFEST_MAX_LEN,
+};
+
+/// Image located in a flash slot
+#[derive(Clone, Debug, PartialEq)]
+pub struct FlashImage {
+    /// Image manifest
+    pub manifest: Manifest,
+    body_offset: u32,
+    meta_offset: u32,
+}
+
+impl FlashImage {
+    /// Locate the manifest for an image in the slot at `offset` of length `size`
+    ///
+    /// Note this does not check the image checksums, see [FlashImage::check]
+    pub fn locate<F: ReadNorFlash>(flash: &mut F, offset: u32, size: u32, buff: &mut [u8]) -> Result<Self, StreamError<F::Error>> {
+        let end = offset.checked_add(size)
+            .ok_or(ManifestError::PackageLength)?;
+
+        // Read leading and trailing bytes for manifest detection
+        let n = size.min(MANIFEST_MAX_LEN as u32);
+        let (mut head, mut tail) = ([0u8; MANIFEST_MAX_LEN], [0u8; MANIFEST_MAX_LEN]);
+        let (head, tail) = (&mut head[..n as usize], &mut tail[..n as usize]);
+
+        read_into(flash, offset, head, buff)?;
+        read_into(flash, end - n, tail, buff)?;
+
+        // Prefer a valid header manifest, otherwise the trailing manifest
+        let header = match Manifest::decode_owned(head) {
+            Ok((m, n)) if m.flags().contains(Flags::HEADER) => Some((m, n as u32)),
+            _ => None,
+        };
+        let trailer = || Manifest::decode_trailer(tail)
+            .map(|(m, o)| (m, offset, end - n + o as u32));
+
+        let (manifest, body_offset, body_end) = match header {
+            Some((m, n)) if m.check_sig().is_ok() => (m, offset + n, end),
+            Some((m, n)) => trailer().unwrap_or((m, offset + n, end)),
+            None => trailer()?,
+        };
+
+        // Check app and metadata are within the slot
+        let meta_offset = manifest.checked_meta_offset()? as u64;
+        if meta_offset.saturating_add(manifest.meta_len) > (body_end - body_offset) as u64 {
+            return Err(ManifestError::PackageLength.into());
+        }
+
+        Ok(Self { manifest, body_offset, meta_offset: meta_offset as u32 })
+    }
+
+    /// Fetch the flash offset of the application
+    pub fn app_offset(&self) -> u32 {
+        self.body_offset
+    }
+
+    /// Fetch the flash offset of the metadata
+    pub fn meta_offset(&self) -> u32 {
+        self.body_offset + self.meta_offset
+    }
+
+    /// Check the image application and metadata against the manifest, streaming each through the buffer
+    pub fn check<F: ReadNorFlash>(&self, flash: &mut F, buff: &mut [u8]) -> Result<(), StreamError<F::Error>> {
+        let m = &self.manifest;
+
+        // Lengths are bounded by the slot size when locating the image
+        let app_len = u32::try_from(m.app_len).map_err(|_e| ManifestError::PackageLength)?;
+        let meta_len = u32::try_from(m.meta_len).map_err(|_e| ManifestError::PackageLength)?;
+
+        let mut app = AppHasher::new(m);
+        read_range(flash, self.app_offset(), app_len, buff, |d| app.update(d))?;
+
+        let mut meta = Sha512Trunc256::new();
+        read_range(flash, self.meta_offset(), meta_len, buff, |d| meta.update(d))?;
+
+        Ok(m.check_precomputed(&app.finalize(), m.app_len(), &Checksum::from(meta.finalize()), m.meta_len())?)
+    }
+}
+
+/// Verify the image in the slot at `offset` of length `size`, locating the manifest, checking
+/// the signing key against the trusted `keys`, then checking the application and metadata
+pub fn verify<F: ReadNorFlash>(flash: &mut F, offset: u32, size: u32, keys: &[PublicKey], buff: &mut [u8]) -> Result<FlashImage, StreamError<F::Error>> {
+    let i = FlashImage::locate(flash, offset, size, buff)?;
+
+    // Check the signing key prior to hashing the image
+    i.manifest.verify(keys)?;
+
+    i.check(flash, buff)?;
+
+    Ok(i)
+}
+
+/// Internal helper to read flash into the provided slice
+fn read_into<F: ReadNorFlash>(flash: &mut F, offset: u32, out: &mut [u8], buff: &mut [u8]) -> Result<(), StreamError<F::Error>> {
+    let mut n = 0;
+    read_range(flash, offset, out.len() as u32, buff, |d| {
+        out[n..][..d.len()].copy_from_slice(d);
+        n += d.len();
+    })
+}
+
+/// Internal helper to stream `len` bytes from `offset` through the buffer, using reads
+/// aligned to [ReadNorFlash::READ_SIZE]
+///
+/// Ranges past the flash capacity fail with [ManifestError::PackageLength], and the final
+/// aligned read is clamped to the capacity
+fn read_range<F: ReadNorFlash>(flash: &mut F, offset: u32, len: u32, buff: &mut [u8], mut f: impl FnMut(&[u8])) -> Result<(), StreamError<F::Error>> {
+    // Positions are computed in 64 bits so alignment near the end of the address space cannot overflow
+    let align = F::READ_SIZE as u64;
+    let chunk = (buff.len() as u64 / align) * align;
+    if chunk == 0 {
+        return Err(ManifestError::InvalidAlignment.into());
+    }
+
+    let capacity = flash.capacity() as u64;
+    let (offset, end) = (offset as u64, offset as u64 + len as u64);
+    if end > capacity {
+        return Err(ManifestError::PackageLength.into());
+    }
+    let aligned_end = (end.div_ceil(align) * align).min(capacity);
+
+    let mut pos = offset - offset % align;
+    while pos < end {
+        let n = chunk.min(aligned_end - pos);
+        flash.read(pos as u32, &mut buff[..n as usize])
+            .map_err(StreamError::Io)?;
+
+        let (s, e) = (offset.max(pos) - pos, end.min(pos + n) - pos);
+        f(&buff[s as usize..e as usize]);
+
+        pos += n;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_storage::nor_flash::{check_read, ErrorType, NorFlashErrorKind};
+    use rand::rngs::OsRng;
+
+    use crate::{types::PrivateKey, ManifestBuilder, MetadataFormat, Package, PackageOpts, VerifyError, MANIFEST_LEN};
+
+    use super::*;
+
+    /// In-memory flash with a 4-byte read size
+    struct MockFlash(Vec<u8>);
+
+    impl ErrorType for MockFlash {
+        type Error = NorFlashErrorKind;
+    }
+
+    impl ReadNorFlash for MockFlash {
+        const READ_SIZE: usize = 4;
+
+        fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+            check_read(self, offset, bytes.len())?;
+            bytes.copy_from_slice(&self.0[offset as usize..][..bytes.len()]);
+            Ok(())
+        }
+
+        fn capacity(&self) -> usize {
+            self.0.len()
+        }
+    }
+
+    #[test]
+    fn verify_slots() {
+        let key = PrivateKey::generate(&mut OsRng{});
+        let keys = [PublicKey::from(&key)];
+        let (app, meta) = ([0xab; 1001], [0xcd; 13]);
+        let mut buff = [0u8; 64];
+
+        // Slots at 0x1000 and 0x2000, one trailing and one header layout
+        let mut flash = MockFlash(vec![0xFF; 0x3000]);
+        let opts = PackageOpts{ fill: 0xFF, slot_size: Some(0x1000) };
+        for (i, flags) in [(1, Flags::empty()), (2, Flags::HEADER)] {
+            let m = ManifestBuilder::new()
+                .flags(flags)
+                .app_bin(&app).unwrap()
+                .meta_bin(MetadataFormat::Binary, &meta).unwrap()
+                .build::<OsRng>(Some(key.clone())).unwrap();
+
+            let p = Package::encode(&app, &meta, &m, &opts).unwrap();
+            flash.0[i * 0x1000..][..0x1000].copy_from_slice(&p);
+        }
+
+        for (offset, header_len) in [(0x1000, 0), (0x2000, MANIFEST_LEN as u32)] {
+            let i = verify(&mut flash, offset, 0x1000, &keys, &mut buff).unwrap();
+            assert_eq!(i.app_offset(), offset + header_len);
+            assert_eq!(i.meta_offset(), i.app_offset() + 1001);
+
+            // Untrusted keys are rejected before hashing
+            assert_eq!(verify(&mut flash, offset, 0x1000, &[], &mut buff),
+                Err(StreamError::Manifest(ManifestError::NoMatchingKey)));
+        }
+
+        // Corrupt app data is detected
+        flash.0[0x1000 + 500] ^= 0x01;
+        assert_eq!(verify(&mut flash, 0x1000, 0x1000, &keys, &mut buff),
+            Err(StreamError::Verify(VerifyError::AppChecksumMismatch)));
+
+        // Empty slots, out of bounds slots, and undersized buffers fail
+        assert!(verify(&mut flash, 0, 0x1000, &keys, &mut buff).is_err());
+        assert_eq!(verify(&mut flash, 0x2000, 0x2000, &keys, &mut buff),
+            Err(StreamError::Manifest(ManifestError::PackageLength)));
+        assert_eq!(verify(&mut flash, 0x2000, 0x1000, &keys, &mut buff[..3]),
+            Err(StreamError::Manifest(ManifestError::InvalidAlignment)));
+    }
+
+    #[test]
+    fn read_bounds() {
+        let mut buff = [0u8; 64];
+
+        // Unaligned ranges ending at the capacity are read
+        let mut flash = MockFlash((0..0x104).map(|v| v as u8).collect());
+        let mut out = [0u8; 3];
+        read_into(&mut flash, 0x101, &mut out, &mut buff).unwrap();
+        assert_eq!(out, [0x01, 0x02, 0x03]);
+
+        // Ranges past the capacity or end of the address space are rejected
+        let mut out = [0u8; 4];
+        assert_eq!(read_into(&mut flash, 0x101, &mut out, &mut buff),
+            Err(StreamError::Manifest(ManifestError::PackageLength)));
+        assert_eq!(read_range(&mut flash, u32::MAX - 1, 4, &mut buff, |_d| ()),
+            Err(StreamError::Manifest(ManifestError::PackageLength)));
+    }
+}
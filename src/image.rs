@@ -0,0 +1,585 @@
+//! Firmware [Image] loading, flattening ELF, Intel HEX and Motorola S-record
+//! inputs to the binary that will be written to flash (and thus checksummed)
+
+use strum::{Display, EnumString, EnumVariantNames};
+
+use object::read::elf::{ElfFile, FileHeader, ProgramHeader};
+
+use encdec::Encode;
+
+use crate::{EmbedRange, ImageError, Manifest};
+
+/// Firmware image input format
+#[derive(Copy, Clone, Debug, PartialEq, Display, EnumString, EnumVariantNames)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[strum(serialize_all = "snake_case")]
+pub enum ImageFormat {
+    /// Raw binary
+    Bin,
+    /// ELF object, loadable segments are extracted
+    Elf,
+    /// Intel HEX
+    Hex,
+    /// Motorola S-record
+    Srec,
+}
+
+impl ImageFormat {
+    /// Detect image format from the file extension, falling back to [ImageFormat::Elf] for
+    /// files starting with the ELF magic and otherwise [ImageFormat::Bin]
+    ///
+    /// Text formats are only selected by extension, as raw binaries may start with
+    /// bytes that look like Intel HEX or S-record data
+    pub fn detect(path: &str, data: &[u8]) -> Self {
+        let ext = std::path::Path::new(path).extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+
+        match (ext.as_deref(), data) {
+            (Some("elf" | "axf" | "out"), _) => ImageFormat::Elf,
+            (Some("hex" | "ihex" | "ihx"), _) => ImageFormat::Hex,
+            (Some("srec" | "s19" | "s28" | "s37" | "mot"), _) => ImageFormat::Srec,
+            (Some("bin"), _) => ImageFormat::Bin,
+            (_, [0x7f, b'E', b'L', b'F', ..]) => ImageFormat::Elf,
+            _ => ImageFormat::Bin,
+        }
+    }
+}
+
+/// Default maximum span of a flattened image (lowest to highest address), 256 MiB
+pub const DEFAULT_MAX_SPAN: u64 = 256 * 1024 * 1024;
+
+/// Options for flattening segmented images to a binary
+#[derive(Clone, Debug, PartialEq)]
+pub struct FlattenOpts {
+    /// Byte used to fill gaps between segments (typically the erased flash value)
+    pub fill: u8,
+    /// Base address for the flattened image, defaults to the lowest segment address
+    pub base: Option<u64>,
+    /// Maximum flattened image length, as widely separated segments are filled with
+    /// gap bytes (see [DEFAULT_MAX_SPAN])
+    pub max_span: u64,
+}
+
+impl Default for FlattenOpts {
+    fn default() -> Self {
+        Self { fill: 0xFF, base: None, max_span: DEFAULT_MAX_SPAN }
+    }
+}
+
+/// Flattened firmware image
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    /// Load address of the first byte of the image
+    pub base: u64,
+    /// Flattened image data
+    pub data: Vec<u8>,
+}
+
+impl Image {
+    /// Parse and flatten an image in the specified format
+    ///
+    /// Raw binaries are loaded as-is at the configured base address (or zero)
+    pub fn parse(format: ImageFormat, data: &[u8], opts: &FlattenOpts) -> Result<Self, ImageError> {
+        match format {
+            ImageFormat::Bin => Ok(Self { base: opts.base.unwrap_or(0), data: data.to_vec() }),
+            ImageFormat::Elf => Self::flatten(&elf_segments(data)?, opts),
+            ImageFormat::Hex => Self::flatten(&hex_segments(data)?, opts),
+            ImageFormat::Srec => Self::flatten(&srec_segments(data)?, opts),
+        }
+    }
+
+    /// Flatten a set of `(address, data)` segments into a contiguous image
+    ///
+    /// Fails with [ImageError::SpanTooLarge] if the image would exceed [FlattenOpts::max_span]
+    pub fn flatten<D: AsRef<[u8]>>(segments: &[(u64, D)], opts: &FlattenOpts) -> Result<Self, ImageError> {
+        // Sort non-empty segments by address
+        let mut segments: Vec<_> = segments.iter()
+            .map(|(a, d)| (*a, d.as_ref()))
+            .filter(|(_a, d)| !d.is_empty())
+            .collect();
+        segments.sort_by_key(|(a, _d)| *a);
+
+        // Determine image base
+        let base = match (opts.base, segments.first()) {
+            (Some(b), _) => b,
+            (None, Some((a, _d))) => *a,
+            (None, None) => return Err(ImageError::NoSegments),
+        };
+
+        // Copy segments into flattened image
+        let mut data = Vec::new();
+        for (addr, d) in segments {
+            let offset = addr.checked_sub(base)
+                .ok_or(ImageError::SegmentBelowBase)?;
+
+            // Bound the image before filling any gap
+            match offset.checked_add(d.len() as u64) {
+                Some(end) if end <= opts.max_span => (),
+                _ => return Err(ImageError::SpanTooLarge),
+            }
+            let offset = offset as usize;
+
+            if offset < data.len() {
+                return Err(ImageError::OverlappingSegments);
+            }
+
+            data.resize(offset, opts.fill);
+            data.extend_from_slice(d);
+        }
+
+        Ok(Self { base, data })
+    }
+}
+
+/// Load an image from a file, detecting the format if not specified (see [ImageFormat::detect])
+pub fn load_file(path: &str, format: Option<ImageFormat>, opts: &FlattenOpts) -> Result<(ImageFormat, Image), ImageError> {
+    let d = std::fs::read(path)
+        .map_err(|_e| ImageError::Io)?;
+    let format = format.unwrap_or_else(|| ImageFormat::detect(path, &d));
+    let image = Image::parse(format, &d, opts)?;
+    Ok((format, image))
+}
+
+/// ELF section location, used for embedding manifests into reserved sections
+#[derive(Clone, Debug, PartialEq)]
+pub struct ElfSection {
+    /// Offset of section data within the ELF file
+    pub offset: u64,
+    /// Section size in bytes
+    pub size: u64,
+    /// Section (virtual) address
+    pub addr: u64,
+}
+
+/// Extract loadable segments from an ELF file
+///
+/// Segments are placed at their physical (load) address, and only
+/// file-backed data is included (zero-initialised memory is not written to flash)
+pub fn elf_segments(data: &[u8]) -> Result<Vec<(u64, &[u8])>, ImageError> {
+    elf_dispatch(data, |d| elf_segments_inner::<object::elf::FileHeader32<object::Endianness>>(d, None),
+        |d| elf_segments_inner::<object::elf::FileHeader64<object::Endianness>>(d, None))
+}
+
+/// Extract loadable segments from an ELF file, excluding the contents of the provided section
+///
+/// This is used to checksum an image around a reserved manifest section, where excluded
+/// data is treated as a gap (and thus filled or trimmed when flattened)
+pub fn elf_segments_excluding<'a>(data: &'a [u8], exclude: &ElfSection) -> Result<Vec<(u64, &'a [u8])>, ImageError> {
+    let r = Some((exclude.offset, exclude.offset + exclude.size));
+    elf_dispatch(data, |d| elf_segments_inner::<object::elf::FileHeader32<object::Endianness>>(d, r),
+        |d| elf_segments_inner::<object::elf::FileHeader64<object::Endianness>>(d, r))
+}
+
+/// Flatten an ELF file for embedding a manifest into the provided section, returning
+/// the image with the section excluded and the [EmbedRange] it occupies in the image
+///
+/// The range is recorded in the metadata (see [crate::Flags::EMBEDDED]) so verifiers can
+/// replace the embedded manifest with the fill byte when checking the image in place
+pub fn elf_embed_image(data: &[u8], section: &ElfSection, opts: &FlattenOpts) -> Result<(Image, EmbedRange), ImageError> {
+    let image = Image::flatten(&elf_segments_excluding(data, section)?, opts)?;
+
+    let r = (section.offset, section.offset + section.size);
+    let addr = elf_dispatch(data, |d| elf_load_addr_inner::<object::elf::FileHeader32<object::Endianness>>(d, r),
+        |d| elf_load_addr_inner::<object::elf::FileHeader64<object::Endianness>>(d, r))?;
+
+    // Sections outside loadable segments are not part of the image
+    let offset = addr.and_then(|a| a.checked_sub(image.base))
+        .ok_or(ImageError::SectionNotLoaded)?;
+
+    Ok((image, EmbedRange { offset, len: section.size, fill: opts.fill }))
+}
+
+/// Locate a named section with file-backed data in an ELF file
+pub fn elf_section(data: &[u8], name: &str) -> Result<ElfSection, ImageError> {
+    elf_dispatch(data, |d| elf_section_inner::<object::elf::FileHeader32<object::Endianness>>(d, name),
+        |d| elf_section_inner::<object::elf::FileHeader64<object::Endianness>>(d, name))
+}
+
+/// Internal helper to dispatch ELF operations by class (32 or 64-bit)
+fn elf_dispatch<'a, T>(
+    data: &'a [u8],
+    elf32: impl FnOnce(&'a [u8]) -> Result<T, ImageError>,
+    elf64: impl FnOnce(&'a [u8]) -> Result<T, ImageError>,
+) -> Result<T, ImageError> {
+    match data.get(4) {
+        Some(1) => elf32(data),
+        Some(2) => elf64(data),
+        _ => Err(ImageError::InvalidElf),
+    }
+}
+
+/// Write an encoded [Manifest] into a reserved ELF section (located via [elf_section]),
+/// returning the encoded manifest length
+pub fn elf_embed(data: &mut [u8], section: &ElfSection, m: &Manifest) -> Result<usize, ImageError> {
+    let n = m.encode_len()?;
+    if (n as u64) > section.size {
+        return Err(ImageError::SectionTooSmall);
+    }
+
+    let b = data.get_mut(section.offset as usize..)
+        .ok_or(ImageError::InvalidElf)?;
+    Ok(m.encode(b)?)
+}
+
+fn elf_segments_inner<Elf: FileHeader<Endian = object::Endianness>>(data: &[u8], exclude: Option<(u64, u64)>) -> Result<Vec<(u64, &[u8])>, ImageError> {
+    let elf = ElfFile::<Elf>::parse(data)
+        .map_err(|_e| ImageError::InvalidElf)?;
+    let endian = elf.endian();
+
+    let mut segments = Vec::new();
+    for ph in elf.elf_program_headers() {
+        if ph.p_type(endian) != object::elf::PT_LOAD {
+            continue;
+        }
+
+        let d = ph.data(endian, data)
+            .map_err(|_e| ImageError::InvalidElf)?;
+        let addr: u64 = ph.p_paddr(endian).into();
+        let offset: u64 = ph.p_offset(endian).into();
+
+        // Split segments around excluded file ranges
+        match exclude {
+            Some((start, end)) if start < offset + d.len() as u64 && end > offset => {
+                let a = start.saturating_sub(offset) as usize;
+                let b = (end - offset).min(d.len() as u64) as usize;
+
+                segments.push((addr, &d[..a]));
+                segments.push((addr + b as u64, &d[b..]));
+            },
+            _ => segments.push((addr, d)),
+        }
+    }
+
+    Ok(segments)
+}
+
+/// Internal helper to find the load address of a file range within a loadable segment
+fn elf_load_addr_inner<Elf: FileHeader<Endian = object::Endianness>>(data: &[u8], (start, end): (u64, u64)) -> Result<Option<u64>, ImageError> {
+    let elf = ElfFile::<Elf>::parse(data)
+        .map_err(|_e| ImageError::InvalidElf)?;
+    let endian = elf.endian();
+
+    for ph in elf.elf_program_headers() {
+        let offset: u64 = ph.p_offset(endian).into();
+        let len: u64 = ph.p_filesz(endian).into();
+        if ph.p_type(endian) == object::elf::PT_LOAD && start >= offset && end <= offset + len {
+            let addr: u64 = ph.p_paddr(endian).into();
+            return Ok(Some(addr + (start - offset)));
+        }
+    }
+
+    Ok(None)
+}
+
+fn elf_section_inner<Elf: FileHeader<Endian = object::Endianness>>(data: &[u8], name: &str) -> Result<ElfSection, ImageError> {
+    use object::{Object, ObjectSection};
+
+    let elf = ElfFile::<Elf>::parse(data)
+        .map_err(|_e| ImageError::InvalidElf)?;
+
+    let s = elf.section_by_name(name)
+        .ok_or(ImageError::SectionNotFound)?;
+
+    // Sections without file data (ie. NOLOAD / NOBITS) cannot hold a manifest
+    let (offset, size) = s.file_range()
+        .ok_or(ImageError::SectionNotFound)?;
+
+    Ok(ElfSection { offset, size, addr: s.address() })
+}
+
+/// Extract data segments from an Intel HEX file
+pub fn hex_segments(data: &[u8]) -> Result<Vec<(u64, Vec<u8>)>, ImageError> {
+    use ihex::Record;
+
+    let s = core::str::from_utf8(data)
+        .map_err(|_e| ImageError::InvalidHex)?;
+
+    let mut segments = Vec::new();
+    let mut upper = 0u64;
+    for r in ihex::Reader::new(s) {
+        match r.map_err(|_e| ImageError::InvalidHex)? {
+            Record::Data { offset, value } => segments.push((upper + offset as u64, value)),
+            Record::ExtendedSegmentAddress(a) => upper = (a as u64) << 4,
+            Record::ExtendedLinearAddress(a) => upper = (a as u64) << 16,
+            Record::EndOfFile => break,
+            _ => (),
+        }
+    }
+
+    Ok(coalesce(segments))
+}
+
+/// Extract data segments from a Motorola S-record file
+pub fn srec_segments(data: &[u8]) -> Result<Vec<(u64, Vec<u8>)>, ImageError> {
+    let s = core::str::from_utf8(data)
+        .map_err(|_e| ImageError::InvalidSrec)?;
+
+    let mut segments = Vec::new();
+    for l in s.lines().map(|l| l.trim()).filter(|l| !l.is_empty()) {
+        // Parse record type, all records are `S<type><count><address><data><checksum>`
+        let b = l.as_bytes();
+        if b.len() < 4 || b[0] != b'S' || !b.is_ascii() {
+            return Err(ImageError::InvalidSrec);
+        }
+        let kind = b[1];
+
+        let mut r = vec![0u8; (b.len() - 2) / 2];
+        hex::decode_to_slice(&b[2..], &mut r)
+            .map_err(|_e| ImageError::InvalidSrec)?;
+
+        // Check count and checksum
+        if r[0] as usize != r.len() - 1 {
+            return Err(ImageError::InvalidSrec);
+        }
+        let sum = r.iter().fold(0u8, |a, b| a.wrapping_add(*b));
+        if sum != 0xFF {
+            return Err(ImageError::InvalidSrec);
+        }
+
+        // Handle data records, skipping header / count / start address records
+        let addr_len = match kind {
+            b'1' => 2,
+            b'2' => 3,
+            b'3' => 4,
+            b'0' | b'5' | b'6' | b'7' | b'8' | b'9' => continue,
+            _ => return Err(ImageError::InvalidSrec),
+        };
+        if r.len() < 2 + addr_len {
+            return Err(ImageError::InvalidSrec);
+        }
+
+        let addr = r[1..][..addr_len].iter().fold(0u64, |a, b| (a << 8) | *b as u64);
+        segments.push((addr, r[1 + addr_len..r.len() - 1].to_vec()));
+    }
+
+    Ok(coalesce(segments))
+}
+
+/// Merge contiguous record segments to simplify flattening
+fn coalesce(mut segments: Vec<(u64, Vec<u8>)>) -> Vec<(u64, Vec<u8>)> {
+    segments.sort_by_key(|(a, _d)| *a);
+
+    let mut merged: Vec<(u64, Vec<u8>)> = Vec::new();
+    for (a, d) in segments {
+        match merged.last_mut() {
+            Some((la, ld)) if *la + ld.len() as u64 == a => ld.extend_from_slice(&d),
+            _ => merged.push((a, d)),
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEX: &str = ":020000040800F2\n\
+        :0400000001020304F2\n\
+        :0400080005060708DA\n\
+        :00000001FF\n";
+
+    const SREC: &str = "S00600004844521B\n\
+        S3090800000001020304E4\n\
+        S3090800000805060708CC\n\
+        S70508000000F2\n";
+
+    #[test]
+    fn detect() {
+        assert_eq!(ImageFormat::detect("app", b"\x7fELF\x01"), ImageFormat::Elf);
+        assert_eq!(ImageFormat::detect("app.elf", b""), ImageFormat::Elf);
+        assert_eq!(ImageFormat::detect("app.HEX", HEX.as_bytes()), ImageFormat::Hex);
+        assert_eq!(ImageFormat::detect("app.s19", SREC.as_bytes()), ImageFormat::Srec);
+        assert_eq!(ImageFormat::detect("app.bin", b"\x7fELF\x01"), ImageFormat::Bin);
+        assert_eq!(ImageFormat::detect("app", &[0x00, 0x20, 0x00, 0x20]), ImageFormat::Bin);
+
+        // Raw binaries resembling text formats are not parsed
+        assert_eq!(ImageFormat::detect("app.bin", HEX.as_bytes()), ImageFormat::Bin);
+        assert_eq!(ImageFormat::detect("app", b"S1\x00\x20"), ImageFormat::Bin);
+    }
+
+    #[test]
+    fn flatten_hex() {
+        let i = Image::parse(ImageFormat::Hex, HEX.as_bytes(), &FlattenOpts::default()).unwrap();
+        assert_eq!(i.base, 0x0800_0000);
+        assert_eq!(&i.data, &[1, 2, 3, 4, 0xff, 0xff, 0xff, 0xff, 5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn flatten_srec() {
+        let opts = FlattenOpts{ fill: 0x00, base: Some(0x07ff_fffe), ..Default::default() };
+        let i = Image::parse(ImageFormat::Srec, SREC.as_bytes(), &opts).unwrap();
+        assert_eq!(i.base, 0x07ff_fffe);
+        assert_eq!(&i.data, &[0, 0, 1, 2, 3, 4, 0, 0, 0, 0, 5, 6, 7, 8]);
+
+        // Malformed and non-ASCII records are rejected
+        for r in ["S1", "X1030000FC", "S\u{e9}0000", "S10300\u{e9}FC"] {
+            assert_eq!(srec_segments(r.as_bytes()), Err(ImageError::InvalidSrec), "{}", r);
+        }
+    }
+
+    #[test]
+    fn flatten_below_base() {
+        let opts = FlattenOpts{ fill: 0xff, base: Some(0x0800_0004), ..Default::default() };
+        assert_eq!(Image::parse(ImageFormat::Hex, HEX.as_bytes(), &opts), Err(ImageError::SegmentBelowBase));
+    }
+
+    #[test]
+    fn flatten_span() {
+        let opts = FlattenOpts{ max_span: 12, ..Default::default() };
+        assert!(Image::parse(ImageFormat::Hex, HEX.as_bytes(), &opts).is_ok());
+
+        let opts = FlattenOpts{ max_span: 11, ..Default::default() };
+        assert_eq!(Image::parse(ImageFormat::Hex, HEX.as_bytes(), &opts), Err(ImageError::SpanTooLarge));
+
+        // Distant segments are rejected without allocating the gap
+        let segments = [(0u64, vec![0u8; 4]), (u64::MAX - 1, vec![0u8; 4])];
+        assert_eq!(Image::flatten(&segments, &FlattenOpts::default()), Err(ImageError::SpanTooLarge));
+    }
+
+    /// Build a minimal ELF32 LE with a single PT_LOAD segment containing
+    /// `.text`, `.fwsig_manifest` and `.rodata` sections
+    fn test_elf(manifest_len: usize) -> Vec<u8> {
+        let load = 0x54;
+        let text = [0xaa; 8];
+        let rodata = [0xbb; 8];
+        let shstrtab = b"\0.text\0.fwsig_manifest\0.rodata\0.shstrtab\0";
+
+        let load_len = text.len() + manifest_len + rodata.len();
+        let strtab = load + load_len;
+        let shoff = (strtab + shstrtab.len() + 3) & !3;
+
+        let mut e = vec![0u8; shoff + 5 * 40];
+        e[..16].copy_from_slice(b"\x7fELF\x01\x01\x01\0\0\0\0\0\0\0\0\0");
+        e[16..18].copy_from_slice(&2u16.to_le_bytes());
+        e[18..20].copy_from_slice(&40u16.to_le_bytes());
+        e[20..24].copy_from_slice(&1u32.to_le_bytes());
+        e[28..32].copy_from_slice(&0x34u32.to_le_bytes());
+        e[32..36].copy_from_slice(&(shoff as u32).to_le_bytes());
+        e[40..42].copy_from_slice(&0x34u16.to_le_bytes());
+        e[42..44].copy_from_slice(&0x20u16.to_le_bytes());
+        e[44..46].copy_from_slice(&1u16.to_le_bytes());
+        e[46..48].copy_from_slice(&40u16.to_le_bytes());
+        e[48..50].copy_from_slice(&5u16.to_le_bytes());
+        e[50..52].copy_from_slice(&4u16.to_le_bytes());
+
+        let ph = &mut e[0x34..0x54];
+        ph[0..4].copy_from_slice(&object::elf::PT_LOAD.to_le_bytes());
+        ph[4..8].copy_from_slice(&(load as u32).to_le_bytes());
+        ph[8..12].copy_from_slice(&0x0800_0000u32.to_le_bytes());
+        ph[12..16].copy_from_slice(&0x0800_0000u32.to_le_bytes());
+        ph[16..20].copy_from_slice(&(load_len as u32).to_le_bytes());
+        ph[20..24].copy_from_slice(&(load_len as u32).to_le_bytes());
+
+        e[load..][..8].copy_from_slice(&text);
+        e[load + 8 + manifest_len..][..8].copy_from_slice(&rodata);
+        e[strtab..][..shstrtab.len()].copy_from_slice(shstrtab);
+
+        // Section headers: (name, type, offset, size), index 0 is null
+        let sections = [
+            (1u32, object::elf::SHT_PROGBITS, load, 8),
+            (7, object::elf::SHT_PROGBITS, load + 8, manifest_len),
+            (23, object::elf::SHT_PROGBITS, load + 8 + manifest_len, 8),
+            (31, object::elf::SHT_STRTAB, strtab, shstrtab.len()),
+        ];
+        for (i, (name, kind, offset, size)) in sections.iter().enumerate() {
+            let sh = &mut e[shoff + (i + 1) * 40..][..40];
+            sh[0..4].copy_from_slice(&name.to_le_bytes());
+            sh[4..8].copy_from_slice(&kind.to_le_bytes());
+            if *kind == object::elf::SHT_PROGBITS {
+                sh[12..16].copy_from_slice(&(0x0800_0000 + (offset - load) as u32).to_le_bytes());
+            }
+            sh[16..20].copy_from_slice(&(*offset as u32).to_le_bytes());
+            sh[20..24].copy_from_slice(&(*size as u32).to_le_bytes());
+        }
+
+        e
+    }
+
+    #[test]
+    fn embed_elf() {
+        use encdec::Decode;
+        use rand::rngs::OsRng;
+        use crate::{ManifestBuilder, MetadataFormat, MANIFEST_LEN};
+
+        let mut e = test_elf(256);
+        let s = elf_section(&e, ".fwsig_manifest").unwrap();
+        assert_eq!(s.size, 256);
+        assert_eq!(s.addr, 0x0800_0008);
+
+        // Manifest section is excluded from the flattened image
+        let opts = FlattenOpts::default();
+        let i = Image::flatten(&elf_segments_excluding(&e, &s).unwrap(), &opts).unwrap();
+        assert_eq!(i.data.len(), 8 + 256 + 8);
+        assert_eq!(&i.data[..8], &[0xaa; 8]);
+        assert!(i.data[8..][..256].iter().all(|b| *b == 0xff));
+
+        let m = ManifestBuilder::new()
+            .app_bin(&i.data).unwrap()
+            .meta_bin(MetadataFormat::Binary, &[]).unwrap()
+            .build::<OsRng>(None).unwrap();
+        assert_eq!(elf_embed(&mut e, &s, &m), Ok(MANIFEST_LEN));
+
+        // Embedding does not change the checksummed image
+        let i1 = Image::flatten(&elf_segments_excluding(&e, &s).unwrap(), &opts).unwrap();
+        assert_eq!(i, i1);
+
+        let (m1, _) = Manifest::decode(&e[s.offset as usize..]).unwrap();
+        m1.check(&i1.data, &[]).unwrap();
+
+        // Recorded range allows the image to be checked in place, with the manifest present
+        let (i2, r) = elf_embed_image(&e, &s, &opts).unwrap();
+        assert_eq!(i2, i);
+        assert_eq!(r, EmbedRange { offset: 8, len: 256, fill: 0xff });
+
+        let mut flashed = Image::flatten(&elf_segments(&e).unwrap(), &opts).unwrap().data;
+        assert_ne!(flashed, i.data);
+        for (n, c) in flashed.chunks_mut(100).enumerate() {
+            r.apply(n as u64 * 100, c);
+        }
+        assert_eq!(flashed, i.data);
+    }
+
+    #[test]
+    fn embed_elf_errors() {
+        let e = test_elf(64);
+        assert_eq!(elf_section(&e, ".fwsig_missing"), Err(ImageError::SectionNotFound));
+
+        let mut e1 = e.clone();
+        let s = elf_section(&e, ".fwsig_manifest").unwrap();
+        let m = crate::ManifestBuilder::new()
+            .app_bin(&[]).unwrap()
+            .meta_bin(crate::MetadataFormat::Binary, &[]).unwrap()
+            .build::<rand::rngs::OsRng>(None).unwrap();
+        assert_eq!(elf_embed(&mut e1, &s, &m), Err(ImageError::SectionTooSmall));
+        assert_eq!(e1, e);
+    }
+
+    #[test]
+    fn flatten_elf() {
+        // Minimal ELF32 LE with a single PT_LOAD segment (vaddr != paddr)
+        let mut e = vec![0u8; 0x54 + 8];
+        e[..16].copy_from_slice(b"\x7fELF\x01\x01\x01\0\0\0\0\0\0\0\0\0");
+        e[16..18].copy_from_slice(&2u16.to_le_bytes());     // e_type: EXEC
+        e[18..20].copy_from_slice(&40u16.to_le_bytes());    // e_machine: ARM
+        e[20..24].copy_from_slice(&1u32.to_le_bytes());     // e_version
+        e[28..32].copy_from_slice(&0x34u32.to_le_bytes());  // e_phoff
+        e[40..42].copy_from_slice(&0x34u16.to_le_bytes());  // e_ehsize
+        e[42..44].copy_from_slice(&0x20u16.to_le_bytes());  // e_phentsize
+        e[44..46].copy_from_slice(&1u16.to_le_bytes());     // e_phnum
+
+        let ph = &mut e[0x34..0x54];
+        ph[0..4].copy_from_slice(&object::elf::PT_LOAD.to_le_bytes());
+        ph[4..8].copy_from_slice(&0x54u32.to_le_bytes());           // p_offset
+        ph[8..12].copy_from_slice(&0x2000_0000u32.to_le_bytes());   // p_vaddr
+        ph[12..16].copy_from_slice(&0x0800_0000u32.to_le_bytes());  // p_paddr
+        ph[16..20].copy_from_slice(&8u32.to_le_bytes());            // p_filesz
+        ph[20..24].copy_from_slice(&16u32.to_le_bytes());           // p_memsz
+
+        e[0x54..].copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+
+        let i = Image::parse(ImageFormat::Elf, &e, &FlattenOpts::default()).unwrap();
+        assert_eq!(i.base, 0x0800_0000);
+        assert_eq!(&i.data, &[1, 2, 3, 4, 5, 6, 7, 8]);
+    }
+}
@@ -10,8 +10,63 @@ pub use manifest::*;
 mod builder;
 pub use builder::*;
 
+mod package;
+pub use package::*;
+
 mod error;
 pub use error::*;
 
 pub mod types;
 
+pub mod rotation;
+
+pub mod tlog;
+
+pub mod blocks;
+
+pub mod boot;
+
+pub mod update;
+
+#[cfg(feature = "image")]
+pub mod image;
+
+#[cfg(feature = "std")]
+pub mod uf2;
+
+#[cfg(feature = "std")]
+pub mod dfu;
+
+
+#[cfg(feature = "std")]
+pub mod mcuboot;
+
+#[cfg(feature = "cose")]
+pub mod cose;
+
+#[cfg(feature = "suit")]
+pub mod suit;
+
+#[cfg(feature = "std")]
+pub mod vectors;
+
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+#[cfg(feature = "remote")]
+pub mod remote;
+
+#[cfg(feature = "audit")]
+pub mod audit;
+
+#[cfg(feature = "approval")]
+pub mod approval;
+
+#[cfg(feature = "async")]
+pub mod asynch;
+
+#[cfg(feature = "flash")]
+pub mod flash;
+
+#[cfg(test)]
+mod test_utils;
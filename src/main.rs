@@ -2,7 +2,10 @@
 //! 
 //! supports signing, packaging, and verifying binaries
 
-use std::{str::FromStr, fs::File, io::Write};
+use std::{
+    io::{Read, Seek, SeekFrom},
+    str::FromStr,
+};
 
 use clap::Parser;
 use encdec::{EncodeExt, Decode};
@@ -10,11 +13,17 @@ use log::{debug, info, error, LevelFilter, warn};
 use rand_core::OsRng;
 
 use fwsig::{
-    MetadataFormat, ManifestError,
-    types::{PrivateKey, PublicKey}, 
-    ManifestBuilder, MANIFEST_LEN, Manifest,
+    MetadataFormat, ManifestError, Layout, Flags, SigMode,
+    types::{Checksum, PrivateKey, PublicKey}, 
+    ManifestBuilder, MANIFEST_MAX_LEN, Manifest, Package, PackageOpts,
+    image::{self, ImageFormat, FlattenOpts},
+    uf2, dfu, mcuboot, remote, tlog, vectors,
+    blocks::{self, BlockTable, BlockVerifier},
+    audit::{self, AuditLog},
+    approval::SigningRequest,
 };
 use simplelog::SimpleLogger;
+use strum::{Display, EnumString, EnumVariantNames};
 
 /// fwsig firmware signing / packaging / verification utility
 #[derive(Clone, PartialEq, Debug, Parser)]
@@ -28,7 +37,151 @@ struct Args {
     log_level: LevelFilter,
 }
 
+/// Output file format
+#[derive(Copy, Clone, PartialEq, Debug, Display, EnumString, EnumVariantNames, clap::ValueEnum)]
+#[strum(serialize_all = "snake_case")]
+enum OutputFormat {
+    /// Raw binary
+    Raw,
+    /// UF2 blocks, placed at the application base address
+    Uf2,
+    /// Raw binary with DFU suffix
+    Dfu,
+    /// DfuSe file, placed at the application base address
+    Dfuse,
+}
+
+/// Image format for conversion
+#[derive(Copy, Clone, PartialEq, Debug, Display, EnumString, EnumVariantNames, clap::ValueEnum)]
+#[strum(serialize_all = "snake_case")]
+enum ConvertFormat {
+    /// MCUboot image (header, image, TLVs)
+    Mcuboot,
+    /// fwsig attached package
+    Fwsig,
+}
+
+/// Conformance test vector operations
+#[derive(Clone, PartialEq, Debug, Parser)]
+enum ConformanceOps {
+    /// Generate conformance vectors (packages and expected results) from a seed
+    Generate{
+        /// Seed for deterministic key and data generation
+        #[clap(long, default_value = "fwsig")]
+        seed: String,
+
+        /// Output directory
+        output: String,
+    },
+
+    /// Run a verifier over conformance vectors, reporting pass / fail for each vector
+    Run{
+        /// Vector directory
+        vectors: String,
+
+        /// Verifier command, exiting with success for accepted packages. `{file}` and `{key}`
+        /// are replaced by the package path and trusted key (hex), otherwise these are appended.
+        /// If not provided the built-in verifier is used.
+        #[clap(last = true)]
+        command: Vec<String>,
+    },
+}
+
+/// Signing audit log operations
+#[derive(Clone, PartialEq, Debug, Parser)]
+enum AuditOps {
+    /// Verify the audit log hash chain, reporting the number of entries and the head hash
+    Verify{
+        /// Audit log file
+        log: String,
+
+        /// Previously recorded head hash, which must be present in the log (detects truncation)
+        #[clap(long)]
+        head: Option<String>,
+    },
+}
+
+/// Transparency log operations
+#[derive(Clone, PartialEq, Debug, Parser)]
+enum LogOps {
+    /// Append a package manifest to the log, signing the new tree head
+    Append{
+        /// Log directory
+        dir: String,
+
+        /// Signed package (or detached manifest) file
+        package: String,
+
+        /// Log signing key
+        #[clap(value_parser = parse_private_key)]
+        key: PrivateKey,
+    },
+
+    /// Generate an inclusion proof for a logged package, attaching this to the package
+    Prove{
+        /// Log directory
+        dir: String,
+
+        /// Signed package file
+        package: String,
+
+        /// Output file
+        #[clap(long)]
+        output: String,
+
+        /// Write only the inclusion proof to the output file
+        #[clap(long)]
+        detached: bool,
+
+        /// Slot size in bytes, if set the package is padded so the manifest ends the slot
+        #[clap(long, value_parser = parse_usize)]
+        slot_size: Option<usize>,
+
+        /// Fill byte for padding
+        #[clap(long, value_parser = parse_u8, default_value = "0xff")]
+        fill: u8,
+    },
+
+    /// Verify a package inclusion proof against the log key
+    Verify{
+        /// Signed package file
+        package: String,
+
+        /// Log public key
+        #[clap(value_parser = parse_public_key)]
+        key: PublicKey,
+
+        /// Detached inclusion proof file, if not provided the proof attached to the package is used
+        #[clap(long)]
+        proof: Option<String>,
+    },
+}
+
+/// Conformance vector index, written as `vectors.json`
+#[derive(Clone, PartialEq, Debug, serde::Serialize, serde::Deserialize)]
+struct VectorIndex {
+    /// Seed used to generate vectors
+    seed: String,
+    /// Trusted public keys (hex)
+    keys: Vec<String>,
+    /// Vector entries
+    vectors: Vec<VectorEntry>,
+}
+
+/// Conformance vector entry
+#[derive(Clone, PartialEq, Debug, serde::Serialize, serde::Deserialize)]
+struct VectorEntry {
+    name: String,
+    description: String,
+    /// Package file, relative to the index
+    file: String,
+    /// Expected result (`valid` or `invalid`)
+    expected: String,
+}
+
 /// firmware signing / packaging / verification operations
+// Parsed once per invocation, so variant sizes are not a concern
+#[allow(clippy::large_enum_variant)]
 #[derive(Clone, PartialEq, Debug, Parser)]
 enum Ops {
 
@@ -37,17 +190,434 @@ enum Ops {
         /// Application file
         app: String,
 
+        /// Application file format, detected from the file extension (or ELF magic) if not provided
+        #[clap(long)]
+        app_format: Option<ImageFormat>,
+
+        /// Fill byte for gaps between segments when flattening ELF / HEX / S-record inputs
+        #[clap(long, value_parser = parse_u8, default_value = "0xff")]
+        fill: u8,
+
+        /// Maximum flattened image length, rejecting images with widely separated segments
+        #[clap(long, value_parser = parse_u64, default_value = "0x10000000")]
+        max_span: u64,
+
+        /// Base (load) address for the flattened application, defaults to the lowest segment address
+        #[clap(long, value_parser = parse_u64)]
+        base_address: Option<u64>,
+
         /// Metadata file
         meta: String,
         
         /// Metadata format
-        #[clap(long, default_value = "bin")]
+        #[clap(long, default_value = "binary")]
         meta_format: MetadataFormat,
 
+        /// Manifest layout, `large` supports apps over 4GiB and metadata over 64KiB
+        #[clap(long, default_value = "compact")]
+        layout: Layout,
+
+        /// Signature mode, `ed25519` for signers without Ed25519ph support
+        #[clap(long, default_value = "ed25519ph")]
+        sig_mode: SigMode,
+
+        /// Signing key, if not provided a transient per-operation key will be used
+        #[clap(value_parser = parse_private_key, conflicts_with = "remote")]
+        key: Option<PrivateKey>,
+
+        /// Remote signing server URL (see `fwsig serve`), the manifest is built locally
+        /// and only the unsigned manifest is sent for signing
+        #[clap(long, requires = "remote_key")]
+        remote: Option<String>,
+
+        /// Bearer token for the remote signing server
+        #[clap(long, env = "FWSIG_TOKEN", hide_env_values = true, requires = "remote")]
+        token: Option<String>,
+
+        /// Expected remote signing server public key, required with `--remote`
+        #[clap(long, value_parser = parse_public_key, requires = "remote")]
+        remote_key: Option<PublicKey>,
+
+        /// Audit log file, if set an entry for the signature is appended to the log
+        #[clap(long)]
+        audit_log: Option<String>,
+
+        /// Next signing key, if set a commitment to this key is appended to the metadata
+        /// so devices accepting this package will trust the next key for later packages
+        #[clap(long, value_parser = parse_public_key, conflicts_with = "detached")]
+        next_key: Option<PublicKey>,
+
+        /// Block size in bytes (1024, 4096 or 16384), if set the app checksum is a Merkle root
+        /// over app blocks and the block table is included in the package for per-block verification
+        #[clap(long, value_parser = parse_usize)]
+        block_size: Option<usize>,
+
+        /// Block table output file, for distribution alongside detached manifests
+        #[clap(long, requires = "block_size")]
+        block_table: Option<String>,
+
+        /// Output file
+        #[clap(long)]
+        output: String,
+
+        /// Specify only manifest should be written to output file (detached mode)
+        #[clap(long)]
+        detached: bool,
+
+        /// Place the manifest before the app and metadata (header layout)
+        #[clap(long)]
+        header: bool,
+
+        /// Metadata alignment in bytes (eg. flash page size), recorded in the manifest
+        #[clap(long, value_parser = parse_usize)]
+        align: Option<usize>,
+
+        /// Slot size in bytes, if set the package is padded so the manifest ends the slot
+        #[clap(long, value_parser = parse_usize)]
+        slot_size: Option<usize>,
+
+        /// Output file format
+        #[clap(long, default_value = "raw")]
+        output_format: OutputFormat,
+
+        /// UF2 family ID
+        #[clap(long, value_parser = parse_u32)]
+        uf2_family_id: Option<u32>,
+
+        /// DFU USB vendor ID
+        #[clap(long, value_parser = parse_u16, default_value = "0xffff")]
+        dfu_vid: u16,
+
+        /// DFU USB product ID
+        #[clap(long, value_parser = parse_u16, default_value = "0xffff")]
+        dfu_pid: u16,
+
+        /// DfuSe target alternate setting
+        #[clap(long, default_value = "0")]
+        dfu_alt: u8,
+    },
+
+    /// Sign an ELF application, embedding the manifest into a reserved section
+    /// 
+    /// The manifest section is excluded from the application checksum, with the section
+    /// range and fill byte recorded at the end of the metadata (written to `--meta-output`)
+    Embed{
+        /// Application ELF file
+        elf: String,
+
+        /// Metadata file, if not provided empty metadata is used
+        #[clap(long)]
+        meta: Option<String>,
+
+        /// Metadata format
+        #[clap(long, default_value = "binary")]
+        meta_format: MetadataFormat,
+
+        /// Manifest layout
+        #[clap(long, default_value = "compact")]
+        layout: Layout,
+
+        /// Signature mode, `ed25519` for signers without Ed25519ph support
+        #[clap(long, default_value = "ed25519ph")]
+        sig_mode: SigMode,
+
+        /// Name of the reserved manifest section
+        #[clap(long, default_value = ".fwsig_manifest")]
+        section: String,
+
+        /// Fill byte for gaps between segments
+        #[clap(long, value_parser = parse_u8, default_value = "0xff")]
+        fill: u8,
+
+        /// Maximum flattened image length, rejecting images with widely separated segments
+        #[clap(long, value_parser = parse_u64, default_value = "0x10000000")]
+        max_span: u64,
+
         /// Signing key, if not provided a transient per-operation key will be used
         #[clap(value_parser = parse_private_key)]
         key: Option<PrivateKey>,
 
+        /// Audit log file, if set an entry for the signature is appended to the log
+        #[clap(long)]
+        audit_log: Option<String>,
+
+        /// Output file, if not provided the input ELF is updated in place
+        #[clap(long)]
+        output: Option<String>,
+
+        /// Metadata output file, including the embedded range record, defaults to
+        /// the output ELF path with `.meta` appended
+        #[clap(long)]
+        meta_output: Option<String>,
+    },
+
+    /// Verify an ELF application with a manifest embedded in a reserved section (see `embed`)
+    VerifyEmbedded{
+        /// Application ELF file
+        elf: String,
+
+        /// Metadata file (as written by `embed`)
+        meta: String,
+
+        /// Name of the reserved manifest section
+        #[clap(long, default_value = ".fwsig_manifest")]
+        section: String,
+
+        /// Maximum flattened image length, rejecting images with widely separated segments
+        #[clap(long, value_parser = parse_u64, default_value = "0x10000000")]
+        max_span: u64,
+
+        /// Allowed signing keys
+        #[clap(value_parser = parse_public_key)]
+        keys: Vec<PublicKey>,
+    },
+
+    /// Convert between fwsig packages and other signed image formats
+    Convert{
+        /// Input file (fwsig package or MCUboot image)
+        input: String,
+
+        /// Output format
+        #[clap(long, default_value = "mcuboot")]
+        to: ConvertFormat,
+
+        /// Signing key, required for MCUboot output and for fwsig output
+        /// where the MCUboot image does not carry an fwsig manifest
+        #[clap(value_parser = parse_private_key)]
+        key: Option<PrivateKey>,
+
+        /// MCUboot image load address
+        #[clap(long, value_parser = parse_u32, default_value = "0")]
+        load_address: u32,
+
+        /// MCUboot header size (padding the image body)
+        #[clap(long, value_parser = parse_u16, default_value = "0x20")]
+        header_size: u16,
+
+        /// MCUboot image version, defaults to the fwsig app version
+        #[clap(long)]
+        version: Option<mcuboot::ImageVersion>,
+
+        /// Output file
+        #[clap(long)]
+        output: String,
+    },
+
+    /// Export a package signature as a detached COSE_Sign1 object over the encoded manifest
+    #[cfg(feature = "cose")]
+    CoseExport{
+        /// Signed package file
+        package: String,
+
+        /// Signing key for the COSE signature
+        #[clap(value_parser = parse_private_key)]
+        key: PrivateKey,
+
+        /// Output file
+        #[clap(long)]
+        output: String,
+    },
+
+    /// Verify a COSE_Sign1 object against a public key
+    #[cfg(feature = "cose")]
+    CoseVerify{
+        /// COSE_Sign1 file
+        signature: String,
+
+        /// Signing public key
+        #[clap(value_parser = parse_public_key)]
+        key: PublicKey,
+
+        /// Package file, for detached signatures over the package manifest
+        #[clap(long, conflicts_with = "payload")]
+        package: Option<String>,
+
+        /// Payload file, for other detached signatures
+        #[clap(long)]
+        payload: Option<String>,
+    },
+
+    /// Export a package manifest as a signed SUIT envelope
+    #[cfg(feature = "suit")]
+    SuitExport{
+        /// Signed package file
+        package: String,
+
+        /// Signing key for the SUIT envelope
+        #[clap(value_parser = parse_private_key)]
+        key: PrivateKey,
+
+        /// SUIT manifest sequence number
+        #[clap(long, value_parser = parse_u64)]
+        sequence_number: u64,
+
+        /// Vendor identifier (UUID)
+        #[clap(long, value_parser = parse_uuid)]
+        vendor_id: Option<[u8; 16]>,
+
+        /// Class identifier (UUID)
+        #[clap(long, value_parser = parse_uuid)]
+        class_id: Option<[u8; 16]>,
+
+        /// Output file
+        #[clap(long)]
+        output: String,
+    },
+
+    /// Verify application components against a SUIT envelope
+    #[cfg(feature = "suit")]
+    SuitVerify{
+        /// SUIT envelope file
+        envelope: String,
+
+        /// Application file
+        app: String,
+
+        /// Metadata file
+        meta: Option<String>,
+
+        /// Allowed signing keys
+        #[clap(long = "key", value_parser = parse_public_key)]
+        keys: Vec<PublicKey>,
+    },
+
+    /// Run a remote signing server, signing manifests for `sign --remote` clients
+    Serve{
+        /// Signing key
+        #[clap(value_parser = parse_private_key)]
+        key: PrivateKey,
+
+        /// Address to listen on
+        #[clap(long, default_value = "127.0.0.1:8080")]
+        listen: String,
+
+        /// Bearer tokens accepted for signing requests
+        #[clap(long = "token", env = "FWSIG_TOKENS", hide_env_values = true, value_delimiter = ',', required = true)]
+        tokens: Vec<String>,
+
+        /// Application names allowed to be signed (`*` allows any application)
+        #[clap(long = "allow-app", required = true)]
+        allowed_apps: Vec<String>,
+
+        /// Audit log file, if set an entry for each signature is appended to the log
+        #[clap(long)]
+        audit_log: Option<String>,
+    },
+
+    /// Create a signing request (unsigned manifest) for offline multi-party approval
+    Request{
+        /// Application file
+        app: String,
+
+        /// Application file format, detected from the file extension (or ELF magic) if not provided
+        #[clap(long)]
+        app_format: Option<ImageFormat>,
+
+        /// Fill byte for gaps between segments when flattening ELF / HEX / S-record inputs
+        #[clap(long, value_parser = parse_u8, default_value = "0xff")]
+        fill: u8,
+
+        /// Maximum flattened image length, rejecting images with widely separated segments
+        #[clap(long, value_parser = parse_u64, default_value = "0x10000000")]
+        max_span: u64,
+
+        /// Metadata file
+        meta: String,
+
+        /// Metadata format
+        #[clap(long, default_value = "binary")]
+        meta_format: MetadataFormat,
+
+        /// Manifest layout, `large` supports apps over 4GiB and metadata over 64KiB
+        #[clap(long, default_value = "compact")]
+        layout: Layout,
+
+        /// Signature mode, `ed25519` for signers without Ed25519ph support
+        #[clap(long, default_value = "ed25519ph")]
+        sig_mode: SigMode,
+
+        /// Place the manifest before the app and metadata (header layout)
+        #[clap(long)]
+        header: bool,
+
+        /// Metadata alignment in bytes (eg. flash page size), recorded in the manifest
+        #[clap(long, value_parser = parse_usize)]
+        align: Option<usize>,
+
+        /// Release signer key, used as the manifest key
+        #[clap(long, value_parser = parse_public_key)]
+        signer: PublicKey,
+
+        /// Output signing request file
+        #[clap(long)]
+        output: String,
+    },
+
+    /// Review a signing request, optionally checking app and metadata files and adding an approval
+    Review{
+        /// Signing request file
+        request: String,
+
+        /// Application file, checked against the requested manifest
+        #[clap(long)]
+        app: Option<String>,
+
+        /// Application file format, detected from the file extension (or ELF magic) if not provided
+        #[clap(long)]
+        app_format: Option<ImageFormat>,
+
+        /// Fill byte for gaps between application segments
+        #[clap(long, value_parser = parse_u8, default_value = "0xff")]
+        fill: u8,
+
+        /// Maximum flattened application length, rejecting images with widely separated segments
+        #[clap(long, value_parser = parse_u64, default_value = "0x10000000")]
+        max_span: u64,
+
+        /// Metadata file, checked against the requested manifest
+        #[clap(long)]
+        meta: Option<String>,
+
+        /// Approver key, if provided the request is approved and updated
+        #[clap(long, value_parser = parse_private_key)]
+        key: Option<PrivateKey>,
+
+        /// Approver keys, if provided the approval status of each is reported
+        #[clap(long = "approver", value_parser = parse_public_key)]
+        approvers: Vec<PublicKey>,
+
+        /// Output signing request file, if not provided the request is updated in place
+        #[clap(long)]
+        output: Option<String>,
+    },
+
+    /// Finalize an approved signing request, generating the signed package
+    Finalize{
+        /// Signing request file
+        request: String,
+
+        /// Release signer key, must match the requested manifest key
+        #[clap(value_parser = parse_private_key)]
+        key: PrivateKey,
+
+        /// Approver keys, including the release signer
+        #[clap(long = "approver", value_parser = parse_public_key, required = true)]
+        approvers: Vec<PublicKey>,
+
+        /// Number of approvals required, including the release signer
+        #[clap(long)]
+        threshold: usize,
+
+        /// Application file
+        app: String,
+
+        /// Application file format, detected from the file extension (or ELF magic) if not provided
+        #[clap(long)]
+        app_format: Option<ImageFormat>,
+
+        /// Metadata file
+        meta: String,
+
         /// Output file
         #[clap(long)]
         output: String,
@@ -55,6 +625,36 @@ enum Ops {
         /// Specify only manifest should be written to output file (detached mode)
         #[clap(long)]
         detached: bool,
+
+        /// Slot size in bytes, if set the package is padded so the manifest ends the slot
+        #[clap(long, value_parser = parse_usize)]
+        slot_size: Option<usize>,
+
+        /// Fill byte for padding and gaps between segments
+        #[clap(long, value_parser = parse_u8, default_value = "0xff")]
+        fill: u8,
+
+        /// Maximum flattened image length, rejecting images with widely separated segments
+        #[clap(long, value_parser = parse_u64, default_value = "0x10000000")]
+        max_span: u64,
+    },
+
+    /// Transparency log operations
+    Log{
+        #[clap(subcommand)]
+        op: LogOps,
+    },
+
+    /// Signing audit log operations
+    Audit{
+        #[clap(subcommand)]
+        op: AuditOps,
+    },
+
+    /// Generate or run conformance test vectors
+    Conformance{
+        #[clap(subcommand)]
+        op: ConformanceOps,
     },
 
     /// Verify a signed application object (binary + metadata + manifest)
@@ -62,6 +662,10 @@ enum Ops {
         /// Combined application file
         app: String,
 
+        /// Maximum reassembled image length for UF2 / DFU containers
+        #[clap(long, value_parser = parse_usize, default_value = "0x10000000")]
+        max_span: usize,
+
         /// Allowed keys
         #[clap(value_parser = parse_public_key)]
         keys: Vec<PublicKey>,
@@ -95,6 +699,84 @@ fn parse_public_key(v: &str) -> Result<PublicKey, ManifestError> {
     PublicKey::from_str(v)
 }
 
+#[cfg(feature = "suit")]
+fn parse_uuid(v: &str) -> anyhow::Result<[u8; 16]> {
+    let mut b = [0u8; 16];
+    hex::decode_to_slice(v.replace('-', ""), &mut b)
+        .map_err(|e| anyhow::anyhow!("invalid UUID: {}", e))?;
+    Ok(b)
+}
+
+fn parse_u64(v: &str) -> Result<u64, std::num::ParseIntError> {
+    match v.strip_prefix("0x") {
+        Some(h) => u64::from_str_radix(h, 16),
+        None => v.parse(),
+    }
+}
+
+fn parse_usize(v: &str) -> anyhow::Result<usize> {
+    Ok(usize::try_from(parse_u64(v)?)?)
+}
+
+fn parse_u32(v: &str) -> anyhow::Result<u32> {
+    Ok(u32::try_from(parse_u64(v)?)?)
+}
+
+fn parse_u16(v: &str) -> anyhow::Result<u16> {
+    Ok(u16::try_from(parse_u64(v)?)?)
+}
+
+fn parse_u8(v: &str) -> anyhow::Result<u8> {
+    Ok(u8::try_from(parse_u64(v)?)?)
+}
+
+/// Append a signature entry to the audit log (if provided)
+fn audit(path: Option<&str>, m: &Manifest) -> anyhow::Result<()> {
+    if let Some(p) = path {
+        let mut log = AuditLog::open(p)?;
+        let e = log.append(m)?;
+        info!("Recorded audit entry {}, head: {}", e.seq, log.summary().head);
+    }
+    Ok(())
+}
+
+/// Check whether a file is a UF2 or DFU container, reading only the leading and trailing bytes
+fn is_container(path: &str) -> anyhow::Result<bool> {
+    let mut f = std::fs::File::open(path)?;
+    let len = f.metadata()?.len();
+
+    let mut head = vec![0u8; len.min(512) as usize];
+    f.read_exact(&mut head)?;
+
+    let mut tail = [0u8; 8];
+    if len >= tail.len() as u64 {
+        f.seek(SeekFrom::End(-8))?;
+        f.read_exact(&mut tail)?;
+    }
+
+    Ok(uf2::is_uf2(&head) || &tail[..3] == b"UFD")
+}
+
+/// Report corrupt blocks where a package carries a block table
+fn report_blocks(m: &Manifest, extra: &[u8], mut app: impl Read) -> anyhow::Result<()> {
+    let t = match BlockTable::decode(extra) {
+        Ok((t, _n)) => t,
+        Err(_e) => return Ok(()),
+    };
+
+    let v = BlockVerifier::new(m, t)?;
+    let mut b = vec![0u8; v.block_size()];
+    for i in 0..v.num_blocks() {
+        let r = v.block_range(i)?;
+        app.read_exact(&mut b[..r.len()])?;
+        if v.verify_block(i, &b[..r.len()]).is_err() {
+            error!("Block {} (0x{:08x}..0x{:08x}) corrupt", i, r.start, r.end);
+        }
+    }
+
+    Ok(())
+}
+
 fn main() -> anyhow::Result<()> {
     // Parse arguments
     let args = Args::parse();
@@ -104,75 +786,654 @@ fn main() -> anyhow::Result<()> {
 
     // Execute operations
     match args.ops {
-        Ops::Sign { app, meta, meta_format, key, output, detached } => {
+        Ops::Sign { app, app_format, fill, max_span, base_address, meta, meta_format, layout, sig_mode, key, remote, token, remote_key, audit_log, next_key, block_size, block_table, output, detached, header, align, slot_size, output_format, uf2_family_id, dfu_vid, dfu_pid, dfu_alt } => {
             info!("Signing manifest for app: {}", app);
 
-            // Load app and meta files
-            let app = std::fs::read(app)?;
-            let meta = std::fs::read(meta)?;
+            // Load and flatten app image, then load meta file
+            let opts = FlattenOpts{ fill, base: base_address, max_span };
+            let (format, image) = image::load_file(&app, app_format, &opts)?;
+            info!("Loaded {} image: {} bytes at load address 0x{:08x}", format, image.data.len(), image.base);
+
+            let app = &image.data;
+            let mut meta = std::fs::read(meta)?;
 
             // Build manifest
-            let m = ManifestBuilder::new()
-                .app_bin(&app)
-                .meta_bin(meta_format, &meta)
-                .build::<OsRng>(key)?;
+            let mut b = ManifestBuilder::new();
+            if header {
+                b.flags(Flags::HEADER);
+            }
+            if let Some(a) = align {
+                b.meta_align(a)?;
+            }
+            if let Some(n) = block_size {
+                b.block_size(n)?;
+            }
+            b.layout(layout)
+                .sig_mode(sig_mode)
+                .app_bin(app)?;
+
+            // Add metadata, appending the next key commitment if provided
+            match &next_key {
+                Some(k) => {
+                    info!("Committing to next key: {}", k);
+                    b.meta_bin_next_key(meta_format, &meta, k)?;
+                    meta.extend_from_slice(k.commitment().as_slice());
+                },
+                None => {
+                    b.meta_bin(meta_format, &meta)?;
+                },
+            }
+
+            let m = match remote {
+                Some(url) => {
+                    info!("Signing via remote server: {}", url);
+                    let key = remote_key.ok_or_else(|| anyhow::anyhow!("--remote-key is required with --remote"))?;
+                    remote::Client::new(&url, token.as_deref().unwrap_or_default(), key)
+                        .build(&mut b)?
+                },
+                None => b.build::<OsRng>(key)?,
+            };
+            audit(audit_log.as_deref(), &m)?;
             
             // TODO: pretty manifest display
             info!("Generated manifest: {:?}", m);
 
             // Encode manifest data
             debug!("Encoding manifest");
-            let (b, n) = m.encode_buff::<MANIFEST_LEN>()
+            let (b, n) = m.encode_buff::<MANIFEST_MAX_LEN>()
                 .map_err(|_e| anyhow::anyhow!("Encoding error"))?;
 
-            // Write output file
-            let mut f = File::create(output)?;
-            if detached {
+            // Encode block table where block hashing is enabled
+            let table = match m.block_size() {
+                Some(n) => blocks::encode_table(app, n),
+                None => vec![],
+            };
+            if let Some(f) = &block_table {
+                std::fs::write(f, &table)?;
+            }
+
+            let out = if detached {
                 // Detached mode, write manifest to output
-                f.write_all(&b[..n])?;
+                b[..n].to_vec()
             } else {
-                // Combined mode, write app + meta + manifest to output
-                f.write_all(&app)?;
-                f.write_all(&meta)?;
-                f.write_all(&b[..n])?;
-            }
+                // Combined mode, write app + meta + block table + manifest to output (with padding)
+                let opts = PackageOpts{ fill, slot_size };
+                Package::encode_extra(app, &meta, &m, &table, &opts)?
+            };
+
+            // Compute container base address, header manifests precede the app load address
+            let base = match header && !detached {
+                true => image.base.checked_sub(n as u64)
+                    .ok_or_else(|| anyhow::anyhow!("load address too low for header manifest"))?,
+                false => image.base,
+            };
+
+            // Wrap output in container format
+            let out = match output_format {
+                OutputFormat::Raw => out,
+                OutputFormat::Uf2 => {
+                    let base = u32::try_from(base)?;
+                    debug!("Encoding UF2 at 0x{:08x} (family: {:08x?})", base, uf2_family_id);
+                    uf2::encode(&out, base, uf2_family_id)?
+                },
+                OutputFormat::Dfu | OutputFormat::Dfuse => {
+                    let info = dfu::DfuInfo{ vendor_id: dfu_vid, product_id: dfu_pid, ..Default::default() };
+                    if output_format == OutputFormat::Dfu {
+                        dfu::encode(&out, &info)
+                    } else {
+                        let base = u32::try_from(base)?;
+                        debug!("Encoding DfuSe at 0x{:08x} (alt: {}, {:04x}:{:04x})", base, dfu_alt, dfu_vid, dfu_pid);
+                        dfu::encode_dfuse(&out, base, dfu_alt, &info)?
+                    }
+                },
+            };
 
-            f.flush()?;
-            drop(f);
+            // Write output file
+            std::fs::write(output, &out)?;
         },
-        Ops::VerifyAttached { app, keys } => {
-            debug!("Loading combined app file: '{}'", app);
+        Ops::Embed { elf, meta, meta_format, layout, sig_mode, section, fill, max_span, key, audit_log, output, meta_output } => {
+            info!("Embedding manifest for ELF: {}", elf);
 
-            // Read comined app file
-            let data = std::fs::read(app)?;
+            // Load ELF and locate manifest section
+            let mut data = std::fs::read(&elf)?;
+            let s = image::elf_section(&data, &section)?;
+            debug!("Found section '{}' at 0x{:08x} ({} bytes)", section, s.addr, s.size);
 
-            // Parse manifest
-            let b = &data[data.len() - MANIFEST_LEN..];
-            let (m, _) = match Manifest::decode(&b) {
-                Ok(v) => v,
-                Err(e) => {
-                    error!("Failed to decode manifest: {:?}", e);
-                    return Err(anyhow::anyhow!("manifest decode failed"));
-                }
+            // Flatten loadable contents, excluding the manifest section
+            let opts = FlattenOpts{ fill, base: None, max_span };
+            let (app, range) = image::elf_embed_image(&data, &s, &opts)?;
+            info!("Loaded elf image: {} bytes at load address 0x{:08x}", app.data.len(), app.base);
+
+            // Record the excluded range so the image may be checked with the manifest in place
+            let mut meta = match meta {
+                Some(f) => std::fs::read(f)?,
+                None => vec![],
             };
+            meta.extend_from_slice(&range.encode());
+
+            // Build manifest
+            let m = ManifestBuilder::new()
+                .flags(Flags::EMBEDDED)
+                .layout(layout)
+                .sig_mode(sig_mode)
+                .app_bin(&app.data)?
+                .meta_bin(meta_format, &meta)?
+                .build::<OsRng>(key)?;
+            audit(audit_log.as_deref(), &m)?;
+
+            info!("Generated manifest: {:?}", m);
+
+            // Write manifest to section and store output and metadata
+            image::elf_embed(&mut data, &s, &m)?;
+            let output = output.unwrap_or(elf);
+            std::fs::write(&output, &data)?;
+
+            let meta_output = meta_output.unwrap_or_else(|| format!("{}.meta", output));
+            std::fs::write(&meta_output, &meta)?;
+            info!("Wrote metadata to '{}'", meta_output);
+        },
+        Ops::VerifyEmbedded { elf, meta, section, max_span, keys } => {
+            debug!("Loading ELF: '{}'", elf);
+
+            // Load ELF and decode the manifest from the reserved section
+            let data = std::fs::read(&elf)?;
+            let s = image::elf_section(&data, &section)?;
+            let d = data.get(s.offset as usize..).and_then(|d| d.get(..s.size as usize))
+                .ok_or_else(|| anyhow::anyhow!("section '{}' outside file", section))?;
+            let (m, _n) = Manifest::decode(d)?;
 
             info!("Parsed manifest: {:?}", m);
 
-            // Check app and manifest lengths match
-            if data.len() != m.app_len() + m.meta_len() + MANIFEST_LEN {
-                todo!("application length mismatch")
+            // Rebuild the checksummed image using the recorded range and fill
+            let meta = std::fs::read(meta)?;
+            let range = m.embed_range(&meta)?
+                .ok_or_else(|| anyhow::anyhow!("manifest does not record an embedded range"))?;
+            let opts = FlattenOpts{ fill: range.fill, base: None, max_span };
+            let (app, r) = image::elf_embed_image(&data, &s, &opts)?;
+            if r != range {
+                return Err(anyhow::anyhow!("section '{}' does not match the recorded range {:?}", section, range));
+            }
+
+            // Check app and metadata match manifest
+            if let Err(e) = m.check(&app.data, &meta) {
+                error!("Manifest verification failed: {}", e);
+                return Err(e.into())
+            }
+
+            // If we have a set of allowed keys, check the signing key matches
+            if !keys.is_empty() {
+                debug!("Verifying signing keys");
+                m.verify(&keys)?;
+            } else {
+                warn!("No public keys provided, skipping key verification");
+            }
+
+            info!("App signature OK!");
+        },
+        Ops::Convert { input, to, key, load_address, header_size, version, output } => {
+            let data = std::fs::read(&input)?;
+
+            let out = match to {
+                ConvertFormat::Mcuboot => {
+                    info!("Converting fwsig package '{}' to MCUboot image", input);
+
+                    let key = key.ok_or(anyhow::anyhow!("signing key required for MCUboot output"))?;
+
+                    // Parse and check package
+                    let p = Package::parse(&data)?;
+                    p.check()?;
+
+                    // Default to the fwsig app version where this is MCUboot compatible
+                    let version = match version {
+                        Some(v) => v,
+                        None => mcuboot::ImageVersion::from_str(p.manifest.app_version()).unwrap_or_else(|_e| {
+                            warn!("App version '{}' is not an MCUboot version, using 0.0.0", p.manifest.app_version());
+                            Default::default()
+                        }),
+                    };
+
+                    let opts = mcuboot::McubootOpts{ load_addr: load_address, header_size, version };
+                    mcuboot::from_package(&p, &key, &opts)?
+                },
+                ConvertFormat::Fwsig => {
+                    info!("Converting MCUboot image '{}' to fwsig package", input);
+
+                    let i = mcuboot::McubootImage::parse(&data)?;
+                    debug!("Parsed MCUboot image: version {} ({} bytes at 0x{:08x})", i.version, i.body.len(), i.load_addr);
+
+                    // Check the image signature where we have the signing key
+                    match &key {
+                        Some(k) => i.verify(&PublicKey::from(k))?,
+                        None => warn!("No signing key provided, skipping MCUboot signature verification"),
+                    }
+
+                    i.to_package::<OsRng>(key, &PackageOpts::default())?
+                },
+            };
+
+            std::fs::write(output, &out)?;
+        },
+        #[cfg(feature = "cose")]
+        Ops::CoseExport { package, key, output } => {
+            info!("Exporting COSE signature for package: {}", package);
+
+            // Parse and check package
+            let data = std::fs::read(&package)?;
+            let p = Package::parse(&data)?;
+            p.check()?;
+
+            if p.manifest.key != PublicKey::from(&key) {
+                warn!("COSE signing key does not match package signing key");
+            }
+
+            let s = fwsig::cose::sign_manifest(&p.manifest, &key)?;
+            std::fs::write(output, &s)?;
+        },
+        #[cfg(feature = "cose")]
+        Ops::CoseVerify { signature, key, package, payload } => {
+            debug!("Loading COSE signature: '{}'", signature);
+
+            let d = std::fs::read(signature)?;
+            let s = fwsig::cose::Sign1::decode(&d)?;
+
+            // Load detached payload, using the encoded manifest for packages
+            let detached = match (package, payload) {
+                (Some(f), _) => {
+                    let data = std::fs::read(f)?;
+                    let p = Package::parse(&data)?;
+                    let (b, n) = p.manifest.encode_buff::<MANIFEST_MAX_LEN>()
+                        .map_err(|_e| anyhow::anyhow!("Encoding error"))?;
+                    Some(b[..n].to_vec())
+                },
+                (None, Some(f)) => Some(std::fs::read(f)?),
+                (None, None) => None,
+            };
+
+            if let Err(e) = s.verify(&key, detached.as_deref()) {
+                error!("COSE signature verification failed: {:?}", e);
+                return Err(e.into())
+            }
+
+            info!("COSE signature OK!");
+        },
+        #[cfg(feature = "suit")]
+        Ops::SuitExport { package, key, sequence_number, vendor_id, class_id, output } => {
+            info!("Exporting SUIT envelope for package: {}", package);
+
+            // Parse and check package
+            let data = std::fs::read(&package)?;
+            let p = Package::parse(&data)?;
+            p.check()?;
+
+            if p.manifest.key != PublicKey::from(&key) {
+                warn!("SUIT signing key does not match package signing key");
+            }
+
+            let opts = fwsig::suit::SuitOpts{ sequence_number, vendor_id, class_id };
+            let e = fwsig::suit::encode(&p.manifest, &key, &opts)?;
+
+            std::fs::write(output, &e)?;
+        },
+        #[cfg(feature = "suit")]
+        Ops::SuitVerify { envelope, app, meta, keys } => {
+            debug!("Loading SUIT envelope: '{}'", envelope);
+
+            let d = std::fs::read(envelope)?;
+            let s = fwsig::suit::SuitEnvelope::decode(&d)?;
+
+            info!("Parsed SUIT manifest: {:?}", s.manifest);
+
+            // Load app and metadata
+            let app = std::fs::read(app)?;
+            let meta = match meta {
+                Some(f) => std::fs::read(f)?,
+                None => vec![],
+            };
+
+            // Check envelope digest and signature
+            if let Err(e) = s.check_sig() {
+                error!("SUIT envelope signature verification failed: {:?}", e);
+                return Err(e.into())
             }
-            let app = &data[..m.app_len as usize];
-            let meta = &data[m.app_len()..][..m.meta_len()];
 
             // Check app and metadata match manifest
-            if let Err(e) = m.check(app, meta) {
-                error!("Manifest verification failed: {:?}", e);
+            if let Err(e) = s.manifest.check(&app, &meta) {
+                error!("SUIT manifest verification failed: {:?}", e);
                 return Err(e.into())
             }
 
+            // Check the signing key against allowed keys
+            if !keys.is_empty() {
+                debug!("Verifying signing keys");
+                s.verify(&keys)?;
+            } else {
+                warn!("No public keys provided, skipping key verification");
+            }
+
+            info!("SUIT envelope OK!");
+        },
+        Ops::Serve { key, listen, tokens, allowed_apps, audit_log } => {
+            let policy = remote::Policy { tokens, allowed_apps };
+            let mut s = remote::Server::bind(&listen, key, policy)?;
+            if let Some(p) = audit_log {
+                s = s.audit_log(AuditLog::open(p)?);
+            }
+
+            info!("Serving signing requests on {} for key: {}", listen, s.public_key());
+
+            s.run::<OsRng>()?;
+        },
+        Ops::Request { app, app_format, fill, max_span, meta, meta_format, layout, sig_mode, header, align, signer, output } => {
+            info!("Creating signing request for app: {}", app);
+
+            // Load and flatten app image, then load meta file
+            let opts = FlattenOpts{ fill, base: None, max_span };
+            let (_format, image) = image::load_file(&app, app_format, &opts)?;
+            let meta = std::fs::read(meta)?;
+
+            // Build unsigned manifest with the release signer key
+            let mut b = ManifestBuilder::new();
+            if header {
+                b.flags(Flags::HEADER);
+            }
+            if let Some(a) = align {
+                b.meta_align(a)?;
+            }
+            let m = b.layout(layout)
+                .sig_mode(sig_mode)
+                .app_bin(&image.data)?
+                .meta_bin(meta_format, &meta)?
+                .build_unsigned(signer)?;
+
+            let r = SigningRequest::new(m)?;
+            std::fs::write(&output, r.encode()?)?;
+
+            info!("Wrote signing request to '{}'", output);
+        },
+        Ops::Review { request, app, app_format, fill, max_span, meta, key, approvers, output } => {
+            let mut r = SigningRequest::decode(&std::fs::read_to_string(&request)?)?;
+            let m = r.manifest();
+
+            info!("Signing request: {}", request);
+            info!("  app: {} version: {}", m.app_name(), m.app_version());
+            info!("  app length: {} checksum: {}", m.app_len, m.app_csum);
+            info!("  meta length: {} checksum: {} (kind: 0x{:04x})", m.meta_len, m.meta_csum, m.meta_kind);
+            info!("  flags: {:?} signature mode: {}", m.flags(), m.sig_mode()?);
+            info!("  release signer: {}", m.key);
+            for a in r.approvals() {
+                info!("  approval: {}", a.key);
+            }
+            for k in &approvers {
+                info!("  approver: {} ({})", k, if r.is_approved_by(k) || k == &m.key { "approved" } else { "pending" });
+            }
+
+            // Check app and metadata files match the requested manifest
+            if let Some(f) = &app {
+                let opts = FlattenOpts{ fill, base: None, max_span };
+                let (_format, image) = image::load_file(f, app_format, &opts)?;
+                let csum = m.app_checksum(&image.data);
+                if image.data.len() as u64 != m.app_len || csum != m.app_csum {
+                    return Err(anyhow::anyhow!("application '{}' does not match signing request", f));
+                }
+                info!("Application '{}' matches request", f);
+            }
+            if let Some(f) = &meta {
+                let d = std::fs::read(f)?;
+                if d.len() as u64 != m.meta_len || Checksum::compute(&d) != m.meta_csum {
+                    return Err(anyhow::anyhow!("metadata '{}' does not match signing request", f));
+                }
+                info!("Metadata '{}' matches request", f);
+            }
+
+            // Add approval and update request
+            if let Some(k) = key {
+                let a = r.approve(&k)?;
+                info!("Approved request with key: {}", a.key);
+
+                let output = output.as_ref().unwrap_or(&request);
+                std::fs::write(output, r.encode()?)?;
+
+                info!("Wrote signing request to '{}' ({} approvals)", output, r.approvals().len());
+            }
+        },
+        Ops::Finalize { request, key, approvers, threshold, app, app_format, meta, output, detached, slot_size, fill, max_span } => {
+            let r = SigningRequest::decode(&std::fs::read_to_string(&request)?)?;
+            let m = r.finalize::<OsRng>(key, &approvers, threshold)?;
+
+            // Load app and metadata and check these match the approved manifest
+            let opts = FlattenOpts{ fill, base: None, max_span };
+            let (_format, image) = image::load_file(&app, app_format, &opts)?;
+            let meta = std::fs::read(meta)?;
+            m.check(&image.data, &meta)?;
+
+            info!("Finalized manifest with {} of {} approvals: {:?}", r.count_approvals(&approvers), threshold, m);
+
+            let out = if detached {
+                let (b, n) = m.encode_buff::<MANIFEST_MAX_LEN>()
+                    .map_err(|_e| anyhow::anyhow!("Encoding error"))?;
+                b[..n].to_vec()
+            } else {
+                let opts = PackageOpts{ fill, slot_size };
+                Package::encode(&image.data, &meta, &m, &opts)?
+            };
+
+            std::fs::write(output, &out)?;
+        },
+        Ops::Log { op: LogOps::Append { dir, package, key } } => {
+            let d = std::fs::read(&package)?;
+            let m = match Package::parse(&d) {
+                Ok(p) => {
+                    p.check()?;
+                    p.manifest
+                },
+                Err(_e) => Manifest::decode(&d)?.0,
+            };
+
+            let mut log = tlog::Log::open(&dir)?;
+            let (index, head) = log.append(&m, &key)?;
+
+            info!("Appended '{}' at index {}, tree size: {} root: {}", package, index, head.size, hex::encode(head.root));
+        },
+        Ops::Log { op: LogOps::Prove { dir, package, output, detached, slot_size, fill } } => {
+            let d = std::fs::read(&package)?;
+            let p = Package::parse(&d)?;
+            p.check()?;
+
+            let log = tlog::Log::open(&dir)?;
+            let proof = log.prove(&p.manifest)?;
+            let (b, n) = proof.encode_buff::<1024>()?;
+
+            info!("Generated proof for '{}' at index {}, tree size: {}", package, proof.index, proof.head.size);
+
+            let out = match detached {
+                true => b[..n].to_vec(),
+                false => {
+                    // Retain any block table, replacing existing proofs
+                    let mut extra = match BlockTable::decode(p.extra) {
+                        Ok((_t, n)) => p.extra[..n].to_vec(),
+                        Err(_e) => vec![],
+                    };
+                    extra.extend_from_slice(&b[..n]);
+
+                    Package::encode_extra(p.app, p.meta, &p.manifest, &extra, &PackageOpts{ fill, slot_size })?
+                },
+            };
+
+            std::fs::write(output, &out)?;
+        },
+        Ops::Log { op: LogOps::Verify { package, key, proof } } => {
+            let d = std::fs::read(&package)?;
+            let p = Package::parse(&d)?;
+            p.check()?;
+
+            let proof = match proof {
+                Some(f) => tlog::InclusionProof::decode(&std::fs::read(f)?)?.0,
+                None => p.inclusion_proof()?
+                    .ok_or_else(|| anyhow::anyhow!("package does not contain an inclusion proof"))?,
+            };
+
+            proof.verify(&p.manifest, &key)?;
+
+            info!("Inclusion proof OK! index {}, tree size: {} root: {}", proof.index, proof.head.size, hex::encode(proof.head.root));
+        },
+        Ops::Audit { op: AuditOps::Verify { log, head } } => {
+            let s = audit::verify(&log, head.as_deref())?;
+            info!("Audit log OK: {} entries, head: {}", s.entries, s.head);
+        },
+        Ops::Conformance { op: ConformanceOps::Generate { seed, output } } => {
+            info!("Generating conformance vectors with seed '{}'", seed);
+
+            let v = vectors::generate(seed.as_bytes())?;
+            std::fs::create_dir_all(&output)?;
+
+            // Write vector packages and index
+            let mut entries = vec![];
+            for t in &v.vectors {
+                let file = format!("{}.bin", t.name);
+                std::fs::write(std::path::Path::new(&output).join(&file), &t.data)?;
+
+                entries.push(VectorEntry{
+                    name: t.name.to_string(),
+                    description: t.description.to_string(),
+                    file,
+                    expected: t.expected.to_string(),
+                });
+            }
+
+            let index = VectorIndex{
+                seed,
+                keys: v.keys.iter().map(|k| k.to_string()).collect(),
+                vectors: entries,
+            };
+            let f = std::fs::File::create(std::path::Path::new(&output).join("vectors.json"))?;
+            serde_json::to_writer_pretty(f, &index)?;
+
+            info!("Wrote {} vectors to '{}'", index.vectors.len(), output);
+        },
+        Ops::Conformance { op: ConformanceOps::Run { vectors: dir, command } } => {
+            let dir = std::path::Path::new(&dir);
+            let f = std::fs::File::open(dir.join("vectors.json"))?;
+            let index: VectorIndex = serde_json::from_reader(f)?;
+
+            let keys = index.keys.iter()
+                .map(|k| PublicKey::from_str(k))
+                .collect::<Result<Vec<_>, _>>()?;
+            if keys.is_empty() {
+                return Err(anyhow::anyhow!("no trusted keys in '{}'", dir.join("vectors.json").display()));
+            }
+
+            let mut failures = 0;
+            for e in &index.vectors {
+                let expected = vectors::Expected::from_str(&e.expected)
+                    .map_err(|_e| anyhow::anyhow!("invalid expected result '{}' for vector {}", e.expected, e.name))?;
+                let path = dir.join(&e.file);
+
+                // Run built-in or external verifier
+                let result = match command.split_first() {
+                    None => {
+                        let t = vectors::Vector{ name: "", description: "", expected, data: std::fs::read(&path)? };
+                        t.verify(&keys)
+                    },
+                    Some((cmd, args)) => {
+                        let (file, key) = (path.to_string_lossy(), &index.keys[0]);
+                        let mut c = std::process::Command::new(cmd);
+                        if args.iter().any(|a| a.contains("{file}") || a.contains("{key}")) {
+                            c.args(args.iter().map(|a| a.replace("{file}", &file).replace("{key}", key)));
+                        } else {
+                            c.args(args).arg(file.as_ref()).arg(key);
+                        }
+
+                        match c.output()?.status.success() {
+                            true => vectors::Expected::Valid,
+                            false => vectors::Expected::Invalid,
+                        }
+                    },
+                };
+
+                if result == expected {
+                    info!("PASS {} ({})", e.name, expected);
+                } else {
+                    error!("FAIL {} (expected {}, got {}): {}", e.name, expected, result, e.description);
+                    failures += 1;
+                }
+            }
+
+            if failures > 0 {
+                return Err(anyhow::anyhow!("{} of {} vectors failed", failures, index.vectors.len()));
+            }
+            info!("All {} vectors passed", index.vectors.len());
+        },
+        Ops::VerifyAttached { app, max_span, keys } => {
+            debug!("Loading combined app file: '{}'", app);
+
+            // Unpack container files (UF2 / DFU) in memory, otherwise stream the package from the file
+            let (m, meta) = match is_container(&app)? {
+                true => {
+                    let mut data = std::fs::read(app)?;
+                    if uf2::is_uf2(&data) {
+                        let u = uf2::decode(&data, max_span)?;
+                        debug!("Reassembled UF2 at 0x{:08x} (family: {:08x?}, {} bytes)", u.base, u.family_id, u.data.len());
+                        data = u.data;
+                    } else if dfu::is_dfu(&data) {
+                        let d = dfu::decode(&data, max_span)?;
+                        debug!("Unpacked DFU file (address: {:08x?}, alt: {:?}, {:04x}:{:04x}, {} bytes)",
+                            d.address, d.alt, d.info.vendor_id, d.info.product_id, d.data.len());
+                        data = d.data;
+                    }
+
+                    // Parse package, locating manifest, app and metadata
+                    let p = match Package::parse(&data) {
+                        Ok(v) => v,
+                        Err(e) => {
+                            error!("Failed to parse package: {:?}", e);
+                            return Err(anyhow::anyhow!("package parse failed"));
+                        }
+                    };
+
+                    info!("Parsed manifest: {:?}", p.manifest);
+
+                    // Check app and metadata match manifest
+                    if let Err(e) = p.check() {
+                        error!("Manifest verification failed: {:?}", e);
+                        report_blocks(&p.manifest, p.extra, p.app)?;
+                        return Err(e.into())
+                    }
+
+                    (p.manifest.clone(), p.meta.to_vec())
+                },
+                false => {
+                    // Open package, locating manifest, app and metadata
+                    let mut p = match Package::open(&app) {
+                        Ok(v) => v,
+                        Err(e) => {
+                            error!("Failed to parse package: {}", e);
+                            return Err(anyhow::anyhow!("package parse failed"));
+                        }
+                    };
+
+                    info!("Parsed manifest: {:?}", p.manifest);
+
+                    // Check app and metadata match manifest, streaming the app
+                    if let Err(e) = p.check() {
+                        error!("Manifest verification failed: {}", e);
+                        let (m, extra) = (p.manifest.clone(), p.extra()?);
+                        report_blocks(&m, &extra, p.app()?)?;
+                        return Err(e.into())
+                    }
+
+                    let mut meta = vec![];
+                    p.meta()?.read_to_end(&mut meta)?;
+
+                    (p.manifest, meta)
+                },
+            };
+
+            if let Some(c) = m.next_key(&meta)? {
+                info!("Next key commitment: {}", c);
+            }
+
             // If we have a set of allowed keys, check the signing key matches
-            if keys.len() > 0 {
+            if !keys.is_empty() {
                 debug!("Verifying signing keys");
                 m.verify(&keys)?;
             
@@ -197,18 +1458,18 @@ fn main() -> anyhow::Result<()> {
 
             info!("Parsed manifest: {:?}", m);
 
-            // Load app and metadata
-            let app = std::fs::read(app)?;
-            let meta = std::fs::read(meta)?;
+            // Open app and metadata
+            let app = std::fs::File::open(app)?;
+            let meta = std::fs::File::open(meta)?;
             
-            // Check app and metadata match manifest
-            if let Err(e) = m.check(&app, &meta) {
-                error!("Manifest verification failed: {:?}", e);
+            // Check app and metadata match manifest, streaming from the files
+            if let Err(e) = m.check_reader(std::io::BufReader::new(app), meta) {
+                error!("Manifest verification failed: {}", e);
                 return Err(e.into())
             }
 
             // If we have a set of allowed keys, check the signing key matches
-            if keys.len() > 0 {
+            if !keys.is_empty() {
                 debug!("Verifying signing keys");
                 m.verify(&keys)?;
             
@@ -4,6 +4,7 @@
 use core::ops::Deref;
 
 use bitflags::bitflags;
+use ed25519_dalek::{Signer, Verifier};
 use encdec::{Encode, Decode, DecodeOwned};
 use rand_core::{RngCore, CryptoRng};
 use sha2::{Sha512, Digest};
@@ -19,6 +20,9 @@ use crate::{
 /// Manifest version identifier, MUST be 0x0001, MAY be extended in following versions
 pub const MANIFEST_VERSION: u16 = 0x0001;
 
+/// Manifest version identifier for the [Layout::Large] variant with 64-bit lengths
+pub const MANIFEST_VERSION_LARGE: u16 = 0x0002;
+
 /// Encoded manifest length, constant to simplify parsing when included in binary form
 pub const MANIFEST_LEN: usize = 2 + 2 
     + 16 + 24
@@ -27,6 +31,92 @@ pub const MANIFEST_LEN: usize = 2 + 2
     + ed25519_dalek::PUBLIC_KEY_LENGTH
     + ed25519_dalek::SIGNATURE_LENGTH;
 
+/// Encoded manifest length for the [Layout::Large] variant
+pub const MANIFEST_LEN_LARGE: usize = 2 + 2 
+    + 16 + 24
+    + 8 + 32 
+    + 2 + 2 + 8 + 32 
+    + ed25519_dalek::PUBLIC_KEY_LENGTH
+    + ed25519_dalek::SIGNATURE_LENGTH;
+
+/// Maximum encoded manifest length over all supported layouts
+pub const MANIFEST_MAX_LEN: usize = MANIFEST_LEN_LARGE;
+
+/// Manifest layout enumeration, selects the width of length fields
+#[derive(Copy, Clone, Debug, PartialEq, Display, EnumString, EnumVariantNames)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[strum(serialize_all = "snake_case")]
+pub enum Layout {
+    /// Compact layout (version 1), 32-bit app and 16-bit metadata lengths
+    Compact,
+    /// Large layout (version 2), 64-bit app and metadata lengths
+    Large,
+}
+
+impl Layout {
+    /// Fetch the layout for a given manifest version
+    pub fn from_version(version: u16) -> Result<Self, ManifestError> {
+        match version {
+            MANIFEST_VERSION => Ok(Layout::Compact),
+            MANIFEST_VERSION_LARGE => Ok(Layout::Large),
+            _ => Err(ManifestError::UnsupportedVersion),
+        }
+    }
+
+    /// Fetch the manifest version for this layout
+    pub fn version(&self) -> u16 {
+        match self {
+            Layout::Compact => MANIFEST_VERSION,
+            Layout::Large => MANIFEST_VERSION_LARGE,
+        }
+    }
+
+    /// Fetch the encoded manifest length for this layout
+    pub fn encoded_len(&self) -> usize {
+        match self {
+            Layout::Compact => MANIFEST_LEN,
+            Layout::Large => MANIFEST_LEN_LARGE,
+        }
+    }
+
+    /// Fetch the maximum application length supported by this layout
+    pub fn max_app_len(&self) -> u64 {
+        match self {
+            Layout::Compact => u32::MAX as u64,
+            Layout::Large => u64::MAX,
+        }
+    }
+
+    /// Fetch the maximum metadata length supported by this layout
+    pub fn max_meta_len(&self) -> u64 {
+        match self {
+            Layout::Compact => u16::MAX as u64,
+            Layout::Large => u64::MAX,
+        }
+    }
+}
+
+/// Signing context for [SigMode::Ed25519ph] and [SigMode::Ed25519] manifests
+pub const SIG_CONTEXT: &[u8] = b"fwsig-manifest-v1";
+
+/// Length of the message signed in [SigMode::Ed25519] (see [Manifest::signing_message])
+pub const SIG_MESSAGE_LEN: usize = SIG_CONTEXT.len() + 64;
+
+/// Signature mode enumeration, recorded in the manifest [Flags::SIG_MODE] field
+#[derive(Copy, Clone, Debug, PartialEq, Display, EnumString, EnumVariantNames)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[strum(serialize_all = "snake_case")]
+#[repr(u16)]
+pub enum SigMode {
+    /// Ed25519ph over the manifest digest without a context (legacy manifests)
+    Legacy = 0,
+    /// Ed25519ph over the manifest digest with the [SIG_CONTEXT] context
+    Ed25519ph = 1,
+    /// Pure Ed25519 over [SIG_CONTEXT] followed by the manifest digest,
+    /// for signers without Ed25519ph support (eg. some secure elements)
+    Ed25519 = 2,
+}
+
 /// Metadata format enumeration
 #[derive(Copy, Clone, Debug, PartialEq, Display, EnumString, EnumVariantNames)]
 #[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
@@ -40,15 +130,97 @@ pub enum MetadataFormat {
 
 bitflags! {
     /// Manifest information flags
+    ///
+    /// Flags are covered by the signature, and verifiers reject manifests with bits not defined here
     pub struct Flags: u16 {
         /// Indicate the signing key is transient / should not prompt for TOFU if available
         const TRANSIENT_KEY = 1 << 0;
+
+        /// Indicate the manifest precedes the app and metadata in attached packages
+        /// (header layout), rather than trailing them
+        const HEADER = 1 << 1;
+
+        /// Indicate the metadata ends with a commitment to the next signing key
+        /// ([NEXT_KEY_LEN] bytes, see [Manifest::next_key] and [PublicKey::commitment])
+        const NEXT_KEY = 1 << 4;
+
+        /// Signature mode field (see [SigMode])
+        const SIG_MODE = 0b11 << SIG_MODE_SHIFT;
+
+        /// Block size field, where non-zero the application checksum is a Merkle root over
+        /// fixed-size blocks of `1 << (8 + 2n)` bytes (1, 4, or 16 KiB, see [crate::blocks])
+        const BLOCK_SIZE = 0b11 << BLOCK_SIZE_SHIFT;
+
+        /// Metadata alignment field, metadata starts at the application length
+        /// rounded up to `1 << n` bytes where `n` is non-zero (see [Manifest::checked_meta_offset])
+        const META_ALIGN = 0x1F << META_ALIGN_SHIFT;
+
+        /// Indicate the manifest is embedded within the application, with the metadata ending
+        /// with an [EmbedRange] record (preceding any next key commitment, see [Manifest::embed_range])
+        const EMBEDDED = 1 << 13;
     }
 }
 
+/// Length of the next key commitment trailing metadata where [Flags::NEXT_KEY] is set
+pub const NEXT_KEY_LEN: usize = 32;
+
+/// Length of the [EmbedRange] record in metadata where [Flags::EMBEDDED] is set
+pub const EMBED_RANGE_LEN: usize = 17;
+
+/// Application range reserved for an embedded manifest (eg. an ELF section), which is
+/// replaced with the fill byte when computing the application checksum
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct EmbedRange {
+    /// Offset of the reserved range within the application
+    pub offset: u64,
+    /// Length of the reserved range, this may extend past the application
+    pub len: u64,
+    /// Fill byte for the reserved range
+    pub fill: u8,
+}
+
+impl EmbedRange {
+    /// Encode the range for inclusion in metadata
+    pub fn encode(&self) -> [u8; EMBED_RANGE_LEN] {
+        let mut b = [0u8; EMBED_RANGE_LEN];
+        b[..8].copy_from_slice(&self.offset.to_le_bytes());
+        b[8..16].copy_from_slice(&self.len.to_le_bytes());
+        b[16] = self.fill;
+        b
+    }
+
+    /// Decode an encoded range
+    pub fn decode(b: &[u8; EMBED_RANGE_LEN]) -> Self {
+        Self {
+            offset: u64::from_le_bytes(b[..8].try_into().unwrap()),
+            len: u64::from_le_bytes(b[8..16].try_into().unwrap()),
+            fill: b[16],
+        }
+    }
+
+    /// Replace any part of the reserved range within `data`, located at `pos` in
+    /// the application, with the fill byte (for streaming application data)
+    pub fn apply(&self, pos: u64, data: &mut [u8]) {
+        let end = pos.saturating_add(data.len() as u64);
+        let (s, e) = (self.offset.max(pos), self.offset.saturating_add(self.len).min(end));
+        if s < e {
+            data[(s - pos) as usize..(e - pos) as usize].fill(self.fill);
+        }
+    }
+}
+
+/// Bit offset of the [Flags::SIG_MODE] field
+pub const SIG_MODE_SHIFT: u16 = 2;
+
+/// Bit offset of the [Flags::BLOCK_SIZE] field
+pub const BLOCK_SIZE_SHIFT: u16 = 6;
+
+/// Bit offset of the [Flags::META_ALIGN] field
+pub const META_ALIGN_SHIFT: u16 = 8;
+
 /// Applet manifest, links app and metadata checksums with overall applet signature
 /// 
-/// Encoding:
+/// Encoding ([Layout::Compact], version 1):
 /// 
 /// ```text
 /// 0                   1                   2                   3
@@ -92,9 +264,34 @@ bitflags! {
 /// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
 /// ```
 /// 
-#[derive(Clone, Debug, PartialEq, Encode, DecodeOwned)]
+/// The [Layout::Large] encoding (version 2) widens the length fields
+/// to support applications over 4 GiB and metadata over 64 KiB:
+/// 
+/// ```text
+/// 0                   1                   2                   3
+/// 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |       MANIFEST_VERSION        |         MANIFEST_FLAGS        |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// /                  APP_NAME, APP_VERSION (as v1)                /
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |                        APP_LENGTH (u64)                       |
+/// |                                                               |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// /                    APP_CHECKSUM (as v1)                       /
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |           META_KIND           |        RESERVED (zero)        |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |                       META_LENGTH (u64)                       |
+/// |                                                               |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// /         META_CHECKSUM, SIGNING KEY, SIGNATURE (as v1)         /
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// ```
+/// 
+#[derive(Clone, Debug, PartialEq)]
 pub struct Manifest {
-    /// Manifest version (must be 1)
+    /// Manifest version (1 for [Layout::Compact], 2 for [Layout::Large])
     pub version: u16,
     /// Manifest flags
     pub flags: u16,
@@ -106,14 +303,14 @@ pub struct Manifest {
     pub app_version: Stringish<24>,
 
     /// Application binary length
-    pub app_len: u32,
+    pub app_len: u64,
     /// Application binary checksum (sha512)
     pub app_csum: Checksum,
 
     /// Metadata encoding kind
     pub meta_kind: u16,
     /// Metadata binary length
-    pub meta_len: u16,
+    pub meta_len: u64,
     /// Metadata binary checksum
     pub meta_csum: Checksum,
 
@@ -135,6 +332,11 @@ impl Manifest {
         Flags::from_bits_truncate(self.flags)
     }
 
+    /// Fetch manifest [Layout] (fails for unsupported versions)
+    pub fn layout(&self) -> Result<Layout, ManifestError> {
+        Layout::from_version(self.version)
+    }
+
     /// Fetch app name
     pub fn app_name(&self) -> &str {
         self.app_name.as_ref()
@@ -146,32 +348,168 @@ impl Manifest {
     }
 
     /// Fetch app length
+    ///
+    /// Lengths exceeding the address space saturate to `usize::MAX`, so never match a buffer length
     pub fn app_len(&self) -> usize {
-        self.app_len as usize
+        usize::try_from(self.app_len).unwrap_or(usize::MAX)
     }
 
     /// Fetch metadata length
+    ///
+    /// Lengths exceeding the address space saturate to `usize::MAX`, so never match a buffer length
     pub fn meta_len(&self) -> usize {
-        self.meta_len as usize
+        usize::try_from(self.meta_len).unwrap_or(usize::MAX)
+    }
+
+    /// Fetch manifest [SigMode] (fails for unsupported modes)
+    pub fn sig_mode(&self) -> Result<SigMode, ManifestError> {
+        match (self.flags() & Flags::SIG_MODE).bits() >> SIG_MODE_SHIFT {
+            0 => Ok(SigMode::Legacy),
+            1 => Ok(SigMode::Ed25519ph),
+            2 => Ok(SigMode::Ed25519),
+            _ => Err(ManifestError::UnsupportedSigMode),
+        }
+    }
+
+    /// Fetch metadata alignment in bytes (1 where metadata is unaligned)
+    pub fn meta_align(&self) -> usize {
+        let n = (self.flags() & Flags::META_ALIGN).bits() >> META_ALIGN_SHIFT;
+        1 << n
+    }
+
+    /// Fetch the application block size where block hashing is enabled (see [Flags::BLOCK_SIZE])
+    pub fn block_size(&self) -> Option<usize> {
+        match (self.flags() & Flags::BLOCK_SIZE).bits() >> BLOCK_SIZE_SHIFT {
+            0 => None,
+            n => Some(1 << (8 + 2 * n)),
+        }
+    }
+
+    /// Compute the application checksum for comparison with the manifest, a Merkle root
+    /// over application blocks where block hashing is enabled (see [crate::blocks::block_root])
+    pub fn app_checksum(&self, app: &[u8]) -> Checksum {
+        match self.block_size() {
+            Some(n) => Checksum(crate::blocks::block_root(app, n)),
+            None => Checksum::compute(app),
+        }
+    }
+
+    /// Compute the metadata offset from the start of an attached package,
+    /// the application length rounded up to [Manifest::meta_align]
+    ///
+    /// Fails with [ManifestError::PackageLength] where the offset is not addressable
+    pub fn checked_meta_offset(&self) -> Result<usize, ManifestError> {
+        let a = self.meta_align() as u64;
+        self.app_len.div_ceil(a).checked_mul(a)
+            .and_then(|n| usize::try_from(n).ok())
+            .ok_or(ManifestError::PackageLength)
+    }
+
+    /// Fetch the commitment to the next signing key from the metadata trailer
+    /// where [Flags::NEXT_KEY] is set (see [crate::rotation])
+    ///
+    /// Metadata should be checked against the manifest prior to use
+    pub fn next_key(&self, meta: &[u8]) -> Result<Option<Checksum>, VerifyError> {
+        if !self.flags().contains(Flags::NEXT_KEY) {
+            return Ok(None);
+        }
+
+        let offset = meta.len().checked_sub(NEXT_KEY_LEN)
+            .ok_or(VerifyError::MetaLengthMismatch)?;
+
+        let mut c = [0u8; NEXT_KEY_LEN];
+        c.copy_from_slice(&meta[offset..]);
+
+        Ok(Some(Checksum(c)))
     }
 
-    /// Sign manifest using provided key
+    /// Fetch the [EmbedRange] record from the metadata where [Flags::EMBEDDED] is set
+    ///
+    /// Metadata should be checked against the manifest prior to use
+    pub fn embed_range(&self, meta: &[u8]) -> Result<Option<EmbedRange>, VerifyError> {
+        if !self.flags().contains(Flags::EMBEDDED) {
+            return Ok(None);
+        }
+
+        let trailer = match self.flags().contains(Flags::NEXT_KEY) {
+            true => NEXT_KEY_LEN,
+            false => 0,
+        };
+        let offset = meta.len().checked_sub(trailer + EMBED_RANGE_LEN)
+            .ok_or(VerifyError::MetaLengthMismatch)?;
+
+        let b = meta[offset..][..EMBED_RANGE_LEN].try_into().unwrap();
+        Ok(Some(EmbedRange::decode(b)))
+    }
+
+    /// Decode a manifest trailing the provided data (ie. from an attached package),
+    /// returning the manifest and the offset of the manifest within the data
+    /// 
+    /// As manifest lengths vary by [Layout] each supported layout is attempted,
+    /// preferring the first candidate with a valid signature.
+    pub fn decode_trailer(data: &[u8]) -> Result<(Self, usize), ManifestError> {
+        let mut candidate = None;
+
+        for layout in [Layout::Large, Layout::Compact] {
+            // Skip layouts that do not fit or do not match the version field
+            let offset = match data.len().checked_sub(layout.encoded_len()) {
+                Some(v) => v,
+                None => continue,
+            };
+            let b = &data[offset..];
+            if u16::from_le_bytes([b[0], b[1]]) != layout.version() {
+                continue;
+            }
+
+            // Attempt to decode the manifest
+            let m = match Self::decode_owned(b) {
+                Ok((m, _n)) => m,
+                Err(_e) => continue,
+            };
+
+            // Return immediately on a valid signature
+            if m.check_sig().is_ok() {
+                return Ok((m, offset));
+            }
+
+            if candidate.is_none() {
+                candidate = Some((m, offset));
+            }
+        }
+
+        match candidate {
+            Some(v) => Ok(v),
+            None if data.len() < MANIFEST_LEN => Err(ManifestError::Codec(encdec::Error::Length)),
+            None => Err(ManifestError::UnsupportedVersion),
+        }
+    }
+
+    /// Sign manifest using provided key, using the [SigMode] recorded in the manifest flags
     pub fn sign<RNG: RngCore + CryptoRng + Default>(&mut self, signing_key: PrivateKey) -> Result<(), ManifestError> {        
-        // Generate manifest digest for signing
-        let digest = self.digest();
+        // Generate signature and write to manifest
+        self.sig = self.signature::<RNG>(signing_key)?;
+
+        Ok(())
+    }
 
+    /// Generate a signature over the manifest using the [SigMode] recorded in the manifest flags,
+    /// without updating the manifest signature
+    ///
+    /// Signatures may be generated with keys other than the manifest key,
+    /// these are checked with [Manifest::verify_signature]
+    pub fn signature<RNG: RngCore + CryptoRng + Default>(&self, signing_key: PrivateKey) -> Result<Signature, ManifestError> {
         // Load keys
         let public_key = ed25519_dalek::PublicKey::from(signing_key.deref());
         let keys = ed25519_dalek::Keypair{ public: public_key, secret: signing_key.inner() };
 
         // Generate signature
-        let sig = keys.sign_prehashed(digest, None)
-            .map_err(|_e| ManifestError::SigningFailed)?;
-
-        // Write signature to manifest
-        self.sig = Signature::from(sig);
+        let sig = match self.sig_mode()? {
+            SigMode::Legacy => keys.sign_prehashed(self.digest()?, None),
+            SigMode::Ed25519ph => keys.sign_prehashed(self.digest()?, Some(SIG_CONTEXT)),
+            SigMode::Ed25519 => keys.try_sign(&self.signing_message()?),
+        }.map_err(|_e| ManifestError::SigningFailed)?;
 
-        Ok(())
+        Ok(Signature::from(sig))
     }
 
     /// Verify manifest signature against allowed keys
@@ -182,16 +520,48 @@ impl Manifest {
             None => return Err(ManifestError::NoMatchingKey),
         };
 
-        // Generate manifest digest for verification
-        let digest = self.digest();
-
         // Verify signature
-        let sig = ed25519_dalek::Signature::try_from(&self.sig)?;
+        self.verify_sig(signing_key, &self.sig)
+    }
 
-        signing_key.verify_prehashed(digest, None, &sig)
-            .map_err(|e| ManifestError::VerificationFailed)?;
+    /// Verify a signature over the manifest (see [Manifest::signature]) against the provided key
+    pub fn verify_signature(&self, key: &PublicKey, sig: &Signature) -> Result<(), ManifestError> {
+        self.verify_sig(key, sig)
+    }
 
-        Ok(())
+    /// Fetch the message signed in [SigMode::Ed25519], [SIG_CONTEXT] followed by the manifest digest
+    pub fn signing_message(&self) -> Result<[u8; SIG_MESSAGE_LEN], ManifestError> {
+        let mut b = [0u8; SIG_MESSAGE_LEN];
+        b[..SIG_CONTEXT.len()].copy_from_slice(SIG_CONTEXT);
+        b[SIG_CONTEXT.len()..].copy_from_slice(&self.signed_digest()?);
+        Ok(b)
+    }
+
+    /// Fetch the SHA-512 digest over the signed manifest fields (all fields other than the signature)
+    pub fn signed_digest(&self) -> Result<[u8; 64], ManifestError> {
+        let mut b = [0u8; 64];
+        b.copy_from_slice(&self.digest()?.finalize());
+        Ok(b)
+    }
+
+    /// Internal helper to verify the manifest signature against a key using the manifest [SigMode]
+    ///
+    /// Manifests with unknown [Flags] bits are rejected with [ManifestError::UnsupportedFlags].
+    /// Flags such as [Flags::BLOCK_SIZE] and [Flags::META_ALIGN] change how checksums are computed
+    /// and where data is located, so a verifier ignoring a flag it does not understand could accept
+    /// a package under a different interpretation than the signer intended.
+    fn verify_sig(&self, key: &ed25519_dalek::PublicKey, sig: &Signature) -> Result<(), ManifestError> {
+        if Flags::from_bits(self.flags).is_none() {
+            return Err(ManifestError::UnsupportedFlags);
+        }
+
+        let sig = ed25519_dalek::Signature::try_from(sig)?;
+
+        match self.sig_mode()? {
+            SigMode::Legacy => key.verify_prehashed(self.digest()?, None, &sig),
+            SigMode::Ed25519ph => key.verify_prehashed(self.digest()?, Some(SIG_CONTEXT), &sig),
+            SigMode::Ed25519 => key.verify(&self.signing_message()?, &sig),
+        }.map_err(|_e| ManifestError::VerificationFailed)
     }
 
 
@@ -201,7 +571,7 @@ impl Manifest {
         self.check_sig()?;
 
         // Check app length and checksum
-        let app_csum = Checksum::compute(app);
+        let app_csum = self.app_checksum(app);
         self.check_app(app.len(), &app_csum)?;
 
         // Check meta length and checksum
@@ -213,7 +583,8 @@ impl Manifest {
 
     /// Check application and metadata against manifest using pre-computed values
     /// 
-    /// This is useful where the app is not entirely in memory for checksum computations
+    /// This is useful where the app is not entirely in memory for checksum computations,
+    /// where block hashing is enabled `app_csum` must be the block root (see [crate::blocks])
     pub fn check_precomputed(&self, app_csum: &Checksum, app_len: usize, meta_csum: &Checksum, meta_len: usize) -> Result<(), VerifyError> {
         // Ensure signature is valid / object is well formed
         self.check_sig()?;
@@ -225,27 +596,59 @@ impl Manifest {
         self.check_meta(meta_len, meta_csum)?;
         
         // TODO: check signing key against allowed
-        
+
         Ok(())
     }
 
-    /// Internal helper to check the manifest signature is valid
-    fn check_sig(&self) -> Result<(), VerifyError> {
-        // Generate manifest digest for verification
-        let digest = self.digest();
+    /// Check application and metadata against manifest, streaming each to EOF
+    /// so the application need not be held in memory
+    ///
+    /// Verification failures are returned as [std::io::ErrorKind::InvalidData] errors
+    /// wrapping the [VerifyError]
+    #[cfg(feature = "std")]
+    pub fn check_reader(&self, mut app: impl std::io::Read, mut meta: impl std::io::Read) -> Result<(), std::io::Error> {
+        let mut b = vec![0u8; 64 * 1024];
+
+        // Stream app checksum
+        let mut app_hasher = crate::blocks::AppHasher::new(self);
+        loop {
+            match app.read(&mut b)? {
+                0 => break,
+                n => app_hasher.update(&b[..n]),
+            }
+        }
+        let app_len = app_hasher.len() as usize;
+
+        // Stream meta checksum
+        let (mut meta_hasher, mut meta_len) = (sha2::Sha512Trunc256::new(), 0);
+        loop {
+            match meta.read(&mut b)? {
+                0 => break,
+                n => {
+                    meta_hasher.update(&b[..n]);
+                    meta_len += n;
+                },
+            }
+        }
 
-        // Check manifest signature (should _always_ be valid)
-        let sig = ed25519_dalek::Signature::try_from(&self.sig)
-            .map_err(|_| VerifyError::InvalidSignature)?;
-        self.key.deref().verify_prehashed(digest, None, &sig)
-            .map_err(|e| VerifyError::InvalidSignature)?;
+        self.check_precomputed(&app_hasher.finalize(), app_len, &Checksum::from(meta_hasher.finalize()), meta_len)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
 
-        Ok(())
+    /// Internal helper to check the manifest signature is valid
+    pub(crate) fn check_sig(&self) -> Result<(), VerifyError> {
+        // Check manifest signature (should _always_ be valid)
+        match self.verify_sig(&self.key, &self.sig) {
+            Ok(_) => Ok(()),
+            Err(ManifestError::UnsupportedVersion) => Err(VerifyError::UnsupportedVersion),
+            Err(ManifestError::UnsupportedFlags) => Err(VerifyError::UnsupportedFlags),
+            Err(_) => Err(VerifyError::InvalidSignature),
+        }
     }
 
     /// Internal helper to check app app length and checksum match
     fn check_app(&self, app_len: usize, app_csum: &Checksum) -> Result<(), VerifyError> {
-        if app_len != self.app_len() {
+        if app_len as u64 != self.app_len {
             return Err(VerifyError::AppLengthMismatch)
         }
         if app_csum != &self.app_csum {
@@ -255,8 +658,8 @@ impl Manifest {
     }
 
     /// Internal helper to check app app length and checksum match
-    fn check_meta(&self, meta_len: usize, meta_csum: &Checksum) -> Result<(), VerifyError> {
-        if meta_len != self.meta_len() {
+    pub(crate) fn check_meta(&self, meta_len: usize, meta_csum: &Checksum) -> Result<(), VerifyError> {
+        if meta_len as u64 != self.meta_len {
             return Err(VerifyError::MetaLengthMismatch)
         }
         if meta_csum != &self.meta_csum {
@@ -269,27 +672,157 @@ impl Manifest {
     /// 
     /// (this is equivalent to computing the digest over the encoded object,
     /// while avoiding the need to encode prior to signing)
-    fn digest(&self) -> Sha512 {
+    fn digest(&self) -> Result<Sha512, ManifestError> {
         let mut h = Sha512::new();
 
-        h.update(&self.version.to_le_bytes());
-        h.update(&self.flags.to_le_bytes());
+        self.write_fields(|d| {
+            h.update(d);
+            Ok(())
+        })?;
+
+        Ok(h)
+    }
+
+    /// Internal helper to emit signed manifest fields in encoding order,
+    /// shared between encoding and digest computation
+    fn write_fields(&self, mut w: impl FnMut(&[u8]) -> Result<(), ManifestError>) -> Result<(), ManifestError> {
+        let layout = self.layout()?;
+
+        w(&self.version.to_le_bytes())?;
+        w(&self.flags.to_le_bytes())?;
+
+        w(self.app_name.deref())?;
+        w(self.app_version.deref())?;
+
+        match layout {
+            Layout::Compact => {
+                let app_len = u32::try_from(self.app_len)
+                    .map_err(|_| ManifestError::AppTooLarge)?;
+                let meta_len = u16::try_from(self.meta_len)
+                    .map_err(|_| ManifestError::MetaTooLarge)?;
+
+                w(&app_len.to_le_bytes())?;
+                w(self.app_csum.deref())?;
+
+                w(&self.meta_kind.to_le_bytes())?;
+                w(&meta_len.to_le_bytes())?;
+            },
+            Layout::Large => {
+                w(&self.app_len.to_le_bytes())?;
+                w(self.app_csum.deref())?;
+
+                w(&self.meta_kind.to_le_bytes())?;
+                w(&0u16.to_le_bytes())?;
+                w(&self.meta_len.to_le_bytes())?;
+            },
+        }
+
+        w(self.meta_csum.deref())?;
 
-        h.update(&self.app_name.deref());
-        h.update(&self.app_version.deref());
+        w(self.key.as_bytes())?;
 
-        h.update(&self.app_len.to_le_bytes());
-        h.update(&self.app_csum.deref());
+        Ok(())
+    }
+}
+
+impl Encode for Manifest {
+    type Error = ManifestError;
+
+    fn encode_len(&self) -> Result<usize, Self::Error> {
+        Ok(self.layout()?.encoded_len())
+    }
+
+    fn encode(&self, buff: &mut [u8]) -> Result<usize, Self::Error> {
+        // Check buffer length
+        let n = self.encode_len()?;
+        if buff.len() < n {
+            return Err(encdec::Error::Length.into());
+        }
 
-        h.update(&self.meta_kind.to_le_bytes());
-        h.update(&self.meta_len.to_le_bytes());
-        h.update(&self.meta_csum.deref());
+        // Write signed fields
+        let mut index = 0;
+        self.write_fields(|d| {
+            buff[index..][..d.len()].copy_from_slice(d);
+            index += d.len();
+            Ok(())
+        })?;
 
-        h.update(&self.key.deref());
+        // Write signature
+        index += self.sig.encode(&mut buff[index..])?;
 
-        h
+        Ok(index)
     }
+}
+
+impl DecodeOwned for Manifest {
+    type Output = Manifest;
+
+    type Error = ManifestError;
+
+    fn decode_owned(buff: &[u8]) -> Result<(Self::Output, usize), Self::Error> {
+        // Read version and check buffer length for the matching layout
+        if buff.len() < 2 {
+            return Err(encdec::Error::Length.into());
+        }
+        let version = u16::from_le_bytes([buff[0], buff[1]]);
+        let layout = Layout::from_version(version)?;
+        if buff.len() < layout.encoded_len() {
+            return Err(encdec::Error::Length.into());
+        }
 
+        let mut index = 2;
+        let mut take = |n: usize| {
+            let d = &buff[index..][..n];
+            index += n;
+            d
+        };
+
+        let flags = u16::from_le_bytes(take(2).try_into().unwrap());
+
+        let (app_name, _) = Stringish::<16>::decode(take(16))?;
+        let (app_version, _) = Stringish::<24>::decode(take(24))?;
+
+        let (app_len, app_csum, meta_kind, meta_len) = match layout {
+            Layout::Compact => {
+                let app_len = u32::from_le_bytes(take(4).try_into().unwrap()) as u64;
+                let (app_csum, _) = Checksum::decode(take(32))?;
+                let meta_kind = u16::from_le_bytes(take(2).try_into().unwrap());
+                let meta_len = u16::from_le_bytes(take(2).try_into().unwrap()) as u64;
+                (app_len, app_csum, meta_kind, meta_len)
+            },
+            Layout::Large => {
+                let app_len = u64::from_le_bytes(take(8).try_into().unwrap());
+                let (app_csum, _) = Checksum::decode(take(32))?;
+                let meta_kind = u16::from_le_bytes(take(2).try_into().unwrap());
+                // Reserved bytes are signed as zero, reject other values so encodings are not malleable
+                if take(2) != [0u8; 2] {
+                    return Err(ManifestError::InvalidReserved);
+                }
+                let meta_len = u64::from_le_bytes(take(8).try_into().unwrap());
+                (app_len, app_csum, meta_kind, meta_len)
+            },
+        };
+
+        let (meta_csum, _) = Checksum::decode(take(32))?;
+        let (key, _) = PublicKey::decode_owned(take(ed25519_dalek::PUBLIC_KEY_LENGTH))?;
+        let (sig, _) = Signature::decode(take(ed25519_dalek::SIGNATURE_LENGTH))?;
+
+        let m = Manifest {
+            version,
+            flags,
+            app_name,
+            app_version,
+            app_len,
+            app_csum,
+            meta_kind,
+            meta_len,
+            meta_csum,
+            key,
+            sig,
+        };
+
+        Ok((m, index))
+    }
 }
 
 #[cfg(test)]
@@ -343,6 +876,54 @@ mod tests {
         m1.verify(&[public_key]).expect("Verification failed");
     }
 
+    #[test]
+    fn sig_modes() {
+        let private_key = PrivateKey::generate(&mut OsRng{});
+        let public_key = PublicKey::from(&private_key);
+        let keys = [public_key.clone()];
+
+        let m = Manifest {
+            version: MANIFEST_VERSION,
+            flags: 0,
+            app_name: "test_app".into(),
+            app_version: "1.2.7".into(),
+            app_len: 64 * 1024,
+            app_csum: Checksum::compute(&[0xab; 32]),
+            meta_len: 1024,
+            meta_csum: Checksum::compute(&[0xbc; 32]),
+            meta_kind: MetadataFormat::Binary as u16,
+            key: public_key.clone(),
+            sig: Signature::empty(),
+        };
+
+        // Legacy manifests are signed without a context
+        let mut legacy = m.clone();
+        legacy.sign::<OsRng>(private_key.clone()).unwrap();
+        assert_eq!(legacy.sig_mode(), Ok(SigMode::Legacy));
+        let digest = legacy.digest().unwrap();
+        let sig = ed25519_dalek::Signature::try_from(&legacy.sig).unwrap();
+        public_key.verify_prehashed(digest, None, &sig).unwrap();
+        legacy.verify(&keys).unwrap();
+
+        for mode in [SigMode::Ed25519ph, SigMode::Ed25519] {
+            let mut m1 = m.clone();
+            m1.flags = (mode as u16) << SIG_MODE_SHIFT;
+            m1.sign::<OsRng>(private_key.clone()).unwrap();
+            assert_eq!(m1.sig_mode(), Ok(mode));
+            m1.verify(&keys).unwrap();
+
+            // Signatures do not verify under other modes
+            let mut m2 = m1.clone();
+            m2.flags = 0;
+            assert_eq!(m2.verify(&keys), Err(ManifestError::VerificationFailed));
+        }
+
+        // Unknown modes are rejected
+        let mut m1 = m.clone();
+        m1.flags = Flags::SIG_MODE.bits();
+        assert_eq!(m1.sign::<OsRng>(private_key), Err(ManifestError::UnsupportedSigMode));
+    }
+
     #[test]
     fn digests_match() {
         // Setup keys
@@ -365,11 +946,11 @@ mod tests {
         };
 
         // Compute piecewise (pre-encode) digest
-        let d = m.digest().finalize();
+        let d = m.digest().unwrap().finalize();
 
         // Encode manifest object
         let mut b = [0u8; 256];
-        let n = m.encode(&mut b).unwrap();
+        let _n = m.encode(&mut b).unwrap();
 
         // Compute complete (post-encode) digest
         let mut h = Sha512::new();
@@ -380,4 +961,115 @@ mod tests {
         assert_eq!(d, d1);
         
     }
+
+    #[test]
+    fn large_layout() {
+        // Setup keys
+        let private_key = PrivateKey::generate(&mut OsRng{});
+        let public_key = PublicKey::from(&private_key);
+
+        // Build manifest with lengths exceeding the compact layout
+        let mut m = Manifest {
+            version: MANIFEST_VERSION_LARGE,
+            flags: 0,
+            app_name: "rootfs".into(),
+            app_version: "2.0.0".into(),
+            app_len: 6 * 1024 * 1024 * 1024,
+            app_csum: Checksum::compute(&[0xab; 32]),
+            meta_len: 128 * 1024,
+            meta_csum: Checksum::compute(&[0xbc; 32]),
+            meta_kind: MetadataFormat::Json as u16,
+            key: public_key.clone(),
+            sig: Signature::empty(),
+        };
+        m.sign::<OsRng>(private_key).expect("Signing failed");
+
+        // Encode and decode manifest object
+        let mut b = [0u8; 256];
+        let n = m.encode(&mut b).unwrap();
+        assert_eq!(n, MANIFEST_LEN_LARGE);
+
+        let (m1, n1) = Manifest::decode(&b[..n]).unwrap();
+        assert_eq!(n, n1);
+        assert_eq!(m, m1);
+
+        m1.verify(&[public_key]).expect("Verification failed");
+
+        // Reserved bytes (following the metadata kind) must be zero
+        let mut b1 = b;
+        b1[86] = 0x01;
+        assert_eq!(Manifest::decode(&b1[..n]), Err(ManifestError::InvalidReserved));
+
+        // Compact layout cannot represent these lengths
+        m.version = MANIFEST_VERSION;
+        assert_eq!(m.encode(&mut b), Err(ManifestError::AppTooLarge));
+    }
+
+    #[test]
+    fn decode_trailer() {
+        let private_key = PrivateKey::generate(&mut OsRng{});
+
+        for layout in [Layout::Compact, Layout::Large] {
+            let mut m = Manifest {
+                version: layout.version(),
+                flags: 0,
+                app_name: "test_app".into(),
+                app_version: "1.2.7".into(),
+                app_len: 16,
+                app_csum: Checksum::compute(&[0xab; 16]),
+                meta_len: 4,
+                meta_csum: Checksum::compute(&[0xbc; 4]),
+                meta_kind: MetadataFormat::Binary as u16,
+                key: PublicKey::from(&private_key),
+                sig: Signature::empty(),
+            };
+            m.sign::<OsRng>(private_key.clone()).unwrap();
+
+            // Build attached package
+            let mut p = vec![0xab; 16];
+            p.extend_from_slice(&[0xbc; 4]);
+            let mut b = [0u8; MANIFEST_MAX_LEN];
+            let n = m.encode(&mut b).unwrap();
+            p.extend_from_slice(&b[..n]);
+
+            let (m1, offset) = Manifest::decode_trailer(&p).unwrap();
+            assert_eq!(m1, m);
+            assert_eq!(offset, 20);
+        }
+    }
+
+    #[test]
+    fn embed_range() {
+        let r = EmbedRange { offset: 0x100, len: 0x200, fill: 0xff };
+        assert_eq!(EmbedRange::decode(&r.encode()), r);
+
+        // Range record precedes any next key commitment
+        let mut m = crate::ManifestBuilder::new()
+            .app_bin(&[]).unwrap()
+            .meta_bin(MetadataFormat::Binary, &[]).unwrap()
+            .build::<OsRng>(None).unwrap();
+        let mut meta = vec![0xcd; 4];
+        meta.extend_from_slice(&r.encode());
+        assert_eq!(m.embed_range(&meta), Ok(None));
+
+        m.flags |= Flags::EMBEDDED.bits();
+        assert_eq!(m.embed_range(&meta), Ok(Some(r)));
+        assert_eq!(m.embed_range(&meta[5..]), Err(VerifyError::MetaLengthMismatch));
+
+        m.flags |= Flags::NEXT_KEY.bits();
+        meta.extend_from_slice(&[0u8; NEXT_KEY_LEN]);
+        assert_eq!(m.embed_range(&meta), Ok(Some(r)));
+
+        // Applied to application data in chunks, including ranges past the data
+        let mut d = [0u8; 0x400];
+        for (i, c) in d.chunks_mut(0x180).enumerate() {
+            r.apply(i as u64 * 0x180, c);
+        }
+        assert!(d[..0x100].iter().chain(&d[0x300..]).all(|v| *v == 0));
+        assert!(d[0x100..0x300].iter().all(|v| *v == 0xff));
+
+        let mut d = [0u8; 0x180];
+        r.apply(0, &mut d);
+        assert!(d[0x100..].iter().all(|v| *v == 0xff));
+    }
 }
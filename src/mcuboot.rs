@@ -0,0 +1,545 @@
+//! [MCUboot](https://docs.mcuboot.com/design.html) image format interoperability
+//!
+//! Supports converting fwsig packages to MCUboot images (header, image, and a TLV trailer
+//! with `SHA256`, `KEYHASH` and `ED25519` entries) using the same ed25519 key material,
+//! and verifying MCUboot images as `imgtool verify` does (with either `KEYHASH` or `PUBKEY` entries).
+//!
+//! The encoded fwsig [Manifest] and metadata may additionally be carried in custom TLVs
+//! ([TLV_FWSIG_MANIFEST], [TLV_FWSIG_META]), allowing images to be converted back into
+//! the original fwsig package without re-signing.
+
+use core::{fmt::Display, ops::Deref, str::FromStr};
+
+use ed25519_dalek::{Signer, Verifier};
+use encdec::{Encode, DecodeOwned};
+use sha2::{Digest, Sha256};
+
+use rand_core::{CryptoRng, RngCore};
+
+use crate::{
+    types::{PrivateKey, PublicKey},
+    Manifest, ManifestBuilder, McubootError, MetadataFormat, Package, PackageOpts, MANIFEST_MAX_LEN,
+};
+
+/// MCUboot image header magic
+pub const IMAGE_MAGIC: u32 = 0x96f3_b83d;
+
+/// MCUboot image header length
+pub const IMAGE_HEADER_LEN: usize = 32;
+
+/// Unprotected TLV area magic
+pub const TLV_INFO_MAGIC: u16 = 0x6907;
+/// Protected TLV area magic
+pub const TLV_PROT_INFO_MAGIC: u16 = 0x6908;
+
+/// SHA256 of the public key used to sign the image
+pub const TLV_KEYHASH: u16 = 0x01;
+/// Public key used to sign the image (DER SubjectPublicKeyInfo), from `imgtool --public-key-format full`
+pub const TLV_PUBKEY: u16 = 0x02;
+/// SHA256 of the image header, body and protected TLVs
+pub const TLV_SHA256: u16 = 0x10;
+/// ED25519 signature over the image SHA256
+pub const TLV_ED25519: u16 = 0x24;
+
+/// Custom TLV containing the encoded fwsig [Manifest]
+pub const TLV_FWSIG_MANIFEST: u16 = 0xa0;
+/// Custom TLV containing fwsig metadata
+pub const TLV_FWSIG_META: u16 = 0xa1;
+
+/// DER SubjectPublicKeyInfo prefix for ed25519 keys, as hashed by imgtool for `KEYHASH`
+const ED25519_SPKI_PREFIX: [u8; 12] = [0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00];
+
+/// MCUboot image version (`major.minor.revision+build`)
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct ImageVersion {
+    pub major: u8,
+    pub minor: u8,
+    pub revision: u16,
+    pub build: u32,
+}
+
+impl FromStr for ImageVersion {
+    type Err = McubootError;
+
+    /// Parse version strings in `imgtool` format (eg. `1.2.3+4`), omitted components are zero
+    ///
+    /// Additional dot-separated components (eg. `1.2.3.4`) are rejected, the build number is only set with `+`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (v, build) = match s.split_once('+') {
+            Some((v, b)) => (v, b.parse().map_err(|_| McubootError::InvalidVersion)?),
+            None => (s, 0),
+        };
+
+        let mut p = v.split('.');
+        let mut next = || p.next().unwrap_or("0");
+
+        let major = next().parse().map_err(|_| McubootError::InvalidVersion)?;
+        let minor = next().parse().map_err(|_| McubootError::InvalidVersion)?;
+        let revision = next().parse().map_err(|_| McubootError::InvalidVersion)?;
+
+        if p.next().is_some() {
+            return Err(McubootError::InvalidVersion);
+        }
+
+        Ok(Self { major, minor, revision, build })
+    }
+}
+
+impl Display for ImageVersion {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}.{}.{}+{}", self.major, self.minor, self.revision, self.build)
+    }
+}
+
+/// Options for encoding MCUboot images
+#[derive(Clone, Debug, PartialEq)]
+pub struct McubootOpts {
+    /// Image load address (`ih_load_addr`)
+    pub load_addr: u32,
+    /// Header size, padding the image body (eg. to a vector table alignment)
+    pub header_size: u16,
+    /// Image version
+    pub version: ImageVersion,
+}
+
+impl Default for McubootOpts {
+    fn default() -> Self {
+        Self { load_addr: 0, header_size: IMAGE_HEADER_LEN as u16, version: ImageVersion::default() }
+    }
+}
+
+/// Parsed MCUboot image
+#[derive(Clone, Debug, PartialEq)]
+pub struct McubootImage<'a> {
+    /// Image load address
+    pub load_addr: u32,
+    /// Image header flags
+    pub flags: u32,
+    /// Image version
+    pub version: ImageVersion,
+    /// Header and image body, covered by the image hash
+    pub signed: &'a [u8],
+    /// Image body
+    pub body: &'a [u8],
+    /// Protected TLV area (including info header), covered by the image hash
+    pub protected: &'a [u8],
+    /// Protected TLV entries
+    pub protected_tlvs: Vec<(u16, &'a [u8])>,
+    /// Unprotected TLV entries
+    pub tlvs: Vec<(u16, &'a [u8])>,
+}
+
+/// Encode an MCUboot image signed with the provided key
+///
+/// If `fwsig` is provided, the encoded manifest and metadata are included as custom TLVs
+pub fn encode(app: &[u8], key: &PrivateKey, opts: &McubootOpts, fwsig: Option<(&Manifest, &[u8])>) -> Result<Vec<u8>, McubootError> {
+    if (opts.header_size as usize) < IMAGE_HEADER_LEN {
+        return Err(McubootError::InvalidLength);
+    }
+    let img_size = u32::try_from(app.len())
+        .map_err(|_| McubootError::InvalidLength)?;
+
+    // Write header
+    let mut out = Vec::with_capacity(opts.header_size as usize + app.len());
+    out.extend_from_slice(&IMAGE_MAGIC.to_le_bytes());
+    out.extend_from_slice(&opts.load_addr.to_le_bytes());
+    out.extend_from_slice(&opts.header_size.to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes());
+    out.extend_from_slice(&img_size.to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out.push(opts.version.major);
+    out.push(opts.version.minor);
+    out.extend_from_slice(&opts.version.revision.to_le_bytes());
+    out.extend_from_slice(&opts.version.build.to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+
+    // Pad header and write image body
+    out.resize(opts.header_size as usize, 0);
+    out.extend_from_slice(app);
+
+    // Compute image hash and signature
+    let hash = Sha256::digest(&out);
+
+    let public_key = ed25519_dalek::PublicKey::from(key.deref());
+    let keys = ed25519_dalek::Keypair{ public: public_key, secret: key.clone().inner() };
+    let sig = keys.sign(&hash);
+
+    // Build TLVs
+    let mut tlvs: Vec<(u16, Vec<u8>)> = vec![
+        (TLV_SHA256, hash.to_vec()),
+        (TLV_KEYHASH, key_hash(&PublicKey::from(public_key)).to_vec()),
+        (TLV_ED25519, sig.to_bytes().to_vec()),
+    ];
+
+    if let Some((m, meta)) = fwsig {
+        let mut b = [0u8; MANIFEST_MAX_LEN];
+        let n = m.encode(&mut b)?;
+        tlvs.push((TLV_FWSIG_MANIFEST, b[..n].to_vec()));
+
+        if !meta.is_empty() {
+            tlvs.push((TLV_FWSIG_META, meta.to_vec()));
+        }
+    }
+
+    // Write TLV area
+    let tlv_tot = 4 + tlvs.iter().map(|(_k, v)| 4 + v.len()).sum::<usize>();
+    let tlv_tot = u16::try_from(tlv_tot)
+        .map_err(|_| McubootError::InvalidLength)?;
+
+    out.extend_from_slice(&TLV_INFO_MAGIC.to_le_bytes());
+    out.extend_from_slice(&tlv_tot.to_le_bytes());
+    for (k, v) in tlvs {
+        out.extend_from_slice(&k.to_le_bytes());
+        out.extend_from_slice(&(v.len() as u16).to_le_bytes());
+        out.extend_from_slice(&v);
+    }
+
+    Ok(out)
+}
+
+/// Compute the `KEYHASH` TLV value for an ed25519 public key
+pub fn key_hash(key: &PublicKey) -> [u8; 32] {
+    let mut h = Sha256::new();
+    h.update(ED25519_SPKI_PREFIX);
+    h.update(key.as_bytes());
+
+    let mut b = [0u8; 32];
+    b.copy_from_slice(&h.finalize());
+    b
+}
+
+impl<'a> McubootImage<'a> {
+    /// Parse an MCUboot image, ignoring any trailing (slot / swap) data
+    pub fn parse(data: &'a [u8]) -> Result<Self, McubootError> {
+        if data.len() < IMAGE_HEADER_LEN {
+            return Err(McubootError::InvalidLength);
+        }
+        if read_u32(data, 0) != IMAGE_MAGIC {
+            return Err(McubootError::InvalidMagic);
+        }
+
+        let load_addr = read_u32(data, 4);
+        let hdr_size = read_u16(data, 8) as usize;
+        let protect_tlv_size = read_u16(data, 10) as usize;
+        let img_size = read_u32(data, 12) as usize;
+        let flags = read_u32(data, 16);
+        let version = ImageVersion {
+            major: data[20],
+            minor: data[21],
+            revision: read_u16(data, 22),
+            build: read_u32(data, 24),
+        };
+
+        let body_end = hdr_size.checked_add(img_size)
+            .filter(|n| *n <= data.len() && hdr_size >= IMAGE_HEADER_LEN)
+            .ok_or(McubootError::InvalidLength)?;
+        let signed = &data[..body_end];
+        let body = &data[hdr_size..body_end];
+
+        // Parse protected TLVs, which must exactly fill the area declared in the header
+        let protected = data.get(body_end..body_end + protect_tlv_size)
+            .ok_or(McubootError::InvalidLength)?;
+        let protected_tlvs = match protect_tlv_size {
+            0 => Vec::new(),
+            _ => {
+                let (tlvs, n) = parse_tlvs(protected, TLV_PROT_INFO_MAGIC)?;
+                if n != protect_tlv_size {
+                    return Err(McubootError::InvalidTlv);
+                }
+                tlvs
+            },
+        };
+
+        // Parse unprotected TLVs
+        let (tlvs, _n) = parse_tlvs(&data[body_end + protect_tlv_size..], TLV_INFO_MAGIC)?;
+
+        Ok(Self { load_addr, flags, version, signed, body, protected, protected_tlvs, tlvs })
+    }
+
+    /// Fetch the first TLV of the specified kind
+    pub fn tlv(&self, kind: u16) -> Option<&'a [u8]> {
+        self.tlvs.iter().find(|(k, _v)| *k == kind).map(|(_k, v)| *v)
+    }
+
+    /// Verify the image hash and signature against the provided key,
+    /// following `imgtool verify` (hash, then key hash, then signature)
+    pub fn verify(&self, key: &PublicKey) -> Result<(), McubootError> {
+        // Check image hash
+        let mut h = Sha256::new();
+        h.update(self.signed);
+        h.update(self.protected);
+        let hash = h.finalize();
+
+        match self.tlv(TLV_SHA256) {
+            Some(v) if v == &hash[..] => (),
+            Some(_) => return Err(McubootError::HashMismatch),
+            None => return Err(McubootError::MissingTlv),
+        }
+
+        // Check key hash (or full public key) matches provided key
+        match (self.tlv(TLV_KEYHASH), self.tlv(TLV_PUBKEY)) {
+            (Some(v), _) if v == key_hash(key) => (),
+            (None, Some(v)) if v.strip_prefix(&ED25519_SPKI_PREFIX[..]) == Some(key.as_bytes()) => (),
+            (Some(_), _) | (None, Some(_)) => return Err(McubootError::KeyMismatch),
+            (None, None) => return Err(McubootError::MissingTlv),
+        }
+
+        // Check signature over image hash
+        let sig = self.tlv(TLV_ED25519)
+            .ok_or(McubootError::MissingTlv)?;
+        let sig = ed25519_dalek::Signature::from_bytes(sig)
+            .map_err(|_| McubootError::InvalidSignature)?;
+
+        key.verify(&hash, &sig)
+            .map_err(|_| McubootError::InvalidSignature)
+    }
+
+    /// Recover the embedded fwsig [Manifest] and metadata, if present
+    pub fn fwsig(&self) -> Result<Option<(Manifest, &'a [u8])>, McubootError> {
+        let b = match self.tlv(TLV_FWSIG_MANIFEST) {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+
+        let (m, _n) = Manifest::decode_owned(b)?;
+        let meta = self.tlv(TLV_FWSIG_META).unwrap_or(&[]);
+
+        Ok(Some((m, meta)))
+    }
+}
+
+/// Convert an fwsig [Package] to an MCUboot image signed with the provided key,
+/// carrying the fwsig manifest and metadata in custom TLVs
+pub fn from_package(p: &Package, key: &PrivateKey, opts: &McubootOpts) -> Result<Vec<u8>, McubootError> {
+    encode(p.app, key, opts, Some((&p.manifest, p.meta)))
+}
+
+impl McubootImage<'_> {
+    /// Convert an MCUboot image back to an attached fwsig package
+    ///
+    /// Where the image carries an fwsig manifest this is reused (and checked against the image body),
+    /// otherwise a new manifest is built from the image body and version and signed with `key`.
+    pub fn to_package<RNG: CryptoRng + RngCore + Default>(&self, key: Option<PrivateKey>, opts: &PackageOpts) -> Result<Vec<u8>, McubootError> {
+        let (m, meta) = match self.fwsig()? {
+            Some((m, meta)) => {
+                m.check(self.body, meta).map_err(|_| McubootError::InvalidManifest)?;
+                (m, meta)
+            },
+            None => {
+                let m = ManifestBuilder::new()
+                    .version(&self.version.to_string())?
+                    .app_bin(self.body)?
+                    .meta_bin(MetadataFormat::Binary, &[])?
+                    .build::<RNG>(key)?;
+                (m, &[][..])
+            },
+        };
+
+        Ok(Package::encode(self.body, meta, &m, opts)?)
+    }
+}
+
+/// TLV entries (kind and value)
+type Tlvs<'a> = Vec<(u16, &'a [u8])>;
+
+/// Parse a TLV area (info header and entries) with the provided magic, returning the entries and area length
+fn parse_tlvs(data: &[u8], magic: u16) -> Result<(Tlvs<'_>, usize), McubootError> {
+    if data.len() < 4 || read_u16(data, 0) != magic {
+        return Err(McubootError::InvalidTlv);
+    }
+    let tlv_tot = read_u16(data, 2) as usize;
+    let t = data.get(4..tlv_tot).ok_or(McubootError::InvalidTlv)?;
+
+    let mut tlvs = Vec::new();
+    let mut index = 0;
+    while index < t.len() {
+        let h = t.get(index..index + 4).ok_or(McubootError::InvalidTlv)?;
+        let (k, n) = (read_u16(h, 0), read_u16(h, 2) as usize);
+        let v = t.get(index + 4..index + 4 + n).ok_or(McubootError::InvalidTlv)?;
+        tlvs.push((k, v));
+        index += 4 + n;
+    }
+
+    Ok((tlvs, tlv_tot))
+}
+
+fn read_u16(b: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(b[offset..][..2].try_into().unwrap())
+}
+
+fn read_u32(b: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(b[offset..][..4].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::OsRng;
+
+    use super::*;
+
+    #[test]
+    fn version() {
+        let v = ImageVersion::from_str("1.2.3+4").unwrap();
+        assert_eq!(v, ImageVersion{ major: 1, minor: 2, revision: 3, build: 4 });
+        assert_eq!(v.to_string(), "1.2.3+4");
+
+        assert_eq!(ImageVersion::from_str("2.1").unwrap(), ImageVersion{ major: 2, minor: 1, revision: 0, build: 0 });
+        assert_eq!(ImageVersion::from_str("v1.2"), Err(McubootError::InvalidVersion));
+        assert_eq!(ImageVersion::from_str("1.2.3.4"), Err(McubootError::InvalidVersion));
+        assert_eq!(ImageVersion::from_str("1.2.3+4+5"), Err(McubootError::InvalidVersion));
+    }
+
+    #[test]
+    fn encode_verify() {
+        let key = PrivateKey::generate(&mut OsRng{});
+        let public_key = PublicKey::from(&key);
+        let app = [0xab; 1000];
+
+        let opts = McubootOpts{ load_addr: 0x0800_0000, header_size: 0x200, version: ImageVersion::from_str("1.2.3").unwrap() };
+        let e = encode(&app, &key, &opts, None).unwrap();
+        assert_eq!(read_u32(&e, 0), IMAGE_MAGIC);
+        assert_eq!(&e[0x200..][..1000], &app);
+
+        let i = McubootImage::parse(&e).unwrap();
+        assert_eq!(i.load_addr, 0x0800_0000);
+        assert_eq!(i.version, opts.version);
+        assert_eq!(i.body, &app);
+        i.verify(&public_key).unwrap();
+
+        // Verification fails for other keys
+        let other = PublicKey::from(&PrivateKey::generate(&mut OsRng{}));
+        assert_eq!(i.verify(&other), Err(McubootError::KeyMismatch));
+    }
+
+    #[test]
+    fn verify_tampered() {
+        let key = PrivateKey::generate(&mut OsRng{});
+        let public_key = PublicKey::from(&key);
+
+        let mut e = encode(&[0xab; 100], &key, &McubootOpts::default(), None).unwrap();
+
+        // Modified body fails the hash check
+        let mut e1 = e.clone();
+        e1[IMAGE_HEADER_LEN + 10] ^= 0x01;
+        assert_eq!(McubootImage::parse(&e1).unwrap().verify(&public_key), Err(McubootError::HashMismatch));
+
+        // Modified signature fails signature verification
+        let n = e.len();
+        e[n - 1] ^= 0x01;
+        assert_eq!(McubootImage::parse(&e).unwrap().verify(&public_key), Err(McubootError::InvalidSignature));
+    }
+
+    /// Load the fixture signing key (PKCS#8 ed25519, as used by imgtool)
+    fn fixture_key() -> PrivateKey {
+        const PKCS8_ED25519_PREFIX: [u8; 16] = [0x30, 0x2e, 0x02, 0x01, 0x00, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x04, 0x22, 0x04, 0x20];
+
+        let p = pem::parse(include_str!("../tests/fixtures/mcuboot/key.pem")).unwrap();
+        let seed = p.contents().strip_prefix(&PKCS8_ED25519_PREFIX[..]).unwrap();
+        PrivateKey::from(ed25519_dalek::SecretKey::from_bytes(seed).unwrap())
+    }
+
+    #[test]
+    fn verify_imgtool() {
+        // Images in `imgtool sign` layout signed with key.pem, see tests/fixtures/mcuboot/regen.sh
+        let private_key = fixture_key();
+        let key = PublicKey::from(&private_key);
+        let other = PublicKey::from(&PrivateKey::generate(&mut OsRng{}));
+        let app = include_bytes!("../tests/fixtures/mcuboot/app.bin");
+
+        for (e, kind) in [
+            (&include_bytes!("../tests/fixtures/mcuboot/ed25519-keyhash.bin")[..], TLV_KEYHASH),
+            (&include_bytes!("../tests/fixtures/mcuboot/ed25519-pubkey.bin")[..], TLV_PUBKEY),
+        ] {
+            let i = McubootImage::parse(e).unwrap();
+            assert!(i.tlv(kind).is_some());
+            assert_eq!(i.version, ImageVersion::from_str("1.2.3+4").unwrap());
+            assert_eq!(i.body, &app[..]);
+
+            i.verify(&key).unwrap();
+            assert_eq!(i.verify(&other), Err(McubootError::KeyMismatch));
+        }
+
+        // Re-encoding with the same key reproduces the imgtool image
+        let opts = McubootOpts{ header_size: 0x200, version: ImageVersion::from_str("1.2.3+4").unwrap(), ..Default::default() };
+        assert_eq!(encode(app, &private_key, &opts, None).unwrap(), include_bytes!("../tests/fixtures/mcuboot/ed25519-keyhash.bin"));
+    }
+
+    #[test]
+    fn parse_protected_tlvs() {
+        let key = PrivateKey::generate(&mut OsRng{});
+        let e = encode(&[0xab; 100], &key, &McubootOpts::default(), None).unwrap();
+        let body_end = IMAGE_HEADER_LEN + 100;
+
+        // Build an image with the provided protected TLV area
+        let with_protected = |p: &[u8]| {
+            let mut e1 = e.clone();
+            e1[10..12].copy_from_slice(&(p.len() as u16).to_le_bytes());
+            e1.splice(body_end..body_end, p.iter().cloned());
+            e1
+        };
+
+        let p = [0x08, 0x69, 0x0c, 0x00, 0x50, 0x00, 0x04, 0x00, 0x01, 0x02, 0x03, 0x04];
+        let e1 = with_protected(&p);
+        let i = McubootImage::parse(&e1).unwrap();
+        assert_eq!(i.protected, &p);
+        assert_eq!(i.protected_tlvs, vec![(0x50, &p[8..])]);
+        assert!(i.tlv(TLV_SHA256).is_some());
+
+        // Truncated protected areas are rejected
+        for n in 1..4 {
+            let mut e1 = e.clone();
+            e1[10..12].copy_from_slice(&(n as u16).to_le_bytes());
+            assert_eq!(McubootImage::parse(&e1), Err(McubootError::InvalidTlv));
+        }
+
+        // Protected TLV lengths must match the header and contain whole entries
+        let mut p1 = p;
+        p1[2] = 0x08;
+        assert_eq!(McubootImage::parse(&with_protected(&p1)), Err(McubootError::InvalidTlv));
+
+        let mut p1 = p;
+        p1[6] = 0x08;
+        assert_eq!(McubootImage::parse(&with_protected(&p1)), Err(McubootError::InvalidTlv));
+    }
+
+    #[test]
+    fn fwsig_round_trip() {
+        let key = PrivateKey::generate(&mut OsRng{});
+        let (app, meta) = ([0xab; 100], [0xcd; 10]);
+
+        let m = ManifestBuilder::new()
+            .app_bin(&app).unwrap()
+            .meta_bin(MetadataFormat::Binary, &meta).unwrap()
+            .build::<OsRng>(Some(key.clone())).unwrap();
+
+        let e = encode(&app, &key, &McubootOpts::default(), Some((&m, &meta))).unwrap();
+
+        let i = McubootImage::parse(&e).unwrap();
+        i.verify(&PublicKey::from(&key)).unwrap();
+
+        let (m1, meta1) = i.fwsig().unwrap().unwrap();
+        assert_eq!(m1, m);
+        m1.check(i.body, meta1).unwrap();
+
+        // Converting back reproduces the original package
+        let p = Package::encode(&app, &meta, &m, &PackageOpts::default()).unwrap();
+        assert_eq!(i.to_package::<OsRng>(None, &PackageOpts::default()).unwrap(), p);
+    }
+
+    #[test]
+    fn mcuboot_to_package() {
+        let key = PrivateKey::generate(&mut OsRng{});
+        let opts = McubootOpts{ version: ImageVersion::from_str("1.2.3+4").unwrap(), ..Default::default() };
+
+        // Images without an fwsig manifest are re-signed
+        let e = encode(&[0xab; 100], &key, &opts, None).unwrap();
+        let p = McubootImage::parse(&e).unwrap()
+            .to_package::<OsRng>(Some(key.clone()), &PackageOpts::default()).unwrap();
+
+        let p = Package::parse(&p).unwrap();
+        p.check().unwrap();
+        p.manifest.verify(&[PublicKey::from(&key)]).unwrap();
+        assert_eq!(p.app, &[0xab; 100]);
+        assert_eq!(p.manifest.app_version(), "1.2.3+4");
+    }
+}
@@ -0,0 +1,510 @@
+//! Attached [Package] objects, combining application, metadata and [Manifest]
+//!
+//! Packages are laid out as `APP ‖ PAD ‖ META ‖ PAD ‖ MANIFEST`, where metadata
+//! starts at [Manifest::checked_meta_offset] and the manifest is always located at the
+//! end of the package (or slot, when padded to a fixed slot size).
+//!
+//! Where [Flags::HEADER] is set the manifest is instead placed first, as
+//! `MANIFEST ‖ APP ‖ PAD ‖ META`, so the header can be read before streaming the rest
+//! of the package. Metadata offsets are relative to the start of the application.
+//!
+//! Padding is not covered by the manifest signature. Data following the metadata must
+//! be empty, uniform padding (a single repeated fill byte), or start with a known unsigned
+//! record (a block table or inclusion proof, see [crate::blocks] and [crate::tlog]), so
+//! packages with incorrect lengths are rejected.
+
+use encdec::DecodeOwned;
+#[cfg(feature = "std")]
+use encdec::Encode;
+
+use crate::{Flags, Manifest, ManifestError};
+
+/// Attached package, parsed from a buffer containing application, metadata and manifest
+#[derive(Clone, Debug, PartialEq)]
+pub struct Package<'a> {
+    /// Package manifest
+    pub manifest: Manifest,
+    /// Application binary
+    pub app: &'a [u8],
+    /// Metadata binary
+    pub meta: &'a [u8],
+    /// Unsigned data following the metadata (padding, or an inclusion proof
+    /// see [crate::tlog])
+    pub extra: &'a [u8],
+}
+
+/// Options for encoding attached packages
+#[derive(Clone, Debug, PartialEq)]
+pub struct PackageOpts {
+    /// Byte used for padding (typically the erased flash value)
+    pub fill: u8,
+    /// Slot size, if set the package is padded so the manifest ends the slot
+    pub slot_size: Option<usize>,
+}
+
+impl Default for PackageOpts {
+    fn default() -> Self {
+        Self { fill: 0xFF, slot_size: None }
+    }
+}
+
+impl<'a> Package<'a> {
+    /// Parse an attached package (or slot image), detecting the manifest placement
+    /// and locating the app and metadata using manifest lengths and alignment
+    ///
+    /// Header layouts are detected by a leading manifest with [Flags::HEADER] set and a valid
+    /// signature, otherwise the manifest is read from the end of the data.
+    ///
+    /// Note this does not check the package checksums, see [Package::check]
+    pub fn parse(data: &'a [u8]) -> Result<Self, ManifestError> {
+        // Attempt to decode a leading (header) manifest
+        let header = match Manifest::decode_owned(data) {
+            Ok((m, n)) if m.flags().contains(Flags::HEADER) => Some((m, n)),
+            _ => None,
+        };
+
+        match header {
+            Some((m, n)) if m.check_sig().is_ok() => Self::parse_header(data, m, n),
+            Some((m, n)) => Self::parse_trailer(data).or_else(|_e| Self::parse_header(data, m, n)),
+            None => Self::parse_trailer(data),
+        }
+    }
+
+    /// Parse a package with a trailing manifest
+    fn parse_trailer(data: &'a [u8]) -> Result<Self, ManifestError> {
+        let (manifest, offset) = Manifest::decode_trailer(data)?;
+
+        let (app, meta, extra) = Self::locate(&data[..offset], &manifest)?;
+
+        Ok(Self { manifest, app, meta, extra })
+    }
+
+    /// Parse a package with a leading (header) manifest of length `n`
+    fn parse_header(data: &'a [u8], manifest: Manifest, n: usize) -> Result<Self, ManifestError> {
+        let (app, meta, extra) = Self::locate(&data[n..], &manifest)?;
+
+        Ok(Self { manifest, app, meta, extra })
+    }
+
+    /// Locate app, metadata, and following data within the (non-manifest) package body
+    #[allow(clippy::type_complexity)]
+    fn locate(body: &'a [u8], manifest: &Manifest) -> Result<(&'a [u8], &'a [u8], &'a [u8]), ManifestError> {
+        let meta_offset = manifest.checked_meta_offset()?;
+        let meta_end = usize::try_from(manifest.meta_len).ok()
+            .and_then(|n| meta_offset.checked_add(n))
+            .ok_or(ManifestError::PackageLength)?;
+        if meta_end > body.len() {
+            return Err(ManifestError::PackageLength);
+        }
+
+        let extra = &body[meta_end..];
+        check_extra(extra)?;
+
+        // App length is bounded by the metadata offset
+        Ok((&body[..manifest.app_len as usize], &body[meta_offset..meta_end], extra))
+    }
+
+    /// Check package application and metadata against the manifest
+    pub fn check(&self) -> Result<(), crate::VerifyError> {
+        self.manifest.check(self.app, self.meta)
+    }
+
+    /// Open an attached package file, reading only the manifest so the app and
+    /// metadata may be streamed (see [PackageFile])
+    #[cfg(feature = "std")]
+    pub fn open(path: impl AsRef<std::path::Path>) -> Result<PackageFile, std::io::Error> {
+        PackageFile::open(path)
+    }
+
+    /// Encode an attached package, padding metadata to [Manifest::meta_align]
+    /// and the package to the slot size where configured
+    ///
+    /// The manifest is placed at the end of the package (or slot), or at the
+    /// start where [Flags::HEADER] is set
+    #[cfg(feature = "std")]
+    pub fn encode(app: &[u8], meta: &[u8], manifest: &Manifest, opts: &PackageOpts) -> Result<Vec<u8>, ManifestError> {
+        Self::encode_extra(app, meta, manifest, &[], opts)
+    }
+
+    /// Encode an attached package as in [Package::encode], placing unsigned `extra`
+    /// records (eg. an inclusion proof, see [crate::tlog]) immediately after the metadata
+    #[cfg(feature = "std")]
+    pub fn encode_extra(app: &[u8], meta: &[u8], manifest: &Manifest, extra: &[u8], opts: &PackageOpts) -> Result<Vec<u8>, ManifestError> {
+        if app.len() as u64 != manifest.app_len || meta.len() as u64 != manifest.meta_len {
+            return Err(ManifestError::PackageLength);
+        }
+
+        let manifest_len = manifest.encode_len()?;
+        let header = manifest.flags().contains(Flags::HEADER);
+
+        // Write header manifest
+        let mut out = Vec::new();
+        if header {
+            out.resize(manifest_len, 0);
+            manifest.encode(&mut out)?;
+        }
+
+        // Write app and aligned metadata
+        let body = out.len();
+        out.extend_from_slice(app);
+        out.resize(body + manifest.checked_meta_offset()?, opts.fill);
+        out.extend_from_slice(meta);
+        out.extend_from_slice(extra);
+
+        // Pad to the slot size, placing trailing manifests at the end of the slot
+        if let Some(slot_size) = opts.slot_size {
+            let end = match header {
+                true => slot_size,
+                false => slot_size.checked_sub(manifest_len)
+                    .ok_or(ManifestError::PackageTooLarge)?,
+            };
+            if out.len() > end {
+                return Err(ManifestError::PackageTooLarge);
+            }
+            out.resize(end, opts.fill);
+        }
+
+        // Write trailing manifest
+        if !header {
+            let n = out.len();
+            out.resize(n + manifest_len, 0);
+            manifest.encode(&mut out[n..])?;
+        }
+
+        Ok(out)
+    }
+}
+
+/// Check whether data following the metadata starts with a known unsigned record
+fn is_record(extra: &[u8]) -> bool {
+    extra.starts_with(&crate::blocks::BLOCK_TABLE_MAGIC) || extra.starts_with(&crate::tlog::PROOF_MAGIC)
+}
+
+/// Check data following the metadata is empty, uniform padding, or starts with a known record
+fn check_extra(extra: &[u8]) -> Result<(), ManifestError> {
+    let padding = extra.iter().all(|b| Some(b) == extra.first());
+
+    match is_record(extra) || padding {
+        true => Ok(()),
+        false => Err(ManifestError::PackageLength),
+    }
+}
+
+#[cfg(feature = "std")]
+pub use file::PackageFile;
+
+#[cfg(feature = "std")]
+mod file {
+    use std::{
+        fs::File,
+        io::{Error, ErrorKind, Read, Seek, SeekFrom},
+        path::Path,
+    };
+
+    use encdec::DecodeOwned;
+
+    use crate::{Flags, Manifest, ManifestError, MANIFEST_MAX_LEN};
+
+    /// Attached package backed by a file, locating the manifest as in
+    /// [Package::parse](super::Package::parse) and streaming the app and metadata
+    /// so memory use does not depend on the application length
+    #[derive(Debug)]
+    pub struct PackageFile {
+        /// Package manifest
+        pub manifest: Manifest,
+        file: File,
+        body_offset: u64,
+        body_len: u64,
+        meta_offset: u64,
+    }
+
+    impl PackageFile {
+        /// Open an attached package file, detecting the manifest placement
+        ///
+        /// Note this does not check the package checksums, see [PackageFile::check]
+        pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+            let mut file = File::open(path)?;
+            let len = file.metadata()?.len();
+
+            // Read leading and trailing bytes for manifest detection
+            let n = len.min(MANIFEST_MAX_LEN as u64);
+            let mut head = vec![0u8; n as usize];
+            file.read_exact(&mut head)?;
+
+            let mut tail = vec![0u8; n as usize];
+            file.seek(SeekFrom::Start(len - n))?;
+            file.read_exact(&mut tail)?;
+
+            // Prefer a valid header manifest, otherwise the trailing manifest
+            let header = match Manifest::decode_owned(&head) {
+                Ok((m, n)) if m.flags().contains(Flags::HEADER) => Some((m, n)),
+                _ => None,
+            };
+            let trailer = || Manifest::decode_trailer(&tail)
+                .map(|(m, offset)| (m, 0, len - n + offset as u64));
+
+            let (manifest, body_offset, body_end) = match header {
+                Some((m, n)) if m.check_sig().is_ok() => (m, n as u64, len),
+                Some((m, n)) => trailer().unwrap_or((m, n as u64, len)),
+                None => trailer().map_err(invalid)?,
+            };
+
+            let meta_offset = manifest.checked_meta_offset().map_err(invalid)? as u64;
+            let mut p = Self { manifest, file, body_offset, body_len: body_end - body_offset, meta_offset };
+
+            // Check app and metadata are within the package, followed by padding or known records
+            if p.meta_end() > p.body_len {
+                return Err(invalid(ManifestError::PackageLength));
+            }
+
+            p.check_extra()?;
+
+            Ok(p)
+        }
+
+        /// Fetch a reader over the application
+        pub fn app(&mut self) -> Result<impl Read + '_, Error> {
+            let n = self.manifest.app_len;
+            self.reader(0, n)
+        }
+
+        /// Fetch a reader over the metadata
+        pub fn meta(&mut self) -> Result<impl Read + '_, Error> {
+            let n = self.manifest.meta_len;
+            self.reader(self.meta_offset, n)
+        }
+
+        /// Read unsigned data following the metadata (padding, block table or inclusion proof)
+        pub fn extra(&mut self) -> Result<Vec<u8>, Error> {
+            let (offset, n) = (self.meta_end(), self.body_len - self.meta_end());
+
+            let mut b = Vec::with_capacity(n as usize);
+            self.reader(offset, n)?.read_to_end(&mut b)?;
+
+            Ok(b)
+        }
+
+        /// Check package application and metadata against the manifest, streaming the application
+        ///
+        /// Verification failures are returned as [ErrorKind::InvalidData] errors
+        /// wrapping the [VerifyError](crate::VerifyError)
+        pub fn check(&mut self) -> Result<(), Error> {
+            // Metadata is bounded by the manifest layout, read this first so the app may be streamed
+            let mut meta = Vec::new();
+            self.meta()?.read_to_end(&mut meta)?;
+
+            let m = self.manifest.clone();
+            m.check_reader(self.app()?, &meta[..])
+        }
+
+        /// Internal helper to check data following the metadata, streaming any padding
+        /// (see [Package::parse](super::Package::parse))
+        fn check_extra(&mut self) -> Result<(), Error> {
+            let (offset, n) = (self.meta_end(), self.body_len - self.meta_end());
+            let mut r = self.reader(offset, n)?;
+
+            let mut b = [0u8; 4096];
+            let head = &mut b[..n.min(4) as usize];
+            r.read_exact(head)?;
+            if super::is_record(head) {
+                return Ok(());
+            }
+
+            super::check_extra(head).map_err(invalid)?;
+
+            let fill = head.first().copied();
+            loop {
+                let len = r.read(&mut b)?;
+                if len == 0 {
+                    return Ok(());
+                }
+                if b[..len].iter().any(|v| Some(*v) != fill) {
+                    return Err(invalid(ManifestError::PackageLength));
+                }
+            }
+        }
+
+        /// Internal helper to read `n` bytes at `offset` within the package body
+        fn reader(&mut self, offset: u64, n: u64) -> Result<impl Read + '_, Error> {
+            self.file.seek(SeekFrom::Start(self.body_offset + offset))?;
+            Ok((&mut self.file).take(n))
+        }
+
+        /// Internal helper to compute the end of the metadata within the package body
+        fn meta_end(&self) -> u64 {
+            self.meta_offset.saturating_add(self.manifest.meta_len)
+        }
+    }
+
+    /// Helper to map manifest errors to IO errors
+    fn invalid(e: ManifestError) -> Error {
+        Error::new(ErrorKind::InvalidData, e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use rand::rngs::OsRng;
+
+    use crate::{test_utils::{manifest, temp_path, APP, META}, ManifestBuilder, MetadataFormat, MANIFEST_LEN};
+
+    use super::*;
+
+    #[test]
+    fn encode_parse() {
+        let (app, meta) = ([0xab; 100], [0xcd; 10]);
+
+        let m = ManifestBuilder::new()
+            .app_bin(&app).unwrap()
+            .meta_bin(MetadataFormat::Binary, &meta).unwrap()
+            .build::<OsRng>(None).unwrap();
+
+        let p = Package::encode(&app, &meta, &m, &PackageOpts::default()).unwrap();
+        assert_eq!(p.len(), app.len() + meta.len() + MANIFEST_LEN);
+
+        let p1 = Package::parse(&p).unwrap();
+        assert_eq!(p1, Package{ manifest: m, app: &app, meta: &meta, extra: &[] });
+        p1.check().unwrap();
+    }
+
+    #[test]
+    fn encode_parse_slot() {
+        let (app, meta) = ([0xab; 100], [0xcd; 10]);
+
+        let m = ManifestBuilder::new()
+            .meta_align(64).unwrap()
+            .app_bin(&app).unwrap()
+            .meta_bin(MetadataFormat::Binary, &meta).unwrap()
+            .build::<OsRng>(None).unwrap();
+        assert_eq!(m.meta_align(), 64);
+        assert_eq!(m.checked_meta_offset(), Ok(128));
+
+        let opts = PackageOpts{ fill: 0xFF, slot_size: Some(4096) };
+        let p = Package::encode(&app, &meta, &m, &opts).unwrap();
+        assert_eq!(p.len(), 4096);
+        assert_eq!(&p[100..128], &[0xFF; 28]);
+        assert_eq!(&p[128..138], &meta);
+
+        // Slot images verify by reading the manifest from the end of the slot
+        let p1 = Package::parse(&p).unwrap();
+        assert_eq!(p1.app, &app);
+        assert_eq!(p1.meta, &meta);
+        p1.check().unwrap();
+
+        // Packages that do not fit the slot are rejected
+        let opts = PackageOpts{ fill: 0xFF, slot_size: Some(256) };
+        assert_eq!(Package::encode(&app, &meta, &m, &opts), Err(ManifestError::PackageTooLarge));
+
+        // Trailing data other than padding or known records is rejected
+        let mut p2 = p.clone();
+        p2[200] = 0x00;
+        assert_eq!(Package::parse(&p2), Err(ManifestError::PackageLength));
+
+        let p3 = Package::encode_extra(&app, &meta, &m, &crate::tlog::PROOF_MAGIC, &PackageOpts{ fill: 0xFF, slot_size: Some(4096) }).unwrap();
+        assert_eq!(Package::parse(&p3).unwrap().extra[..4], crate::tlog::PROOF_MAGIC);
+    }
+
+    #[test]
+    fn parse_meta_offset_overflow() {
+        let mut m = ManifestBuilder::new()
+            .layout(crate::Layout::Large)
+            .meta_align(4096).unwrap()
+            .app_bin(&APP).unwrap()
+            .meta_bin(MetadataFormat::Binary, &[]).unwrap()
+            .build::<OsRng>(None).unwrap();
+
+        // Application lengths overflowing the aligned metadata offset are rejected
+        m.app_len = u64::MAX - 1;
+        assert_eq!(m.checked_meta_offset(), Err(ManifestError::PackageLength));
+
+        let mut b = [0u8; crate::MANIFEST_MAX_LEN];
+        let n = m.encode(&mut b).unwrap();
+        assert_eq!(Package::parse(&b[..n]), Err(ManifestError::PackageLength));
+    }
+
+    #[test]
+    fn encode_parse_header() {
+        let (app, meta) = ([0xab; 100], [0xcd; 10]);
+
+        let m = ManifestBuilder::new()
+            .flags(Flags::HEADER)
+            .meta_align(32).unwrap()
+            .app_bin(&app).unwrap()
+            .meta_bin(MetadataFormat::Binary, &meta).unwrap()
+            .build::<OsRng>(None).unwrap();
+
+        let p = Package::encode(&app, &meta, &m, &PackageOpts::default()).unwrap();
+        assert_eq!(p.len(), MANIFEST_LEN + 128 + meta.len());
+        assert_eq!(&p[MANIFEST_LEN..][..100], &app);
+
+        let p1 = Package::parse(&p).unwrap();
+        assert_eq!(p1, Package{ manifest: m.clone(), app: &app, meta: &meta, extra: &[] });
+        p1.check().unwrap();
+
+        // Slot padding follows the metadata in header layouts
+        let opts = PackageOpts{ fill: 0xFF, slot_size: Some(1024) };
+        let p = Package::encode(&app, &meta, &m, &opts).unwrap();
+        assert_eq!(p.len(), 1024);
+        Package::parse(&p).unwrap().check().unwrap();
+    }
+
+    #[test]
+    fn parse_truncated() {
+        let m = manifest(None, &META);
+
+        let p = Package::encode(&APP, &META, &m, &PackageOpts::default()).unwrap();
+        assert_eq!(Package::parse(&p[1..]), Err(ManifestError::PackageLength));
+    }
+
+    #[test]
+    fn open_file() {
+        let path = temp_path("package");
+        let app: Vec<u8> = (0..200_000u32).map(|i| (i * 3) as u8).collect();
+        let meta = [0xcd; 10];
+
+        for (flags, block_size) in [(Flags::empty(), None), (Flags::HEADER, None), (Flags::empty(), Some(4096))] {
+            let mut b = ManifestBuilder::new();
+            b.flags(flags).meta_align(32).unwrap();
+            if let Some(n) = block_size {
+                b.block_size(n).unwrap();
+            }
+            let m = b.app_bin(&app).unwrap()
+                .meta_bin(MetadataFormat::Binary, &meta).unwrap()
+                .build::<OsRng>(None).unwrap();
+
+            let opts = PackageOpts{ fill: 0xFF, slot_size: Some(256 * 1024) };
+            let p = Package::encode_extra(&app, &meta, &m, &crate::tlog::PROOF_MAGIC, &opts).unwrap();
+            std::fs::write(&path, &p).unwrap();
+
+            let mut f = Package::open(&path).unwrap();
+            assert_eq!(f.manifest, m);
+            f.check().unwrap();
+
+            let mut d = vec![];
+            f.meta().unwrap().read_to_end(&mut d).unwrap();
+            assert_eq!(d, meta);
+            assert_eq!(&f.extra().unwrap()[..4], &crate::tlog::PROOF_MAGIC);
+
+            // Corrupt app data is reported via the wrapped verification error
+            let mut p1 = p.clone();
+            p1[p.len() / 2] ^= 0x01;
+            std::fs::write(&path, &p1).unwrap();
+
+            let e = Package::open(&path).unwrap().check().unwrap_err();
+            assert_eq!(e.get_ref().and_then(|e| e.downcast_ref()), Some(&crate::VerifyError::AppChecksumMismatch));
+        }
+
+        // Truncated packages are rejected on open
+        let m = ManifestBuilder::new()
+            .app_bin(&app).unwrap()
+            .meta_bin(MetadataFormat::Binary, &meta).unwrap()
+            .build::<OsRng>(None).unwrap();
+        let p = Package::encode(&app, &meta, &m, &PackageOpts::default()).unwrap();
+        std::fs::write(&path, &p[1..]).unwrap();
+        assert_eq!(Package::open(&path).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
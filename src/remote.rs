@@ -0,0 +1,384 @@
+//! Remote signing, so release keys need not be present on build agents
+//!
+//! A [Server] holds the signing key and signs manifests over a small HTTP API,
+//! with bearer token authentication and an allowed application name [Policy].
+//! The [Client] builds the manifest locally (via [ManifestBuilder::build_unsigned])
+//! so only the unsigned manifest, containing the app and metadata checksums, is sent
+//! for signing. The server computes the manifest digest and signs using [Manifest::sign].
+//!
+//! The whole manifest is sent rather than only the digest so the server can enforce
+//! its [Policy] on the application name and check the manifest key and flags before
+//! signing, as a server signing bare digests would sign anything for a valid token.
+//! Clients are created with the expected server key, so builds fail with
+//! [RemoteError::KeyMismatch] rather than trusting whichever key the server reports.
+//!
+//! Endpoints:
+//! - `GET /v1/key` returns the signing public key (hex)
+//! - `POST /v1/sign` with an encoded unsigned manifest returns the signature (binary)
+//!
+//! The server does not terminate TLS, deployments should bind to localhost
+//! and expose the API via a TLS terminating proxy.
+
+use std::{io::Read, net::SocketAddr, str::FromStr, time::Duration};
+
+use encdec::{DecodeOwned, Encode};
+use ed25519_dalek::SIGNATURE_LENGTH;
+use log::{debug, info, warn};
+use rand_core::{CryptoRng, RngCore};
+
+#[cfg(feature = "audit")]
+use std::sync::Mutex;
+
+#[cfg(feature = "audit")]
+use crate::audit::AuditLog;
+use crate::{
+    types::{PrivateKey, PublicKey, Signature},
+    Flags, Manifest, ManifestBuilder, RemoteError, MANIFEST_MAX_LEN,
+};
+
+/// Public key endpoint
+pub const KEY_PATH: &str = "/v1/key";
+
+/// Signing endpoint
+pub const SIGN_PATH: &str = "/v1/sign";
+
+/// Signing server policy
+#[derive(Clone, Debug, PartialEq)]
+pub struct Policy {
+    /// Bearer tokens accepted for signing requests
+    pub tokens: Vec<String>,
+    /// Application names allowed to be signed, `*` allows any application
+    pub allowed_apps: Vec<String>,
+}
+
+impl Policy {
+    /// Check a request `Authorization` header contains an accepted bearer token
+    fn authorize(&self, header: Option<&str>) -> Result<(), RemoteError> {
+        let token = header
+            .and_then(|h| h.strip_prefix("Bearer "))
+            .ok_or(RemoteError::Unauthorized)?;
+
+        match self.tokens.iter().any(|t| constant_eq(t.as_bytes(), token.trim().as_bytes())) {
+            true => Ok(()),
+            false => Err(RemoteError::Unauthorized),
+        }
+    }
+
+    /// Check an application name is allowed
+    fn allow_app(&self, name: &str) -> Result<(), RemoteError> {
+        match self.allowed_apps.iter().any(|a| a == "*" || a == name) {
+            true => Ok(()),
+            false => Err(RemoteError::AppNotAllowed),
+        }
+    }
+}
+
+/// Helper to compare tokens without early exit on mismatch
+fn constant_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+/// Remote signing server
+pub struct Server {
+    key: PrivateKey,
+    public_key: PublicKey,
+    policy: Policy,
+    http: tiny_http::Server,
+    #[cfg(feature = "audit")]
+    audit: Option<Mutex<AuditLog>>,
+}
+
+impl Server {
+    /// Bind a signing server to the provided address
+    pub fn bind(addr: &str, key: PrivateKey, policy: Policy) -> Result<Self, RemoteError> {
+        let http = tiny_http::Server::http(addr)
+            .map_err(|_e| RemoteError::Io)?;
+
+        Ok(Self {
+            public_key: PublicKey::from(&key),
+            key,
+            policy,
+            http,
+            #[cfg(feature = "audit")]
+            audit: None,
+        })
+    }
+
+    /// Record signatures to the provided audit log
+    #[cfg(feature = "audit")]
+    pub fn audit_log(mut self, log: AuditLog) -> Self {
+        self.audit = Some(Mutex::new(log));
+        self
+    }
+
+    /// Fetch the bound address
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.http.server_addr().to_ip()
+    }
+
+    /// Fetch the signing public key
+    pub fn public_key(&self) -> &PublicKey {
+        &self.public_key
+    }
+
+    /// Handle incoming requests
+    pub fn run<RNG: CryptoRng + RngCore + Default>(&self) -> Result<(), RemoteError> {
+        for mut req in self.http.incoming_requests() {
+            let (status, body) = self.handle::<RNG>(&mut req);
+
+            let resp = tiny_http::Response::from_data(body)
+                .with_status_code(status);
+            if let Err(e) = req.respond(resp) {
+                warn!("Failed to send response: {:?}", e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handle a request, returning the response status and body
+    fn handle<RNG: CryptoRng + RngCore + Default>(&self, req: &mut tiny_http::Request) -> (u16, Vec<u8>) {
+        debug!("Request: {} {}", req.method(), req.url());
+
+        match (req.method(), req.url()) {
+            (tiny_http::Method::Get, KEY_PATH) => (200, self.public_key.to_string().into_bytes()),
+            (tiny_http::Method::Post, SIGN_PATH) => {
+                let auth = req.headers().iter()
+                    .find(|h| h.field.equiv("Authorization"))
+                    .map(|h| h.value.to_string());
+
+                // Read request body, bounded by the maximum manifest length
+                let mut body = vec![];
+                if req.as_reader().take(MANIFEST_MAX_LEN as u64 + 1).read_to_end(&mut body).is_err() {
+                    return (400, b"failed to read request".to_vec());
+                }
+
+                match self.sign::<RNG>(auth.as_deref(), &body) {
+                    Ok(sig) => (200, sig.to_vec()),
+                    Err(e) => {
+                        warn!("Rejected signing request: {}", e);
+                        (status(&e), e.to_string().into_bytes())
+                    },
+                }
+            },
+            _ => (404, b"not found".to_vec()),
+        }
+    }
+
+    /// Check a signing request against the server policy, returning the manifest signature
+    pub fn sign<RNG: CryptoRng + RngCore + Default>(&self, auth: Option<&str>, body: &[u8]) -> Result<Signature, RemoteError> {
+        self.policy.authorize(auth)?;
+
+        // Decode the manifest, rejecting trailing data or unknown flags
+        let mut m = match Manifest::decode_owned(body) {
+            Ok((m, n)) if n == body.len() => m,
+            _ => return Err(RemoteError::InvalidRequest),
+        };
+        if Flags::from_bits(m.flags).is_none() {
+            return Err(RemoteError::InvalidRequest);
+        }
+
+        self.policy.allow_app(m.app_name())?;
+
+        if m.key != self.public_key {
+            return Err(RemoteError::KeyMismatch);
+        }
+
+        m.sign::<RNG>(self.key.clone())?;
+
+        // Record signature, failing the request if this cannot be logged
+        #[cfg(feature = "audit")]
+        if let Some(a) = &self.audit {
+            let mut log = a.lock().map_err(|_e| RemoteError::AuditFailed)?;
+            log.append(&m).map_err(|_e| RemoteError::AuditFailed)?;
+        }
+
+        info!("Signed {} {} (app: {}, meta: {})", m.app_name(), m.app_version(), m.app_csum, m.meta_csum);
+
+        Ok(m.sig)
+    }
+}
+
+/// Map errors to HTTP response status codes
+fn status(e: &RemoteError) -> u16 {
+    match e {
+        RemoteError::Unauthorized => 401,
+        RemoteError::AppNotAllowed => 403,
+        RemoteError::KeyMismatch => 409,
+        RemoteError::AuditFailed => 500,
+        _ => 400,
+    }
+}
+
+/// Remote signing client
+pub struct Client {
+    url: String,
+    token: String,
+    key: PublicKey,
+    agent: ureq::Agent,
+}
+
+impl Client {
+    /// Create a client for the signing server at `url` with the provided bearer token,
+    /// expecting manifests to be signed with `key`
+    pub fn new(url: &str, token: &str, key: PublicKey) -> Self {
+        let agent = ureq::AgentBuilder::new()
+            .timeout(Duration::from_secs(30))
+            .build();
+
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+            token: token.to_string(),
+            key,
+            agent,
+        }
+    }
+
+    /// Fetch the server signing public key
+    pub fn public_key(&self) -> Result<PublicKey, RemoteError> {
+        let resp = self.agent.get(&format!("{}{}", self.url, KEY_PATH))
+            .call()
+            .map_err(map_err)?;
+
+        let s = resp.into_string()
+            .map_err(|_e| RemoteError::InvalidResponse)?;
+        PublicKey::from_str(s.trim())
+            .map_err(|_e| RemoteError::InvalidResponse)
+    }
+
+    /// Build a manifest using the expected signing key, signed by the server
+    ///
+    /// Fails with [RemoteError::KeyMismatch] if the server signing key is not the expected key
+    pub fn build(&self, b: &mut ManifestBuilder) -> Result<Manifest, RemoteError> {
+        if self.public_key()? != self.key {
+            return Err(RemoteError::KeyMismatch);
+        }
+
+        let mut m = b.build_unsigned(self.key.clone())?;
+
+        self.sign(&mut m)?;
+
+        Ok(m)
+    }
+
+    /// Sign a manifest via the server, checking the returned signature is valid for the manifest key
+    ///
+    /// The manifest key must be the expected signing key
+    pub fn sign(&self, m: &mut Manifest) -> Result<(), RemoteError> {
+        if m.key != self.key {
+            return Err(RemoteError::KeyMismatch);
+        }
+
+        let mut b = [0u8; MANIFEST_MAX_LEN];
+        let n = m.encode(&mut b)?;
+
+        let resp = self.agent.post(&format!("{}{}", self.url, SIGN_PATH))
+            .set("Authorization", &format!("Bearer {}", self.token))
+            .set("Content-Type", "application/octet-stream")
+            .send_bytes(&b[..n])
+            .map_err(map_err)?;
+
+        let mut sig = [0u8; SIGNATURE_LENGTH];
+        let mut r = resp.into_reader().take(SIGNATURE_LENGTH as u64 + 1);
+        let mut d = vec![];
+        r.read_to_end(&mut d).map_err(|_e| RemoteError::InvalidResponse)?;
+        if d.len() != SIGNATURE_LENGTH {
+            return Err(RemoteError::InvalidResponse);
+        }
+        sig.copy_from_slice(&d);
+        m.sig = Signature(sig);
+
+        // Check the server signed the manifest we sent
+        m.verify(core::slice::from_ref(&m.key))
+            .map_err(|_e| RemoteError::InvalidResponse)
+    }
+}
+
+/// Map HTTP client errors to [RemoteError]s
+fn map_err(e: ureq::Error) -> RemoteError {
+    match e {
+        ureq::Error::Status(401, _) => RemoteError::Unauthorized,
+        ureq::Error::Status(403, _) => RemoteError::AppNotAllowed,
+        ureq::Error::Status(409, _) => RemoteError::KeyMismatch,
+        ureq::Error::Status(400, _) => RemoteError::InvalidRequest,
+        ureq::Error::Status(s, _) => RemoteError::Status(s),
+        ureq::Error::Transport(_) => RemoteError::Io,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::OsRng;
+
+    use crate::test_utils::{builder, APP, META};
+
+    use super::*;
+
+    fn server(allowed_apps: &[&str]) -> (String, PublicKey) {
+        let policy = Policy {
+            tokens: vec!["token".to_string()],
+            allowed_apps: allowed_apps.iter().map(|a| a.to_string()).collect(),
+        };
+        let s = Server::bind("127.0.0.1:0", PrivateKey::generate(&mut OsRng{}), policy).unwrap();
+        let url = format!("http://{}", s.local_addr().unwrap());
+        let key = s.public_key().clone();
+
+        std::thread::spawn(move || s.run::<OsRng>());
+
+        (url, key)
+    }
+
+    #[test]
+    fn remote_sign() {
+        let (url, key) = server(&["app"]);
+        let c = Client::new(&url, "token", key.clone());
+
+        assert_eq!(c.public_key().unwrap(), key);
+
+        let m = c.build(builder(&META).name("app").unwrap()).unwrap();
+        assert_eq!(m.key, key);
+        assert!(!m.flags().contains(Flags::TRANSIENT_KEY));
+        m.verify(&[key]).unwrap();
+        m.check(&APP, &META).unwrap();
+    }
+
+    #[test]
+    fn remote_policy() {
+        let (url, key) = server(&["app"]);
+
+        // Bad tokens are rejected
+        let c = Client::new(&url, "other", key.clone());
+        assert_eq!(c.build(builder(&META).name("app").unwrap()).err(), Some(RemoteError::Unauthorized));
+
+        // Apps not on the allow list are rejected
+        let c = Client::new(&url, "token", key.clone());
+        assert_eq!(c.build(builder(&META).name("other").unwrap()).err(), Some(RemoteError::AppNotAllowed));
+
+        // Manifests for other keys are rejected by the client
+        let other = PublicKey::from(&PrivateKey::generate(&mut OsRng{}));
+        let mut m = builder(&META).name("app").unwrap().build_unsigned(other.clone()).unwrap();
+        assert_eq!(c.sign(&mut m).err(), Some(RemoteError::KeyMismatch));
+
+        // Servers with unexpected keys are rejected
+        let c = Client::new(&url, "token", other.clone());
+        assert_eq!(c.build(builder(&META).name("app").unwrap()).err(), Some(RemoteError::KeyMismatch));
+
+        // Wildcard allows any app
+        let (url, key) = server(&["*"]);
+        let c = Client::new(&url, "token", key);
+        c.build(builder(&META).name("other").unwrap()).unwrap();
+    }
+
+    #[test]
+    fn server_key_mismatch() {
+        let policy = Policy { tokens: vec!["token".to_string()], allowed_apps: vec!["*".to_string()] };
+        let s = Server::bind("127.0.0.1:0", PrivateKey::generate(&mut OsRng{}), policy).unwrap();
+
+        // Manifests for keys other than the server key are rejected by the server
+        let other = PublicKey::from(&PrivateKey::generate(&mut OsRng{}));
+        let m = builder(&META).name("app").unwrap().build_unsigned(other).unwrap();
+        let mut b = [0u8; MANIFEST_MAX_LEN];
+        let n = m.encode(&mut b).unwrap();
+        assert_eq!(s.sign::<OsRng>(Some("Bearer token"), &b[..n]).err(), Some(RemoteError::KeyMismatch));
+    }
+}
@@ -0,0 +1,204 @@
+//! Key rotation via next key commitments
+//!
+//! Manifests may commit to the next signing key by setting [Flags::NEXT_KEY](crate::Flags::NEXT_KEY)
+//! with the key commitment ([PublicKey::commitment]) trailing the metadata. Once a device has
+//! accepted an image signed by a trusted key with a commitment, images signed by the committed
+//! key are also trusted. Accepting an image signed by the committed key completes the rotation,
+//! replacing the previously trusted keys so a compromised or expiring key can be retired
+//! without updating the device trusted key list.
+//!
+//! Rotation state is persisted via the [KeyRotationStore] trait.
+
+use crate::{
+    types::{Checksum, PublicKey},
+    Manifest, RotationError,
+};
+
+/// Key rotation state
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct RotationState {
+    /// Rotated signing key, replacing the device trusted keys where set
+    pub key: Option<PublicKey>,
+    /// Commitment to the next signing key
+    pub next: Option<Checksum>,
+}
+
+/// Persistent storage for [RotationState]
+pub trait KeyRotationStore {
+    /// Load the current rotation state ([RotationState::default] if not yet stored)
+    fn load(&mut self) -> Result<RotationState, RotationError>;
+
+    /// Store updated rotation state
+    fn store(&mut self, state: &RotationState) -> Result<(), RotationError>;
+}
+
+impl KeyRotationStore for RotationState {
+    fn load(&mut self) -> Result<RotationState, RotationError> {
+        Ok(self.clone())
+    }
+
+    fn store(&mut self, state: &RotationState) -> Result<(), RotationError> {
+        *self = state.clone();
+        Ok(())
+    }
+}
+
+/// Key rotation helper, verifying manifests against device trusted keys
+/// and the persisted [RotationState]
+pub struct KeyRotation<'a, S: KeyRotationStore> {
+    trusted: &'a [PublicKey],
+    store: S,
+}
+
+impl<'a, S: KeyRotationStore> KeyRotation<'a, S> {
+    /// Create a key rotation helper with the device trusted keys and rotation store
+    pub fn new(trusted: &'a [PublicKey], store: S) -> Self {
+        Self { trusted, store }
+    }
+
+    /// Fetch the underlying store
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Verify a manifest signature against the currently trusted keys,
+    /// the rotated key if set (otherwise the device trusted keys) or the committed next key
+    pub fn verify(&mut self, m: &Manifest) -> Result<(), RotationError> {
+        let s = self.store.load()?;
+
+        if s.next.as_ref() == Some(&m.key.commitment()) {
+            return Ok(m.verify(core::slice::from_ref(&m.key))?);
+        }
+
+        match &s.key {
+            Some(k) => m.verify(core::slice::from_ref(k))?,
+            None => m.verify(self.trusted)?,
+        }
+
+        Ok(())
+    }
+
+    /// Accept a verified image, completing any rotation to the manifest key and
+    /// recording the next key commitment (if present)
+    ///
+    /// This should be called once the image is installed (or confirmed), with the
+    /// metadata checked against the manifest before the commitment is read
+    pub fn accept(&mut self, m: &Manifest, meta: &[u8]) -> Result<(), RotationError> {
+        self.verify(m)?;
+        m.check_meta(meta.len(), &Checksum::compute(meta))?;
+
+        let mut s = self.store.load()?;
+
+        // Complete rotation where signed by the committed key
+        if s.next.as_ref() == Some(&m.key.commitment()) {
+            s.key = Some(m.key.clone());
+            s.next = None;
+        }
+
+        // Record new commitment
+        if let Some(c) = m.next_key(meta)? {
+            s.next = Some(c);
+        }
+
+        self.store.store(&s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::OsRng;
+
+    use crate::{test_utils::APP, types::PrivateKey, ManifestBuilder, ManifestError, MetadataFormat, VerifyError};
+
+    use super::*;
+
+    /// Build a signed manifest and metadata (with commitment if `next` is set)
+    fn image(key: &PrivateKey, next: Option<&PublicKey>) -> (Manifest, Vec<u8>) {
+        let mut meta = b"{}".to_vec();
+        let mut b = ManifestBuilder::new();
+        b.app_bin(&APP).unwrap();
+
+        match next {
+            Some(k) => {
+                b.meta_bin_next_key(MetadataFormat::Json, &meta, k).unwrap();
+                meta.extend_from_slice(k.commitment().as_slice());
+            },
+            None => {
+                b.meta_bin(MetadataFormat::Json, &meta).unwrap();
+            },
+        }
+
+        let m = b.build::<OsRng>(Some(key.clone())).unwrap();
+        m.check(&APP, &meta).unwrap();
+
+        (m, meta)
+    }
+
+    #[test]
+    fn next_key_commitment() {
+        let k1 = PrivateKey::generate(&mut OsRng{});
+        let k2 = PublicKey::from(&PrivateKey::generate(&mut OsRng{}));
+
+        let (m, meta) = image(&k1, Some(&k2));
+        assert!(m.flags().contains(crate::Flags::NEXT_KEY));
+        assert_eq!(m.next_key(&meta), Ok(Some(k2.commitment())));
+        assert_eq!(m.next_key(&meta[..4]), Err(VerifyError::MetaLengthMismatch));
+
+        let (m, meta) = image(&k1, None);
+        assert_eq!(m.next_key(&meta), Ok(None));
+    }
+
+    #[test]
+    fn rotation() {
+        let keys: Vec<_> = (0..3).map(|_| PrivateKey::generate(&mut OsRng{})).collect();
+        let public: Vec<_> = keys.iter().map(PublicKey::from).collect();
+        let no_match = Err(RotationError::Manifest(ManifestError::NoMatchingKey));
+
+        let mut r = KeyRotation::new(&public[..1], RotationState::default());
+
+        // Next key is not trusted prior to a commitment
+        let m1 = image(&keys[1], None).0;
+        assert_eq!(r.verify(&m1), no_match);
+
+        // Verifying an image with a commitment does not update the state
+        let (m0, meta0) = image(&keys[0], Some(&public[1]));
+        r.verify(&m0).unwrap();
+        assert_eq!(r.verify(&m1), no_match);
+
+        // Accepting the image trusts the committed key, alongside the trusted keys
+        r.accept(&m0, &meta0).unwrap();
+        assert_eq!(r.store(), &RotationState{ key: None, next: Some(public[1].commitment()) });
+        r.verify(&m1).unwrap();
+        r.verify(&image(&keys[0], None).0).unwrap();
+
+        // Metadata must match the manifest
+        let (m3, meta3) = image(&keys[0], None);
+        assert_eq!(r.accept(&m3, &meta0), Err(RotationError::Verify(VerifyError::MetaLengthMismatch)));
+
+        // Commitments in metadata not matching the manifest are rejected
+        let (_m, meta5) = image(&keys[0], Some(&public[2]));
+        assert_eq!(r.accept(&m0, &meta5), Err(RotationError::Verify(VerifyError::MetaChecksumMismatch)));
+
+        // Commitments are not altered by images without commitments
+        r.accept(&m3, &meta3).unwrap();
+        r.verify(&m1).unwrap();
+
+        // Accepting an image signed by the committed key completes the rotation
+        let (m2, meta2) = image(&keys[1], Some(&public[2]));
+        r.accept(&m2, &meta2).unwrap();
+        assert_eq!(r.store(), &RotationState{ key: Some(public[1].clone()), next: Some(public[2].commitment()) });
+
+        // Previous keys are no longer trusted
+        assert_eq!(r.verify(&m0), no_match);
+        r.verify(&m1).unwrap();
+        r.verify(&image(&keys[2], None).0).unwrap();
+
+        // Rotation state persists across instances
+        let mut r = KeyRotation::new(&public[..1], r.store().clone());
+        let (m4, meta4) = image(&keys[2], None);
+        r.accept(&m4, &meta4).unwrap();
+        assert_eq!(r.store(), &RotationState{ key: Some(public[2].clone()), next: None });
+        assert_eq!(r.verify(&m1), no_match);
+        assert_eq!(r.accept(&m0, &meta0), no_match);
+    }
+}
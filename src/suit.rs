@@ -0,0 +1,485 @@
+//! [SUIT](https://www.rfc-editor.org/rfc/rfc9124) manifest export / import
+//!
+//! Maps an fwsig [Manifest] to a SUIT envelope with one component per package
+//! part (`app` and `meta`), each carrying a SHA-512/256 image digest and size, with
+//! optional vendor and class identifier conditions on the application component.
+//!
+//! The envelope authentication wrapper contains a SHA-256 digest of the SUIT manifest,
+//! signed as a detached-payload `COSE_Sign1` (EdDSA) using the fwsig [PrivateKey], with
+//! the public key bytes as the COSE key ID.
+
+use coset::{
+    cbor::{self, value::{Integer, Value}},
+    iana,
+};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    cose::Sign1,
+    types::{Checksum, PrivateKey, PublicKey},
+    Manifest, SuitError, VerifyError,
+};
+
+/// SUIT envelope CBOR tag
+pub const SUIT_ENVELOPE_TAG: u64 = 107;
+
+/// Component identifier for the application binary
+pub const SUIT_COMPONENT_APP: &[u8] = b"app";
+/// Component identifier for the metadata binary
+pub const SUIT_COMPONENT_META: &[u8] = b"meta";
+
+// Envelope / manifest keys
+const ENVELOPE_AUTHENTICATION: i64 = 2;
+const ENVELOPE_MANIFEST: i64 = 3;
+const MANIFEST_VERSION: i64 = 1;
+const MANIFEST_SEQUENCE_NUMBER: i64 = 2;
+const MANIFEST_COMMON: i64 = 3;
+const MANIFEST_VALIDATE: i64 = 7;
+const COMMON_COMPONENTS: i64 = 2;
+const COMMON_SHARED_SEQUENCE: i64 = 4;
+
+// Commands
+const CONDITION_VENDOR_ID: i64 = 1;
+const CONDITION_CLASS_ID: i64 = 2;
+const CONDITION_IMAGE_MATCH: i64 = 3;
+const DIRECTIVE_SET_COMPONENT_INDEX: i64 = 12;
+const DIRECTIVE_OVERRIDE_PARAMETERS: i64 = 20;
+
+// Parameters
+const PARAMETER_VENDOR_ID: i64 = 1;
+const PARAMETER_CLASS_ID: i64 = 2;
+const PARAMETER_IMAGE_DIGEST: i64 = 3;
+const PARAMETER_IMAGE_SIZE: i64 = 14;
+
+/// Report policy for conditions (send record and system info on success and failure)
+const REPORT_POLICY: i64 = 0x0f;
+
+/// Options for SUIT manifest export
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SuitOpts {
+    /// Manifest sequence number, used by devices for rollback protection
+    pub sequence_number: u64,
+    /// Vendor identifier (RFC 4122 UUID)
+    pub vendor_id: Option<[u8; 16]>,
+    /// Class identifier (RFC 4122 UUID)
+    pub class_id: Option<[u8; 16]>,
+}
+
+/// SUIT component with image digest and size
+#[derive(Clone, Debug, PartialEq)]
+pub struct SuitComponent {
+    /// Component identifier
+    pub id: Vec<Vec<u8>>,
+    /// Image digest (SHA-512/256)
+    pub digest: Checksum,
+    /// Image size
+    pub size: u64,
+}
+
+/// SUIT manifest contents mapped to fwsig types
+#[derive(Clone, Debug, PartialEq)]
+pub struct SuitManifest {
+    /// Manifest sequence number
+    pub sequence_number: u64,
+    /// Vendor identifier
+    pub vendor_id: Option<[u8; 16]>,
+    /// Class identifier
+    pub class_id: Option<[u8; 16]>,
+    /// Manifest components
+    pub components: Vec<SuitComponent>,
+}
+
+/// Decoded SUIT envelope
+#[derive(Clone, Debug, PartialEq)]
+pub struct SuitEnvelope {
+    /// Parsed SUIT manifest
+    pub manifest: SuitManifest,
+    /// COSE key ID (the signing public key for fwsig-produced envelopes)
+    pub key_id: Vec<u8>,
+
+    manifest_data: Vec<u8>,
+    digest_data: Vec<u8>,
+    sign1: Sign1,
+}
+
+impl SuitManifest {
+    /// Map an fwsig [Manifest] to a SUIT manifest
+    pub fn from_manifest(m: &Manifest, opts: &SuitOpts) -> Self {
+        let mut components = vec![SuitComponent {
+            id: vec![SUIT_COMPONENT_APP.to_vec()],
+            digest: m.app_csum.clone(),
+            size: m.app_len,
+        }];
+
+        if m.meta_len > 0 {
+            components.push(SuitComponent {
+                id: vec![SUIT_COMPONENT_META.to_vec()],
+                digest: m.meta_csum.clone(),
+                size: m.meta_len,
+            });
+        }
+
+        Self {
+            sequence_number: opts.sequence_number,
+            vendor_id: opts.vendor_id,
+            class_id: opts.class_id,
+            components,
+        }
+    }
+
+    /// Fetch a component by (single element) identifier
+    pub fn component(&self, id: &[u8]) -> Option<&SuitComponent> {
+        self.components.iter().find(|c| c.id.len() == 1 && c.id[0] == id)
+    }
+
+    /// Check application and metadata against the manifest components
+    pub fn check(&self, app: &[u8], meta: &[u8]) -> Result<(), VerifyError> {
+        let c = self.component(SUIT_COMPONENT_APP)
+            .ok_or(VerifyError::AppLengthMismatch)?;
+        if c.size != app.len() as u64 {
+            return Err(VerifyError::AppLengthMismatch);
+        }
+        if c.digest != Checksum::compute(app) {
+            return Err(VerifyError::AppChecksumMismatch);
+        }
+
+        let (size, digest) = match self.component(SUIT_COMPONENT_META) {
+            Some(c) => (c.size, c.digest.clone()),
+            None => (0, Checksum::compute(&[])),
+        };
+        if size != meta.len() as u64 {
+            return Err(VerifyError::MetaLengthMismatch);
+        }
+        if digest != Checksum::compute(meta) {
+            return Err(VerifyError::MetaChecksumMismatch);
+        }
+
+        Ok(())
+    }
+
+    /// Encode the SUIT manifest to CBOR
+    pub fn encode(&self) -> Result<Vec<u8>, SuitError> {
+        let mut shared = vec![];
+        let mut validate = vec![];
+
+        for (i, c) in self.components.iter().enumerate() {
+            let mut params = vec![];
+            if i == 0 {
+                if let Some(v) = &self.vendor_id {
+                    params.push((int(PARAMETER_VENDOR_ID), Value::Bytes(v.to_vec())));
+                }
+                if let Some(v) = &self.class_id {
+                    params.push((int(PARAMETER_CLASS_ID), Value::Bytes(v.to_vec())));
+                }
+            }
+
+            let digest = Value::Array(vec![int(iana::Algorithm::SHA_512_256 as i64), Value::Bytes(c.digest.to_vec())]);
+            params.push((int(PARAMETER_IMAGE_DIGEST), Value::Bytes(to_vec(&digest)?)));
+            params.push((int(PARAMETER_IMAGE_SIZE), uint(c.size)));
+
+            shared.extend([int(DIRECTIVE_SET_COMPONENT_INDEX), int(i as i64)]);
+            shared.extend([int(DIRECTIVE_OVERRIDE_PARAMETERS), Value::Map(params)]);
+
+            if i == 0 && self.vendor_id.is_some() {
+                shared.extend([int(CONDITION_VENDOR_ID), int(REPORT_POLICY)]);
+            }
+            if i == 0 && self.class_id.is_some() {
+                shared.extend([int(CONDITION_CLASS_ID), int(REPORT_POLICY)]);
+            }
+
+            validate.extend([int(DIRECTIVE_SET_COMPONENT_INDEX), int(i as i64)]);
+            validate.extend([int(CONDITION_IMAGE_MATCH), int(REPORT_POLICY)]);
+        }
+
+        let components = self.components.iter()
+            .map(|c| Value::Array(c.id.iter().map(|v| Value::Bytes(v.clone())).collect()))
+            .collect();
+
+        let common = Value::Map(vec![
+            (int(COMMON_COMPONENTS), Value::Array(components)),
+            (int(COMMON_SHARED_SEQUENCE), Value::Bytes(to_vec(&Value::Array(shared))?)),
+        ]);
+
+        let manifest = Value::Map(vec![
+            (int(MANIFEST_VERSION), int(1)),
+            (int(MANIFEST_SEQUENCE_NUMBER), uint(self.sequence_number)),
+            (int(MANIFEST_COMMON), Value::Bytes(to_vec(&common)?)),
+            (int(MANIFEST_VALIDATE), Value::Bytes(to_vec(&Value::Array(validate))?)),
+        ]);
+
+        to_vec(&manifest)
+    }
+
+    /// Decode a SUIT manifest from CBOR
+    pub fn decode(data: &[u8]) -> Result<Self, SuitError> {
+        let m = from_slice(data)?;
+
+        if map_get(&m, MANIFEST_VERSION).and_then(as_u64) != Some(1) {
+            return Err(SuitError::UnsupportedVersion);
+        }
+        let sequence_number = map_get(&m, MANIFEST_SEQUENCE_NUMBER).and_then(as_u64)
+            .ok_or(SuitError::InvalidManifest)?;
+
+        let common = map_get(&m, MANIFEST_COMMON).and_then(Value::as_bytes)
+            .ok_or(SuitError::InvalidManifest)?;
+        let common = from_slice(common)?;
+
+        // Load component identifiers
+        let ids = map_get(&common, COMMON_COMPONENTS).and_then(Value::as_array)
+            .ok_or(SuitError::InvalidManifest)?;
+        let mut params = Vec::new();
+        for id in ids {
+            let id: Vec<Vec<u8>> = id.as_array().ok_or(SuitError::InvalidManifest)?
+                .iter().map(|v| v.as_bytes().cloned().ok_or(SuitError::InvalidManifest))
+                .collect::<Result<_, _>>()?;
+            params.push((id, Vec::<(Value, Value)>::new()));
+        }
+
+        // Apply shared sequence parameter overrides to components
+        let shared = map_get(&common, COMMON_SHARED_SEQUENCE).and_then(Value::as_bytes)
+            .ok_or(SuitError::InvalidManifest)?;
+        let shared = from_slice(shared)?;
+        let shared = shared.as_array().ok_or(SuitError::InvalidManifest)?;
+
+        let mut index = 0;
+        for c in shared.chunks(2) {
+            let (cmd, arg) = match c {
+                [cmd, arg] => (as_i64(cmd).ok_or(SuitError::InvalidManifest)?, arg),
+                _ => return Err(SuitError::InvalidManifest),
+            };
+
+            match cmd {
+                DIRECTIVE_SET_COMPONENT_INDEX => {
+                    index = as_u64(arg).filter(|i| (*i as usize) < params.len())
+                        .ok_or(SuitError::InvalidManifest)? as usize;
+                },
+                DIRECTIVE_OVERRIDE_PARAMETERS => {
+                    let p = arg.as_map().ok_or(SuitError::InvalidManifest)?;
+                    params.get_mut(index).ok_or(SuitError::InvalidManifest)?
+                        .1.extend(p.iter().cloned());
+                },
+                _ => (),
+            }
+        }
+
+        // Map component parameters
+        let mut vendor_id = None;
+        let mut class_id = None;
+        let mut components = Vec::new();
+
+        for (id, p) in params {
+            if let Some(v) = map_get_last(&p, PARAMETER_VENDOR_ID) {
+                vendor_id = Some(uuid(v)?);
+            }
+            if let Some(v) = map_get_last(&p, PARAMETER_CLASS_ID) {
+                class_id = Some(uuid(v)?);
+            }
+
+            let digest = map_get_last(&p, PARAMETER_IMAGE_DIGEST).and_then(Value::as_bytes)
+                .ok_or(SuitError::InvalidManifest)?;
+            let digest = from_slice(digest)?;
+            let digest = match digest.as_array().map(|v| v.as_slice()) {
+                Some([alg, d]) if as_i64(alg) == Some(iana::Algorithm::SHA_512_256 as i64) => d,
+                Some([_alg, _d]) => return Err(SuitError::UnsupportedAlgorithm),
+                _ => return Err(SuitError::InvalidManifest),
+            };
+            let digest = digest.as_bytes().and_then(|d| <[u8; 32]>::try_from(d.as_slice()).ok())
+                .ok_or(SuitError::InvalidManifest)?;
+
+            let size = map_get_last(&p, PARAMETER_IMAGE_SIZE).and_then(as_u64)
+                .ok_or(SuitError::InvalidManifest)?;
+
+            components.push(SuitComponent { id, digest: Checksum(digest), size });
+        }
+
+        Ok(Self { sequence_number, vendor_id, class_id, components })
+    }
+}
+
+/// Encode an fwsig [Manifest] as a signed SUIT envelope
+///
+/// Manifests using block hashing are not supported as the SUIT image digest
+/// must cover the whole application
+pub fn encode(m: &Manifest, key: &PrivateKey, opts: &SuitOpts) -> Result<Vec<u8>, SuitError> {
+    if m.block_size().is_some() {
+        return Err(SuitError::UnsupportedAlgorithm);
+    }
+
+    let manifest_data = SuitManifest::from_manifest(m, opts).encode()?;
+
+    // Compute manifest digest
+    let digest = Value::Array(vec![int(iana::Algorithm::SHA_256 as i64), Value::Bytes(Sha256::digest(&manifest_data).to_vec())]);
+    let digest_data = to_vec(&digest)?;
+
+    // Sign digest using detached COSE_Sign1
+    let sign1_data = Sign1::sign(&digest_data, key, true).encode()?;
+
+    // Build envelope
+    let auth = Value::Array(vec![Value::Bytes(digest_data), Value::Bytes(sign1_data)]);
+    let envelope = Value::Tag(SUIT_ENVELOPE_TAG, Box::new(Value::Map(vec![
+        (int(ENVELOPE_AUTHENTICATION), Value::Bytes(to_vec(&auth)?)),
+        (int(ENVELOPE_MANIFEST), Value::Bytes(manifest_data)),
+    ])));
+
+    to_vec(&envelope)
+}
+
+impl SuitEnvelope {
+    /// Decode a SUIT envelope, parsing the manifest and authentication wrapper
+    ///
+    /// Note this does not verify the envelope signature, see [SuitEnvelope::verify]
+    pub fn decode(data: &[u8]) -> Result<Self, SuitError> {
+        let e = match from_slice(data)? {
+            Value::Tag(SUIT_ENVELOPE_TAG, v) => *v,
+            v @ Value::Map(_) => v,
+            _ => return Err(SuitError::InvalidEnvelope),
+        };
+
+        let manifest_data = map_get(&e, ENVELOPE_MANIFEST).and_then(Value::as_bytes)
+            .ok_or(SuitError::InvalidEnvelope)?.clone();
+        let manifest = SuitManifest::decode(&manifest_data)?;
+
+        // Load digest and first COSE_Sign1 authentication block
+        let auth = map_get(&e, ENVELOPE_AUTHENTICATION).and_then(Value::as_bytes)
+            .ok_or(SuitError::InvalidEnvelope)?;
+        let auth = from_slice(auth)?;
+        let (digest_data, sign1) = match auth.as_array().map(|v| v.as_slice()) {
+            Some([Value::Bytes(d), Value::Bytes(s), ..]) => (d.clone(), s),
+            _ => return Err(SuitError::InvalidEnvelope),
+        };
+
+        let sign1 = Sign1::decode(sign1)?;
+        let key_id = sign1.key_id().to_vec();
+
+        Ok(Self { manifest, key_id, manifest_data, digest_data, sign1 })
+    }
+
+    /// Check the envelope digest and signature against the signing key in the key ID,
+    /// returning the signing key
+    ///
+    /// Note this does not check the signing key is trusted, see [SuitEnvelope::verify]
+    pub fn check_sig(&self) -> Result<PublicKey, SuitError> {
+        // Check manifest digest
+        let digest = from_slice(&self.digest_data)?;
+        let expected = match digest.as_array().map(|v| v.as_slice()) {
+            Some([alg, Value::Bytes(d)]) if as_i64(alg) == Some(iana::Algorithm::SHA_256 as i64) => d,
+            Some([_alg, _d]) => return Err(SuitError::UnsupportedAlgorithm),
+            _ => return Err(SuitError::InvalidEnvelope),
+        };
+        if &Sha256::digest(&self.manifest_data)[..] != expected.as_slice() {
+            return Err(SuitError::DigestMismatch);
+        }
+
+        // Verify signature over the digest using the key ID
+        let key = ed25519_dalek::PublicKey::from_bytes(&self.key_id)
+            .map(PublicKey::from)
+            .map_err(|_e| SuitError::InvalidSignature)?;
+
+        self.sign1.verify(&key, Some(&self.digest_data))?;
+
+        Ok(key)
+    }
+
+    /// Verify the envelope digest and signature, then check the signing key is one of
+    /// the allowed keys, returning the matching key
+    pub fn verify(&self, allowed_keys: &[PublicKey]) -> Result<PublicKey, SuitError> {
+        let key = self.check_sig()?;
+
+        if !allowed_keys.contains(&key) {
+            return Err(SuitError::NoMatchingKey);
+        }
+
+        Ok(key)
+    }
+}
+
+fn int(v: i64) -> Value {
+    Value::Integer(v.into())
+}
+
+fn uint(v: u64) -> Value {
+    Value::Integer(v.into())
+}
+
+fn as_i64(v: &Value) -> Option<i64> {
+    v.as_integer().and_then(|i| i64::try_from(i).ok())
+}
+
+fn as_u64(v: &Value) -> Option<u64> {
+    v.as_integer().and_then(|i: Integer| u64::try_from(i).ok())
+}
+
+fn uuid(v: &Value) -> Result<[u8; 16], SuitError> {
+    v.as_bytes().and_then(|b| <[u8; 16]>::try_from(b.as_slice()).ok())
+        .ok_or(SuitError::InvalidIdentifier)
+}
+
+fn map_get(v: &Value, key: i64) -> Option<&Value> {
+    v.as_map().and_then(|m| map_get_last(m, key))
+}
+
+fn map_get_last(m: &[(Value, Value)], key: i64) -> Option<&Value> {
+    m.iter().rev().find(|(k, _v)| as_i64(k) == Some(key)).map(|(_k, v)| v)
+}
+
+fn to_vec(v: &Value) -> Result<Vec<u8>, SuitError> {
+    let mut b = Vec::new();
+    cbor::ser::into_writer(v, &mut b).map_err(|_| SuitError::Cbor)?;
+    Ok(b)
+}
+
+fn from_slice(b: &[u8]) -> Result<Value, SuitError> {
+    cbor::de::from_reader(b).map_err(|_| SuitError::Cbor)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::OsRng;
+
+    use crate::test_utils::{manifest, APP, META};
+
+    use super::*;
+
+    #[test]
+    fn encode_decode_verify() {
+        let key = PrivateKey::generate(&mut OsRng{});
+        let m = manifest(Some(&key), &META);
+
+        let opts = SuitOpts{ sequence_number: 7, vendor_id: Some([0x11; 16]), class_id: Some([0x22; 16]) };
+        let e = encode(&m, &key, &opts).unwrap();
+
+        let s = SuitEnvelope::decode(&e).unwrap();
+        assert_eq!(s.manifest, SuitManifest::from_manifest(&m, &opts));
+        assert_eq!(s.manifest.component(SUIT_COMPONENT_APP).unwrap().digest, m.app_csum);
+        s.manifest.check(&APP, &META).unwrap();
+        assert_eq!(s.manifest.check(&APP[..99], &META), Err(VerifyError::AppLengthMismatch));
+
+        let public_key = PublicKey::from(&key);
+        assert_eq!(s.verify(core::slice::from_ref(&public_key)), Ok(public_key));
+
+        let other = PublicKey::from(&PrivateKey::generate(&mut OsRng{}));
+        assert_eq!(s.verify(&[other]), Err(SuitError::NoMatchingKey));
+        assert_eq!(s.check_sig(), Ok(PublicKey::from(&key)));
+    }
+
+    #[test]
+    fn verify_tampered() {
+        let key = PrivateKey::generate(&mut OsRng{});
+        let m = manifest(Some(&key), &META);
+
+        let e = encode(&m, &key, &SuitOpts::default()).unwrap();
+        let mut s = SuitEnvelope::decode(&e).unwrap();
+
+        // Modified manifest fails the digest check
+        let opts = SuitOpts{ sequence_number: 1, ..Default::default() };
+        s.manifest_data = SuitManifest::from_manifest(&m, &opts).encode().unwrap();
+        assert_eq!(s.verify(&[PublicKey::from(&key)]), Err(SuitError::DigestMismatch));
+        assert_eq!(s.check_sig(), Err(SuitError::DigestMismatch));
+
+        // Signatures by other keys fail verification
+        let mut s = SuitEnvelope::decode(&e).unwrap();
+        s.sign1 = Sign1::sign(&s.digest_data, &PrivateKey::generate(&mut OsRng{}), true);
+        assert_eq!(s.verify(&[PublicKey::from(&key)]), Err(SuitError::InvalidSignature));
+        assert_eq!(s.check_sig(), Err(SuitError::InvalidSignature));
+    }
+}
@@ -0,0 +1,30 @@
+//! Shared test helpers
+
+use rand::rngs::OsRng;
+
+use crate::{types::PrivateKey, Manifest, ManifestBuilder, MetadataFormat};
+
+/// Test application image
+pub const APP: [u8; 100] = [0xab; 100];
+
+/// Test metadata
+pub const META: [u8; 10] = [0xcd; 10];
+
+/// Create a manifest builder over [APP] and the provided binary metadata
+pub fn builder(meta: &[u8]) -> ManifestBuilder {
+    let mut b = ManifestBuilder::new();
+    b.app_bin(&APP).unwrap()
+        .meta_bin(MetadataFormat::Binary, meta).unwrap();
+    b
+}
+
+/// Build a manifest over [APP] and the provided metadata, signed with `key` (or a transient key)
+pub fn manifest(key: Option<&PrivateKey>, meta: &[u8]) -> Manifest {
+    builder(meta).build::<OsRng>(key.cloned()).unwrap()
+}
+
+/// Fetch a temporary path unique to this test process
+#[cfg(feature = "std")]
+pub fn temp_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("fwsig-{}-{}", name, std::process::id()))
+}
@@ -0,0 +1,705 @@
+//! Transparency log, an append-only Merkle tree of signed manifest digests
+//!
+//! Each leaf is a manifest digest ([Manifest::signed_digest]), with leaf and node hashes
+//! computed as in [RFC 9162](https://www.rfc-editor.org/rfc/rfc9162) using SHA-512/256.
+//! The log signs a [TreeHead] for each new tree size, and produces [InclusionProof]s
+//! showing a manifest is included in a signed tree, which may be checked by devices
+//! or auditors holding the log public key.
+//!
+//! Inclusion proofs may be carried in attached packages, placed immediately after the
+//! metadata (see [Package::inclusion_proof]). As this is outside the signed package
+//! contents, proofs may be added to packages after signing.
+//!
+//! With the `std` feature the [Log] type maintains a log in a local directory.
+
+use encdec::{DecodeOwned, Encode};
+use sha2::{Digest, Sha512Trunc256};
+
+use crate::{
+    types::{PrivateKey, PublicKey, Signature},
+    blocks::BlockTable,
+    LogError, Manifest, Package,
+};
+
+/// Merkle tree hash
+pub type Hash = [u8; 32];
+
+/// Signing context for tree heads
+pub const LOG_CONTEXT: &[u8] = b"fwsig-log-v1";
+
+/// Magic identifying an encoded [InclusionProof]
+pub const PROOF_MAGIC: [u8; 4] = *b"FWLP";
+
+/// Maximum inclusion proof path length (supporting logs of up to `2^32` entries)
+pub const MAX_PROOF_DEPTH: usize = 32;
+
+/// Encoded [TreeHead] length
+pub const TREE_HEAD_LEN: usize = 8 + 32 + 64;
+
+/// Compute the hash of a leaf, `H(0x00 || data)`
+pub fn leaf_hash(data: &[u8]) -> Hash {
+    Sha512Trunc256::new()
+        .chain([0x00])
+        .chain(data)
+        .finalize()
+        .into()
+}
+
+/// Compute the hash of an interior node, `H(0x01 || left || right)`
+pub fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    Sha512Trunc256::new()
+        .chain([0x01])
+        .chain(left)
+        .chain(right)
+        .finalize()
+        .into()
+}
+
+/// Compute the Merkle tree hash over `n` leaves, with leaf hashes provided by `leaf(i)`
+pub fn tree_root(n: usize, leaf: &impl Fn(usize) -> Hash) -> Hash {
+    fn root(start: usize, n: usize, leaf: &impl Fn(usize) -> Hash) -> Hash {
+        match n {
+            0 => Sha512Trunc256::digest(&[]).into(),
+            1 => leaf(start),
+            n => {
+                let k = split(n);
+                node_hash(&root(start, k, leaf), &root(start + k, n - k, leaf))
+            },
+        }
+    }
+
+    root(0, n, leaf)
+}
+
+/// Compute the largest power of two less than `n` (for `n > 1`)
+fn split(n: usize) -> usize {
+    1 << (usize::BITS - (n - 1).leading_zeros() - 1)
+}
+
+/// Signed tree head
+#[derive(Clone, Debug, PartialEq)]
+pub struct TreeHead {
+    /// Tree size (number of leaves)
+    pub size: u64,
+    /// Merkle tree root hash
+    pub root: Hash,
+    /// Log signature over [TreeHead::message]
+    pub sig: Signature,
+}
+
+impl TreeHead {
+    /// Create and sign a tree head with the log key
+    pub fn sign(size: u64, root: Hash, key: &PrivateKey) -> Result<Self, LogError> {
+        let public_key = ed25519_dalek::PublicKey::from(&**key);
+        let keys = ed25519_dalek::Keypair{ public: public_key, secret: key.clone().inner() };
+
+        let mut h = Self { size, root, sig: Signature::empty() };
+        let sig = ed25519_dalek::Signer::try_sign(&keys, &h.message())
+            .map_err(|_e| LogError::InvalidHead)?;
+        h.sig = Signature::from(sig);
+
+        Ok(h)
+    }
+
+    /// Fetch the signed message, [LOG_CONTEXT] followed by the tree size (u64, LE) and root hash
+    pub fn message(&self) -> [u8; LOG_CONTEXT.len() + 8 + 32] {
+        let mut b = [0u8; LOG_CONTEXT.len() + 8 + 32];
+        b[..LOG_CONTEXT.len()].copy_from_slice(LOG_CONTEXT);
+        b[LOG_CONTEXT.len()..][..8].copy_from_slice(&self.size.to_le_bytes());
+        b[LOG_CONTEXT.len() + 8..].copy_from_slice(&self.root);
+        b
+    }
+
+    /// Verify the tree head signature against the log key
+    pub fn verify(&self, log_key: &PublicKey) -> Result<(), LogError> {
+        let sig = ed25519_dalek::Signature::try_from(&self.sig)
+            .map_err(|_e| LogError::InvalidHead)?;
+
+        ed25519_dalek::Verifier::verify(&**log_key, &self.message(), &sig)
+            .map_err(|_e| LogError::InvalidHead)
+    }
+}
+
+impl Encode for TreeHead {
+    type Error = LogError;
+
+    fn encode_len(&self) -> Result<usize, Self::Error> {
+        Ok(TREE_HEAD_LEN)
+    }
+
+    fn encode(&self, buff: &mut [u8]) -> Result<usize, Self::Error> {
+        if buff.len() < TREE_HEAD_LEN {
+            return Err(encdec::Error::Length.into());
+        }
+
+        buff[..8].copy_from_slice(&self.size.to_le_bytes());
+        buff[8..][..32].copy_from_slice(&self.root);
+        buff[40..][..64].copy_from_slice(&self.sig[..]);
+
+        Ok(TREE_HEAD_LEN)
+    }
+}
+
+impl DecodeOwned for TreeHead {
+    type Output = TreeHead;
+
+    type Error = LogError;
+
+    fn decode_owned(buff: &[u8]) -> Result<(Self::Output, usize), Self::Error> {
+        if buff.len() < TREE_HEAD_LEN {
+            return Err(encdec::Error::Length.into());
+        }
+
+        let mut h = Self { size: 0, root: [0u8; 32], sig: Signature::empty() };
+        h.size = u64::from_le_bytes(buff[..8].try_into().unwrap());
+        h.root.copy_from_slice(&buff[8..][..32]);
+        h.sig.0.copy_from_slice(&buff[40..][..64]);
+
+        Ok((h, TREE_HEAD_LEN))
+    }
+}
+
+/// Inclusion proof for a manifest in a signed tree
+///
+/// Encoding: [PROOF_MAGIC], leaf index (u64, LE), [TreeHead], path length (u8), path hashes
+#[derive(Clone, Debug, PartialEq)]
+pub struct InclusionProof {
+    /// Leaf index
+    pub index: u64,
+    /// Signed tree head the proof is relative to
+    pub head: TreeHead,
+    path: [Hash; MAX_PROOF_DEPTH],
+    path_len: usize,
+}
+
+impl InclusionProof {
+    /// Create an inclusion proof from an audit path (leaf to root order)
+    pub fn new(index: u64, head: TreeHead, audit_path: &[Hash]) -> Result<Self, LogError> {
+        if audit_path.len() > MAX_PROOF_DEPTH {
+            return Err(LogError::InvalidProof);
+        }
+
+        let mut path = [[0u8; 32]; MAX_PROOF_DEPTH];
+        path[..audit_path.len()].copy_from_slice(audit_path);
+
+        Ok(Self { index, head, path, path_len: audit_path.len() })
+    }
+
+    /// Fetch the audit path (leaf to root order)
+    pub fn path(&self) -> &[Hash] {
+        &self.path[..self.path_len]
+    }
+
+    /// Verify the manifest is included in the log, checking the tree head signature
+    /// against the log key then the audit path against the tree head
+    pub fn verify(&self, m: &Manifest, log_key: &PublicKey) -> Result<(), LogError> {
+        self.head.verify(log_key)?;
+
+        let leaf = leaf_hash(&m.signed_digest()?);
+        match self.root(&leaf) {
+            Some(r) if r == self.head.root => Ok(()),
+            _ => Err(LogError::InvalidProof),
+        }
+    }
+
+    /// Compute the root hash from a leaf hash and the audit path (RFC 9162 2.1.3.2)
+    fn root(&self, leaf: &Hash) -> Option<Hash> {
+        if self.index >= self.head.size {
+            return None;
+        }
+
+        let (mut f, mut s) = (self.index, self.head.size - 1);
+        let mut r = *leaf;
+
+        for p in self.path() {
+            if s == 0 {
+                return None;
+            }
+
+            if f & 1 == 1 || f == s {
+                r = node_hash(p, &r);
+                while f & 1 == 0 && f != 0 {
+                    f >>= 1;
+                    s >>= 1;
+                }
+            } else {
+                r = node_hash(&r, p);
+            }
+
+            f >>= 1;
+            s >>= 1;
+        }
+
+        match s {
+            0 => Some(r),
+            _ => None,
+        }
+    }
+}
+
+impl Encode for InclusionProof {
+    type Error = LogError;
+
+    fn encode_len(&self) -> Result<usize, Self::Error> {
+        Ok(PROOF_MAGIC.len() + 8 + TREE_HEAD_LEN + 1 + self.path_len * 32)
+    }
+
+    fn encode(&self, buff: &mut [u8]) -> Result<usize, Self::Error> {
+        let n = self.encode_len()?;
+        if buff.len() < n {
+            return Err(encdec::Error::Length.into());
+        }
+
+        let mut index = 0;
+        let mut put = |d: &[u8]| {
+            buff[index..][..d.len()].copy_from_slice(d);
+            index += d.len();
+        };
+
+        put(&PROOF_MAGIC);
+        put(&self.index.to_le_bytes());
+
+        let mut h = [0u8; TREE_HEAD_LEN];
+        self.head.encode(&mut h)?;
+        put(&h);
+
+        put(&[self.path_len as u8]);
+        for p in self.path() {
+            put(p);
+        }
+
+        Ok(index)
+    }
+}
+
+impl DecodeOwned for InclusionProof {
+    type Output = InclusionProof;
+
+    type Error = LogError;
+
+    fn decode_owned(buff: &[u8]) -> Result<(Self::Output, usize), Self::Error> {
+        let hdr = PROOF_MAGIC.len() + 8 + TREE_HEAD_LEN + 1;
+        if buff.len() < hdr || buff[..PROOF_MAGIC.len()] != PROOF_MAGIC {
+            return Err(LogError::InvalidProof);
+        }
+
+        let index = u64::from_le_bytes(buff[4..][..8].try_into().unwrap());
+        let (head, _) = TreeHead::decode_owned(&buff[12..])?;
+
+        let path_len = buff[hdr - 1] as usize;
+        if path_len > MAX_PROOF_DEPTH || buff.len() < hdr + path_len * 32 {
+            return Err(LogError::InvalidProof);
+        }
+
+        let mut path = [[0u8; 32]; MAX_PROOF_DEPTH];
+        for (i, p) in path[..path_len].iter_mut().enumerate() {
+            p.copy_from_slice(&buff[hdr + i * 32..][..32]);
+        }
+
+        Ok((Self { index, head, path, path_len }, hdr + path_len * 32))
+    }
+}
+
+impl Package<'_> {
+    /// Fetch the inclusion proof carried in the package (following the metadata and any block table), if present
+    pub fn inclusion_proof(&self) -> Result<Option<InclusionProof>, LogError> {
+        // Skip block table where present
+        let extra = match BlockTable::decode(self.extra) {
+            Ok((_t, n)) => &self.extra[n..],
+            Err(_e) => self.extra,
+        };
+
+        if !extra.starts_with(&PROOF_MAGIC) {
+            return Ok(None);
+        }
+
+        let (p, _n) = InclusionProof::decode_owned(extra)?;
+
+        Ok(Some(p))
+    }
+}
+
+#[cfg(feature = "std")]
+pub use store::Log;
+
+#[cfg(feature = "std")]
+mod store {
+    use std::{
+        fs::OpenOptions,
+        io::Write,
+        path::{Path, PathBuf},
+        str::FromStr,
+    };
+
+    use encdec::{DecodeOwned, EncodeExt};
+
+    use super::*;
+
+    /// Log public key file
+    const KEY_FILE: &str = "log.pub";
+    /// Leaf data (64-byte manifest digests) file
+    const LEAVES_FILE: &str = "leaves.bin";
+    /// Signed tree head file
+    const HEAD_FILE: &str = "head.bin";
+
+    /// Leaf data length
+    const LEAF_LEN: usize = 64;
+
+    /// Transparency log stored in a local directory
+    #[derive(Debug)]
+    pub struct Log {
+        dir: PathBuf,
+        key: Option<PublicKey>,
+        leaves: Vec<[u8; LEAF_LEN]>,
+        head: Option<TreeHead>,
+    }
+
+    impl Log {
+        /// Open a log directory, creating this if it does not exist
+        ///
+        /// The log contents are checked against the signed tree head, a (possibly partial)
+        /// leaf following the signed tree size from an interrupted append is discarded
+        pub fn open(dir: impl AsRef<Path>) -> Result<Self, LogError> {
+            let dir = dir.as_ref().to_path_buf();
+            std::fs::create_dir_all(&dir)
+                .map_err(|_e| LogError::Io)?;
+
+            // Load log key
+            let key = match std::fs::read_to_string(dir.join(KEY_FILE)) {
+                Ok(s) => Some(PublicKey::from_str(s.trim()).map_err(|_e| LogError::InvalidLog)?),
+                Err(_e) => None,
+            };
+
+            // Load tree head
+            let head = match std::fs::read(dir.join(HEAD_FILE)) {
+                Ok(d) => Some(TreeHead::decode_owned(&d).map_err(|_e| LogError::InvalidLog)?.0),
+                Err(_e) => None,
+            };
+
+            // Load leaves, truncating a leaf written after the signed tree head
+            let mut d = std::fs::read(dir.join(LEAVES_FILE)).unwrap_or_default();
+            let signed_len = head.as_ref().map(|h| h.size).unwrap_or(0)
+                .checked_mul(LEAF_LEN as u64)
+                .ok_or(LogError::InvalidLog)?;
+            if d.len() as u64 > signed_len + LEAF_LEN as u64 {
+                return Err(LogError::InvalidLog);
+            } else if d.len() as u64 > signed_len {
+                d.truncate(signed_len as usize);
+                truncate(&dir.join(LEAVES_FILE), signed_len)?;
+            }
+            if d.len() % LEAF_LEN != 0 {
+                return Err(LogError::InvalidLog);
+            }
+            let leaves = d.chunks(LEAF_LEN)
+                .map(|c| c.try_into().unwrap())
+                .collect();
+
+            let log = Self { dir, key, leaves, head };
+            log.check()?;
+
+            Ok(log)
+        }
+
+        /// Fetch the log public key (set on the first append)
+        pub fn key(&self) -> Option<&PublicKey> {
+            self.key.as_ref()
+        }
+
+        /// Fetch the number of log entries
+        pub fn len(&self) -> usize {
+            self.leaves.len()
+        }
+
+        /// Check whether the log is empty
+        pub fn is_empty(&self) -> bool {
+            self.leaves.is_empty()
+        }
+
+        /// Fetch the current signed tree head
+        pub fn head(&self) -> Option<&TreeHead> {
+            self.head.as_ref()
+        }
+
+        /// Compute the root hash over the current log entries
+        pub fn root(&self) -> Hash {
+            let hashes: Vec<_> = self.leaves.iter().map(|l| leaf_hash(l)).collect();
+            tree_hash(&hashes)
+        }
+
+        /// Find the index of a manifest in the log
+        pub fn find(&self, m: &Manifest) -> Result<Option<u64>, LogError> {
+            let d = m.signed_digest()?;
+            Ok(self.leaves.iter().position(|l| l == &d).map(|i| i as u64))
+        }
+
+        /// Append a manifest digest to the log, signing the new tree head with the log key
+        ///
+        /// Returns the leaf index and updated tree head, manifests with invalid signatures are rejected
+        pub fn append(&mut self, m: &Manifest, key: &PrivateKey) -> Result<(u64, TreeHead), LogError> {
+            m.verify_signature(&m.key, &m.sig)?;
+
+            let public_key = PublicKey::from(key);
+            if matches!(&self.key, Some(k) if k != &public_key) {
+                return Err(LogError::KeyMismatch);
+            }
+
+            if self.find(m)?.is_some() {
+                return Err(LogError::Duplicate);
+            }
+
+            if self.key.is_none() {
+                write_atomic(&self.dir.join(KEY_FILE), public_key.to_string().as_bytes())?;
+                self.key = Some(public_key);
+            }
+
+            // Append leaf
+            let d = m.signed_digest()?;
+            let mut f = OpenOptions::new().create(true).append(true).open(self.dir.join(LEAVES_FILE))
+                .map_err(|_e| LogError::Io)?;
+            f.write_all(&d)
+                .and_then(|_| f.sync_data())
+                .map_err(|_e| LogError::Io)?;
+
+            let mut leaves = self.leaves.clone();
+            leaves.push(d);
+
+            // Sign and store new tree head, the appended leaf is only part of the log once this is written
+            let hashes: Vec<_> = leaves.iter().map(|l| leaf_hash(l)).collect();
+            let head = TreeHead::sign(leaves.len() as u64, tree_hash(&hashes), key)?;
+            let (b, n) = head.encode_buff::<TREE_HEAD_LEN>()?;
+            if let Err(e) = write_atomic(&self.dir.join(HEAD_FILE), &b[..n]) {
+                let _ = truncate(&self.dir.join(LEAVES_FILE), (self.leaves.len() * LEAF_LEN) as u64);
+                return Err(e);
+            }
+
+            self.leaves = leaves;
+            self.head = Some(head.clone());
+
+            Ok((self.leaves.len() as u64 - 1, head))
+        }
+
+        /// Generate an inclusion proof for a manifest against the current tree head
+        pub fn prove(&self, m: &Manifest) -> Result<InclusionProof, LogError> {
+            let index = self.find(m)?.ok_or(LogError::NotFound)?;
+            let head = self.head.clone().ok_or(LogError::NotFound)?;
+
+            let hashes: Vec<_> = self.leaves.iter().map(|l| leaf_hash(l)).collect();
+            let mut path = vec![];
+            audit_path(index as usize, &hashes, &mut path);
+
+            InclusionProof::new(index, head, &path)
+        }
+
+        /// Check the log contents match the signed tree head
+        pub fn check(&self) -> Result<(), LogError> {
+            match (&self.key, &self.head) {
+                // The key may be written prior to an interrupted first append
+                (_, None) if self.leaves.is_empty() => Ok(()),
+                (Some(k), Some(h)) => {
+                    h.verify(k)?;
+                    if h.size != self.leaves.len() as u64 || h.root != self.root() {
+                        return Err(LogError::InvalidLog);
+                    }
+                    Ok(())
+                },
+                _ => Err(LogError::InvalidLog),
+            }
+        }
+    }
+
+    /// Write a file via a synced temporary file and rename, so a failed write
+    /// or power loss leaves either the previous or new contents
+    fn write_atomic(path: &Path, data: &[u8]) -> Result<(), LogError> {
+        let tmp = path.with_extension("tmp");
+        let mut f = std::fs::File::create(&tmp)
+            .map_err(|_e| LogError::Io)?;
+        f.write_all(data)
+            .and_then(|_| f.sync_all())
+            .and_then(|_| std::fs::rename(&tmp, path))
+            .map_err(|_e| LogError::Io)?;
+
+        // Sync the directory so the rename itself is persisted
+        #[cfg(unix)]
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::File::open(dir)
+                .and_then(|d| d.sync_all())
+                .map_err(|_e| LogError::Io)?;
+        }
+
+        Ok(())
+    }
+
+    /// Truncate a file to the provided length
+    fn truncate(path: &Path, len: u64) -> Result<(), LogError> {
+        OpenOptions::new().write(true).open(path)
+            .and_then(|f| f.set_len(len).and_then(|_| f.sync_all()))
+            .map_err(|_e| LogError::Io)
+    }
+
+    /// Compute the Merkle tree hash over a list of leaf hashes
+    fn tree_hash(leaves: &[Hash]) -> Hash {
+        tree_root(leaves.len(), &|i| leaves[i])
+    }
+
+    /// Compute the audit path for leaf `m` (leaf to root order)
+    fn audit_path(m: usize, leaves: &[Hash], path: &mut Vec<Hash>) {
+        let n = leaves.len();
+        if n <= 1 {
+            return;
+        }
+
+        let k = split(n);
+        if m < k {
+            audit_path(m, &leaves[..k], path);
+            path.push(tree_hash(&leaves[k..]));
+        } else {
+            audit_path(m - k, &leaves[k..], path);
+            path.push(tree_hash(&leaves[..k]));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use encdec::EncodeExt;
+    use rand::rngs::OsRng;
+
+    use crate::{test_utils::{self, temp_path, APP}, ManifestError, PackageOpts};
+
+    use super::*;
+
+    /// Build a manifest unique to index `i`
+    fn manifest(i: u32) -> Manifest {
+        test_utils::manifest(None, &i.to_le_bytes())
+    }
+
+    #[test]
+    fn append_prove_verify() {
+        let dir = temp_path("tlog");
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let key = PrivateKey::generate(&mut OsRng{});
+        let log_key = PublicKey::from(&key);
+        let manifests: Vec<_> = (0..7).map(manifest).collect();
+
+        let mut log = Log::open(&dir).unwrap();
+        for (i, m) in manifests.iter().enumerate() {
+            let (n, h) = log.append(m, &key).unwrap();
+            assert_eq!(n, i as u64);
+            assert_eq!(h.size, i as u64 + 1);
+            h.verify(&log_key).unwrap();
+
+            // Proofs for all entries verify against each tree size
+            for m in &manifests[..=i] {
+                let p = log.prove(m).unwrap();
+                p.verify(m, &log_key).unwrap();
+
+                let (b, n) = p.encode_buff::<1024>().unwrap();
+                assert_eq!(InclusionProof::decode_owned(&b[..n]).unwrap(), (p, n));
+            }
+        }
+
+        assert_eq!(log.append(&manifests[0], &key), Err(LogError::Duplicate));
+        assert_eq!(log.append(&manifest(10), &PrivateKey::generate(&mut OsRng{})), Err(LogError::KeyMismatch));
+        assert_eq!(log.prove(&manifest(10)).unwrap_err(), LogError::NotFound);
+
+        // Manifests with invalid signatures are not logged
+        let mut m = manifest(11);
+        m.sig = manifests[0].sig.clone();
+        assert_eq!(log.append(&m, &key), Err(LogError::Manifest(ManifestError::VerificationFailed)));
+        assert_eq!(log.find(&m), Ok(None));
+
+        // Proofs fail for other manifests, keys or modified paths
+        let p = Log::open(&dir).unwrap().prove(&manifests[3]).unwrap();
+        assert_eq!(p.verify(&manifests[4], &log_key), Err(LogError::InvalidProof));
+        assert_eq!(p.verify(&manifests[3], &PublicKey::from(&PrivateKey::generate(&mut OsRng{}))), Err(LogError::InvalidHead));
+
+        let mut path = p.path().to_vec();
+        path[1][0] ^= 0x01;
+        let p1 = InclusionProof::new(p.index, p.head.clone(), &path).unwrap();
+        assert_eq!(p1.verify(&manifests[3], &log_key), Err(LogError::InvalidProof));
+
+        // Edited logs are rejected on open
+        let leaves = dir.join("leaves.bin");
+        let mut d = std::fs::read(&leaves).unwrap();
+        d[70] ^= 0x01;
+        std::fs::write(&leaves, &d).unwrap();
+        assert_eq!(Log::open(&dir).unwrap_err(), LogError::InvalidLog);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn interrupted_append() {
+        let dir = temp_path("tlog-interrupted");
+        let _ = std::fs::remove_dir_all(&dir);
+        let leaves = dir.join("leaves.bin");
+
+        let key = PrivateKey::generate(&mut OsRng{});
+        let log_key = PublicKey::from(&key);
+        let manifests: Vec<_> = (0..5).map(manifest).collect();
+        let write_leaves = |d: &[u8]| {
+            let mut f = std::fs::OpenOptions::new().create(true).append(true).open(&leaves).unwrap();
+            f.write_all(d).unwrap();
+        };
+
+        // Interrupted first append (key and leaf written, no tree head)
+        let mut log = Log::open(&dir).unwrap();
+        std::fs::write(dir.join("log.pub"), log_key.to_string()).unwrap();
+        write_leaves(&manifests[0].signed_digest().unwrap());
+        assert_eq!(Log::open(&dir).unwrap().len(), 0);
+        assert_eq!(std::fs::metadata(&leaves).unwrap().len(), 0);
+
+        for m in &manifests[..3] {
+            log.append(m, &key).unwrap();
+        }
+
+        // Full and partial leaves written without a tree head update are discarded
+        for (i, n) in [(3, 64), (4, 10)] {
+            write_leaves(&manifests[i].signed_digest().unwrap()[..n]);
+
+            let mut log = Log::open(&dir).unwrap();
+            assert_eq!(log.len(), i);
+            assert_eq!(std::fs::metadata(&leaves).unwrap().len(), i as u64 * 64);
+
+            // Appending continues from the signed tree head
+            let m = &manifests[i];
+            assert_eq!(log.append(m, &key).unwrap().0, i as u64);
+
+            let log = Log::open(&dir).unwrap();
+            assert_eq!(log.len(), i + 1);
+            log.prove(m).unwrap().verify(m, &log_key).unwrap();
+            log.prove(&manifests[0]).unwrap().verify(&manifests[0], &log_key).unwrap();
+        }
+
+        // More than one unsigned leaf is not from an interrupted append
+        write_leaves(&[0u8; 128]);
+        assert_eq!(Log::open(&dir).unwrap_err(), LogError::InvalidLog);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn package_proof() {
+        let key = PrivateKey::generate(&mut OsRng{});
+        let m = manifest(1);
+        let head = TreeHead::sign(1, leaf_hash(&m.signed_digest().unwrap()), &key).unwrap();
+        let p = InclusionProof::new(0, head, &[]).unwrap();
+
+        let meta = 1u32.to_le_bytes();
+        let (b, n) = p.encode_buff::<1024>().unwrap();
+        let data = Package::encode_extra(&APP, &meta, &m, &b[..n], &PackageOpts::default()).unwrap();
+
+        let pkg = Package::parse(&data).unwrap();
+        pkg.check().unwrap();
+        let p1 = pkg.inclusion_proof().unwrap().unwrap();
+        assert_eq!(p1, p);
+        p1.verify(&pkg.manifest, &PublicKey::from(&key)).unwrap();
+
+        // Packages without proofs
+        let data = Package::encode(&APP, &meta, &m, &PackageOpts::default()).unwrap();
+        assert_eq!(Package::parse(&data).unwrap().inclusion_proof(), Ok(None));
+    }
+}
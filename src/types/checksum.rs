@@ -8,7 +8,7 @@ use core::{
 use encdec::{Encode, Decode};
 use sha2::{
     Sha512Trunc256, Digest,
-    digest::{generic_array::GenericArray, consts::U32},
+    digest::Output,
 };
 
 /// SHA512 checksum value
@@ -30,8 +30,8 @@ impl Checksum {
 }
 
 /// Create from digest output
-impl From<GenericArray<u8, U32>> for Checksum {
-    fn from(value: GenericArray<u8, U32>) -> Self {
+impl From<Output<Sha512Trunc256>> for Checksum {
+    fn from(value: Output<Sha512Trunc256>) -> Self {
         let mut b = [0u8; 32];
 
         b.copy_from_slice(&value);
@@ -51,7 +51,7 @@ impl Deref for Checksum {
 
 /// [Display] using hex encoding
 impl Display for Checksum {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         for b in self.0 {
             write!(f, "{:02x}", b)?;
         }
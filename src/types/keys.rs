@@ -9,6 +9,8 @@ use rand_core::{RngCore, CryptoRng};
 
 use crate::ManifestError;
 
+use super::Checksum;
+
 /// [PrivateKey] object wrapping [ed25519_dalek::SecretKey] with encode/decode support
 #[derive(Debug)]
 pub struct PrivateKey (ed25519_dalek::SecretKey);
@@ -16,7 +18,11 @@ pub struct PrivateKey (ed25519_dalek::SecretKey);
 impl PrivateKey {
     /// Generate a new private key using the provided RNG
     pub fn generate<RNG: RngCore + CryptoRng>(rng: &mut RNG) -> Self {
-        let k = ed25519_dalek::SecretKey::generate(rng);
+        let mut b = [0u8; ed25519_dalek::SECRET_KEY_LENGTH];
+        rng.fill_bytes(&mut b);
+
+        // Secret keys of the correct length are always valid
+        let k = ed25519_dalek::SecretKey::from_bytes(&b).unwrap();
         Self(k)
     }
 
@@ -51,7 +57,7 @@ impl Clone for PrivateKey {
 }
 
 impl core::fmt::Display for PrivateKey {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         for b in self.0.as_bytes() {
             write!(f, "{:02x}", b)?;
         }
@@ -90,6 +96,12 @@ impl PublicKey {
     pub fn inner(self) -> ed25519_dalek::PublicKey {
         self.0
     }
+
+    /// Compute the commitment to this key (SHA-512/256 over the encoded key),
+    /// as used for next key commitments (see [crate::Flags::NEXT_KEY])
+    pub fn commitment(&self) -> Checksum {
+        Checksum::compute(self.0.as_bytes())
+    }
 }
 
 impl From<&PrivateKey> for PublicKey {
@@ -112,7 +124,7 @@ impl From<ed25519_dalek::PublicKey> for PublicKey {
 
 impl From<&ed25519_dalek::PublicKey> for PublicKey {
     fn from(value: &ed25519_dalek::PublicKey) -> Self {
-        Self(value.clone())
+        Self(*value)
     }
 }
 
@@ -126,7 +138,7 @@ impl Deref for PublicKey {
 }
 
 impl core::fmt::Display for PublicKey {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         for b in self.0.as_bytes() {
             write!(f, "{:02x}", b)?;
         }
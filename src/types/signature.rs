@@ -48,7 +48,7 @@ impl Deref for Signature {
 
 /// [Display] using hex encoding
 impl Display for Signature {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         for b in self.0 {
             write!(f, "{:02x}", b)?;
         }
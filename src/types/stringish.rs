@@ -67,9 +67,9 @@ impl <const N: usize> AsRef<str> for Stringish<N> {
         // Find end of string if shorter than N
         let n = self.0.iter().enumerate().find_map(|(n, c)| {
             if *c == 0 {
-                return Some(n)
+                Some(n)
             } else {
-                return None
+                None
             }
         }).unwrap_or(N);
 
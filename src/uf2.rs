@@ -0,0 +1,201 @@
+//! [UF2](https://github.com/microsoft/uf2) container support, for packages
+//! distributed for drag-and-drop update via mass storage bootloaders
+
+use crate::ContainerError;
+
+/// UF2 block length
+pub const UF2_BLOCK_LEN: usize = 512;
+
+/// UF2 payload length per block (as commonly used by bootloaders)
+pub const UF2_PAYLOAD_LEN: usize = 256;
+
+const UF2_MAGIC_START0: u32 = 0x0A32_4655;
+const UF2_MAGIC_START1: u32 = 0x9E5D_5157;
+const UF2_MAGIC_END: u32 = 0x0AB1_6F30;
+
+/// Block is not intended for main flash and should be skipped
+const UF2_FLAG_NOT_MAIN_FLASH: u32 = 0x0000_0001;
+/// Block includes a family ID in place of the file size
+const UF2_FLAG_FAMILY_ID: u32 = 0x0000_2000;
+
+/// Check whether data appears to be a UF2 file
+pub fn is_uf2(data: &[u8]) -> bool {
+    data.len() >= UF2_BLOCK_LEN
+        && read_u32(data, 0) == UF2_MAGIC_START0
+        && read_u32(data, 4) == UF2_MAGIC_START1
+}
+
+/// Encode data as UF2 blocks starting at the provided base address,
+/// with an optional family ID
+pub fn encode(data: &[u8], base: u32, family_id: Option<u32>) -> Result<Vec<u8>, ContainerError> {
+    // Check the image fits in the 32-bit address space
+    if base as u64 + data.len() as u64 > u32::MAX as u64 + 1 {
+        return Err(ContainerError::AddressOverflow);
+    }
+
+    let chunks = data.chunks(UF2_PAYLOAD_LEN);
+    let num_blocks = chunks.len() as u32;
+
+    let (flags, family) = match family_id {
+        Some(f) => (UF2_FLAG_FAMILY_ID, f),
+        None => (0, 0),
+    };
+
+    let mut out = Vec::with_capacity(num_blocks as usize * UF2_BLOCK_LEN);
+    for (i, c) in chunks.enumerate() {
+        let mut b = [0u8; UF2_BLOCK_LEN];
+
+        write_u32(&mut b, 0, UF2_MAGIC_START0);
+        write_u32(&mut b, 4, UF2_MAGIC_START1);
+        write_u32(&mut b, 8, flags);
+        write_u32(&mut b, 12, base + (i * UF2_PAYLOAD_LEN) as u32);
+        write_u32(&mut b, 16, c.len() as u32);
+        write_u32(&mut b, 20, i as u32);
+        write_u32(&mut b, 24, num_blocks);
+        write_u32(&mut b, 28, family);
+        b[32..][..c.len()].copy_from_slice(c);
+        write_u32(&mut b, UF2_BLOCK_LEN - 4, UF2_MAGIC_END);
+
+        out.extend_from_slice(&b);
+    }
+
+    Ok(out)
+}
+
+/// Decoded UF2 file
+#[derive(Clone, Debug, PartialEq)]
+pub struct Uf2 {
+    /// Address of the first byte of data
+    pub base: u32,
+    /// Family ID, if specified
+    pub family_id: Option<u32>,
+    /// Reassembled data, gaps between blocks are filled with `0xFF`
+    pub data: Vec<u8>,
+}
+
+/// Decode and reassemble a UF2 file
+///
+/// Fails with [ContainerError::SpanTooLarge] if the reassembled image (including gaps between
+/// blocks) would exceed `max_span` bytes, and with [ContainerError::MultipleTargets] if blocks
+/// carry differing family IDs
+pub fn decode(data: &[u8], max_span: usize) -> Result<Uf2, ContainerError> {
+    if data.is_empty() || !data.len().is_multiple_of(UF2_BLOCK_LEN) {
+        return Err(ContainerError::InvalidLength);
+    }
+
+    // Parse blocks, skipping those not destined for main flash
+    let mut blocks = Vec::new();
+    let mut family_id = None;
+    for b in data.chunks(UF2_BLOCK_LEN) {
+        if read_u32(b, 0) != UF2_MAGIC_START0
+            || read_u32(b, 4) != UF2_MAGIC_START1
+            || read_u32(b, UF2_BLOCK_LEN - 4) != UF2_MAGIC_END {
+            return Err(ContainerError::InvalidMagic);
+        }
+
+        let flags = read_u32(b, 8);
+        if flags & UF2_FLAG_NOT_MAIN_FLASH != 0 {
+            continue;
+        }
+
+        // Blocks for other families may not be mixed into the image
+        let family = match flags & UF2_FLAG_FAMILY_ID != 0 {
+            true => Some(read_u32(b, 28)),
+            false => None,
+        };
+        if blocks.is_empty() {
+            family_id = family;
+        } else if family != family_id {
+            return Err(ContainerError::MultipleTargets);
+        }
+
+        let addr = read_u32(b, 12);
+        let len = read_u32(b, 16) as usize;
+        if len > UF2_BLOCK_LEN - 32 - 4 {
+            return Err(ContainerError::InvalidBlock);
+        }
+
+        blocks.push((addr, &b[32..][..len]));
+    }
+
+    // Reassemble blocks by address
+    blocks.sort_by_key(|(a, _d)| *a);
+    let base = match blocks.first() {
+        Some((a, _d)) => *a,
+        None => return Err(ContainerError::InvalidLength),
+    };
+
+    let mut out = Vec::new();
+    for (addr, d) in blocks {
+        let offset = (addr - base) as usize;
+        if offset.saturating_add(d.len()) > max_span {
+            return Err(ContainerError::SpanTooLarge);
+        }
+        if offset < out.len() {
+            return Err(ContainerError::InvalidBlock);
+        }
+        out.resize(offset, 0xFF);
+        out.extend_from_slice(d);
+    }
+
+    Ok(Uf2{ base, family_id, data: out })
+}
+
+fn read_u32(b: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(b[offset..][..4].try_into().unwrap())
+}
+
+fn write_u32(b: &mut [u8], offset: usize, v: u32) {
+    b[offset..][..4].copy_from_slice(&v.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_decode() {
+        let data: Vec<u8> = (0..1000u32).map(|v| v as u8).collect();
+
+        let e = encode(&data, 0x1000_0000, Some(0xe48b_ff56)).unwrap();
+        assert_eq!(e.len(), 4 * UF2_BLOCK_LEN);
+        assert!(is_uf2(&e));
+
+        let d = decode(&e, data.len()).unwrap();
+        assert_eq!(d.base, 0x1000_0000);
+        assert_eq!(d.family_id, Some(0xe48b_ff56));
+        assert_eq!(d.data, data);
+    }
+
+    #[test]
+    fn decode_invalid() {
+        let mut e = encode(&[0xab; 300], 0, None).unwrap();
+        assert_eq!(decode(&e[..UF2_BLOCK_LEN + 1], 300), Err(ContainerError::InvalidLength));
+
+        e[UF2_BLOCK_LEN - 1] ^= 0xff;
+        assert_eq!(decode(&e, 300), Err(ContainerError::InvalidMagic));
+    }
+
+    #[test]
+    fn decode_span() {
+        let e = encode(&[0xab; 300], 0x1000, None).unwrap();
+        assert_eq!(decode(&e, 299), Err(ContainerError::SpanTooLarge));
+
+        // Distant blocks are rejected without filling the gap
+        let mut e = encode(&[0xab; 300], 0x1000, None).unwrap();
+        write_u32(&mut e[UF2_BLOCK_LEN..], 12, 0xf000_0000);
+        assert_eq!(decode(&e, 1 << 20), Err(ContainerError::SpanTooLarge));
+    }
+
+    #[test]
+    fn decode_mixed_family() {
+        let mut e = encode(&[0xab; 300], 0x1000, Some(0xe48b_ff56)).unwrap();
+        write_u32(&mut e[UF2_BLOCK_LEN..], 28, 0x6800_0000);
+        assert_eq!(decode(&e, 300), Err(ContainerError::MultipleTargets));
+
+        // Blocks without a family ID may not be mixed with those with one
+        let mut e = encode(&[0xab; 300], 0x1000, Some(0xe48b_ff56)).unwrap();
+        write_u32(&mut e[UF2_BLOCK_LEN..], 8, 0);
+        assert_eq!(decode(&e, 300), Err(ContainerError::MultipleTargets));
+    }
+}
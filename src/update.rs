@@ -0,0 +1,402 @@
+//! Trial boot update state machine
+//!
+//! Updates are tracked by manifest digest ([Manifest::signed_digest]) through the states:
+//!
+//! - [UpdateState::Pending] once an update is verified and staged ([Update::stage])
+//! - [UpdateState::Trial] when the bootloader boots the update once ([Update::boot])
+//! - [UpdateState::Confirmed] when the application confirms the update ([Update::confirm])
+//! - [UpdateState::Reverted] where the update is rejected ([Update::revert]) or a trial boot
+//!   ends without confirmation, returning to the previous image
+//!
+//! The digest of the most recently confirmed image is kept alongside the update
+//! ([UpdateStatus::confirmed]), so the bootloader can identify the image to revert to.
+//!
+//! Update state is persisted via the [UpdateStore] trait, with [UpdateFile] for hosts (`std` feature).
+
+use crate::{types::PublicKey, Manifest, UpdateError};
+
+/// Manifest digest length
+pub const DIGEST_LEN: usize = 64;
+
+/// Update status magic
+pub const UPDATE_STATUS_MAGIC: [u8; 4] = *b"FWUS";
+
+/// Encoded update status length
+pub const UPDATE_STATUS_LEN: usize = UPDATE_STATUS_MAGIC.len() + 2 + 2 * DIGEST_LEN;
+
+/// Update state
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum UpdateState {
+    /// Update staged, to be booted on the next reset
+    Pending = 1,
+    /// Update booted once, awaiting confirmation
+    Trial = 2,
+    /// Update confirmed by the application
+    Confirmed = 3,
+    /// Update rejected, the previous image is to be booted
+    Reverted = 4,
+}
+
+impl TryFrom<u8> for UpdateState {
+    type Error = UpdateError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(Self::Pending),
+            2 => Ok(Self::Trial),
+            3 => Ok(Self::Confirmed),
+            4 => Ok(Self::Reverted),
+            _ => Err(UpdateError::InvalidStatus),
+        }
+    }
+}
+
+/// Update status, the digest of the most recent update and its state,
+/// along with the digest of the last confirmed image
+#[derive(Clone, Debug, PartialEq)]
+pub struct UpdateStatus {
+    /// Update manifest digest ([Manifest::signed_digest])
+    pub digest: [u8; DIGEST_LEN],
+    /// Update state
+    pub state: UpdateState,
+    /// Manifest digest of the last confirmed image, booted where the update is reverted
+    /// (`None` where no update has been confirmed)
+    pub confirmed: Option<[u8; DIGEST_LEN]>,
+}
+
+impl UpdateStatus {
+    /// Encode the update status for storage
+    pub fn encode(&self) -> [u8; UPDATE_STATUS_LEN] {
+        let mut b = [0u8; UPDATE_STATUS_LEN];
+        b[..4].copy_from_slice(&UPDATE_STATUS_MAGIC);
+        b[4] = self.state as u8;
+        b[5..][..DIGEST_LEN].copy_from_slice(&self.digest);
+        if let Some(c) = &self.confirmed {
+            b[5 + DIGEST_LEN] = 1;
+            b[6 + DIGEST_LEN..].copy_from_slice(c);
+        }
+        b
+    }
+
+    /// Decode a stored update status
+    pub fn decode(b: &[u8]) -> Result<Self, UpdateError> {
+        if b.len() != UPDATE_STATUS_LEN || b[..4] != UPDATE_STATUS_MAGIC {
+            return Err(UpdateError::InvalidStatus);
+        }
+
+        let confirmed = match b[5 + DIGEST_LEN] {
+            0 if b[6 + DIGEST_LEN..].iter().all(|v| *v == 0) => None,
+            1 => Some(b[6 + DIGEST_LEN..].try_into().unwrap()),
+            _ => return Err(UpdateError::InvalidStatus),
+        };
+
+        Ok(Self {
+            state: UpdateState::try_from(b[4])?,
+            digest: b[5..][..DIGEST_LEN].try_into().unwrap(),
+            confirmed,
+        })
+    }
+}
+
+/// Persistent storage for [UpdateStatus]
+pub trait UpdateStore {
+    /// Load the current update status (`None` if no update has been staged)
+    fn load(&mut self) -> Result<Option<UpdateStatus>, UpdateError>;
+
+    /// Store updated update status
+    fn store(&mut self, status: &UpdateStatus) -> Result<(), UpdateError>;
+}
+
+impl UpdateStore for Option<UpdateStatus> {
+    fn load(&mut self) -> Result<Option<UpdateStatus>, UpdateError> {
+        Ok(self.clone())
+    }
+
+    fn store(&mut self, status: &UpdateStatus) -> Result<(), UpdateError> {
+        *self = Some(status.clone());
+        Ok(())
+    }
+}
+
+/// Boot action for an update image, returned by [Update::boot]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BootAction {
+    /// Boot the update once for a trial, the application must confirm this
+    Trial,
+    /// Boot the update, this has been confirmed
+    Boot,
+    /// Boot the previous image ([UpdateStatus::confirmed]), the update was reverted
+    Revert,
+}
+
+/// Image matched against the stored [UpdateStatus]
+enum Matched {
+    /// Image is the staged update
+    Update(UpdateStatus),
+    /// Image is the last confirmed image, which is not the staged update
+    Confirmed,
+}
+
+/// Update helper, verifying update manifests against the trusted keys
+/// and managing the persisted [UpdateStatus]
+pub struct Update<'a, S: UpdateStore> {
+    keys: &'a [PublicKey],
+    store: S,
+}
+
+impl<'a, S: UpdateStore> Update<'a, S> {
+    /// Create an update helper with the trusted keys and update store
+    pub fn new(keys: &'a [PublicKey], store: S) -> Self {
+        Self { keys, store }
+    }
+
+    /// Fetch the current update status
+    pub fn status(&mut self) -> Result<Option<UpdateStatus>, UpdateError> {
+        self.store.load()
+    }
+
+    /// Stage a verified update, marking this as pending
+    ///
+    /// The manifest signing key must be trusted, and the application and metadata should
+    /// be checked against the manifest prior to staging. A pending update may be replaced,
+    /// however updates under trial must be confirmed or reverted first.
+    pub fn stage(&mut self, m: &Manifest) -> Result<(), UpdateError> {
+        m.verify(self.keys)?;
+
+        // Carry the last confirmed image over to the new update
+        let confirmed = match self.store.load()? {
+            Some(UpdateStatus{ state: UpdateState::Trial, .. }) => return Err(UpdateError::InvalidState),
+            Some(s) => s.confirmed,
+            None => None,
+        };
+
+        self.store.store(&UpdateStatus{ digest: m.signed_digest()?, state: UpdateState::Pending, confirmed })
+    }
+
+    /// Fetch the boot action for an image on reset, called by the bootloader
+    ///
+    /// Pending updates move to [UpdateState::Trial] and are booted once, updates still under
+    /// trial were not confirmed so are reverted. The last confirmed image ([UpdateStatus::confirmed])
+    /// is always booted, and other manifests fail with [UpdateError::UnknownImage].
+    pub fn boot(&mut self, m: &Manifest) -> Result<BootAction, UpdateError> {
+        m.verify(self.keys)?;
+        let mut s = match self.load_matching(m)? {
+            Matched::Update(s) => s,
+            Matched::Confirmed => return Ok(BootAction::Boot),
+        };
+
+        let (state, action) = match s.state {
+            UpdateState::Pending => (UpdateState::Trial, BootAction::Trial),
+            UpdateState::Trial => (UpdateState::Reverted, BootAction::Revert),
+            UpdateState::Confirmed => return Ok(BootAction::Boot),
+            UpdateState::Reverted => return Ok(BootAction::Revert),
+        };
+
+        // Update state prior to booting so a reset during the trial reverts the update
+        s.state = state;
+        self.store.store(&s)?;
+
+        Ok(action)
+    }
+
+    /// Confirm the running update, called by the application following a trial boot
+    ///
+    /// Confirming an already confirmed image has no effect
+    pub fn confirm(&mut self, m: &Manifest) -> Result<(), UpdateError> {
+        let mut s = match self.load_matching(m)? {
+            Matched::Update(s) => s,
+            Matched::Confirmed => return Ok(()),
+        };
+
+        match s.state {
+            UpdateState::Trial => (),
+            UpdateState::Confirmed => return Ok(()),
+            _ => return Err(UpdateError::InvalidState),
+        }
+
+        s.state = UpdateState::Confirmed;
+        s.confirmed = Some(s.digest);
+        self.store.store(&s)
+    }
+
+    /// Revert a pending or trial update, for example where application self-tests fail
+    pub fn revert(&mut self, m: &Manifest) -> Result<(), UpdateError> {
+        let mut s = match self.load_matching(m)? {
+            Matched::Update(s) => s,
+            Matched::Confirmed => return Err(UpdateError::InvalidState),
+        };
+
+        match s.state {
+            UpdateState::Pending | UpdateState::Trial => (),
+            UpdateState::Reverted => return Ok(()),
+            UpdateState::Confirmed => return Err(UpdateError::InvalidState),
+        }
+
+        s.state = UpdateState::Reverted;
+        self.store.store(&s)
+    }
+
+    /// Internal helper to load the update status, matching the manifest against
+    /// the staged update or the last confirmed image
+    fn load_matching(&mut self, m: &Manifest) -> Result<Matched, UpdateError> {
+        let digest = m.signed_digest()?;
+        match self.store.load()? {
+            Some(s) if s.digest == digest => Ok(Matched::Update(s)),
+            Some(s) if s.confirmed == Some(digest) => Ok(Matched::Confirmed),
+            _ => Err(UpdateError::UnknownImage),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+pub use file::UpdateFile;
+
+#[cfg(feature = "std")]
+mod file {
+    use std::{io::Write, path::{Path, PathBuf}};
+
+    use super::*;
+
+    /// File-backed [UpdateStore], atomically replacing the file on each store
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct UpdateFile {
+        path: PathBuf,
+    }
+
+    impl UpdateFile {
+        /// Create a file-backed store, the file is created on the first store
+        pub fn new(path: impl AsRef<Path>) -> Self {
+            Self { path: path.as_ref().to_path_buf() }
+        }
+    }
+
+    impl UpdateStore for UpdateFile {
+        fn load(&mut self) -> Result<Option<UpdateStatus>, UpdateError> {
+            match std::fs::read(&self.path) {
+                Ok(d) => UpdateStatus::decode(&d).map(Some),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(_e) => Err(UpdateError::Store),
+            }
+        }
+
+        fn store(&mut self, status: &UpdateStatus) -> Result<(), UpdateError> {
+            // Write and sync then rename so a failed write or power loss does not
+            // corrupt the stored status
+            let tmp = self.path.with_extension("tmp");
+            let mut f = std::fs::File::create(&tmp)
+                .map_err(|_e| UpdateError::Store)?;
+            f.write_all(&status.encode())
+                .and_then(|_| f.sync_all())
+                .and_then(|_| std::fs::rename(&tmp, &self.path))
+                .map_err(|_e| UpdateError::Store)?;
+
+            // Sync the directory so the rename itself is persisted
+            #[cfg(unix)]
+            if let Some(dir) = self.path.parent().filter(|d| !d.as_os_str().is_empty()) {
+                std::fs::File::open(dir)
+                    .and_then(|d| d.sync_all())
+                    .map_err(|_e| UpdateError::Store)?;
+            }
+
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::OsRng;
+
+    use crate::{test_utils::{manifest, temp_path}, types::PrivateKey, ManifestError};
+
+    use super::*;
+
+    #[test]
+    fn status_codec() {
+        let s = UpdateStatus{ digest: [0x5a; DIGEST_LEN], state: UpdateState::Trial, confirmed: None };
+        assert_eq!(UpdateStatus::decode(&s.encode()), Ok(s));
+
+        let s = UpdateStatus{ digest: [0x5a; DIGEST_LEN], state: UpdateState::Pending, confirmed: Some([0xa5; DIGEST_LEN]) };
+        assert_eq!(UpdateStatus::decode(&s.encode()), Ok(s.clone()));
+
+        let mut b = s.encode();
+        b[5 + DIGEST_LEN] = 2;
+        assert_eq!(UpdateStatus::decode(&b), Err(UpdateError::InvalidStatus));
+
+        // Erased or truncated storage is rejected
+        assert_eq!(UpdateStatus::decode(&[0xff; UPDATE_STATUS_LEN]), Err(UpdateError::InvalidStatus));
+        assert_eq!(UpdateStatus::decode(&[]), Err(UpdateError::InvalidStatus));
+    }
+
+    #[test]
+    fn trial_boot() {
+        let key = PrivateKey::generate(&mut OsRng{});
+        let keys = [PublicKey::from(&key)];
+        let (m1, m2) = (manifest(Some(&key), &[1]), manifest(Some(&key), &[2]));
+
+        let mut u = Update::new(&keys, None);
+        assert_eq!(u.boot(&m1), Err(UpdateError::UnknownImage));
+
+        // Untrusted updates are not staged
+        let other = manifest(Some(&PrivateKey::generate(&mut OsRng{})), &[1]);
+        assert_eq!(u.stage(&other), Err(UpdateError::Manifest(ManifestError::NoMatchingKey)));
+
+        // Pending update is booted once then confirmed
+        u.stage(&m1).unwrap();
+        assert_eq!(u.confirm(&m1), Err(UpdateError::InvalidState));
+        assert_eq!(u.boot(&m1), Ok(BootAction::Trial));
+        assert_eq!(u.confirm(&m2), Err(UpdateError::UnknownImage));
+        u.confirm(&m1).unwrap();
+        assert_eq!(u.boot(&m1), Ok(BootAction::Boot));
+        assert_eq!(u.revert(&m1), Err(UpdateError::InvalidState));
+
+        // Unconfirmed trials are reverted on the next boot, to the confirmed image
+        u.stage(&m2).unwrap();
+        assert_eq!(u.status().unwrap().and_then(|s| s.confirmed), Some(m1.signed_digest().unwrap()));
+        assert_eq!(u.boot(&m2), Ok(BootAction::Trial));
+        assert_eq!(u.stage(&m1), Err(UpdateError::InvalidState));
+        assert_eq!(u.boot(&m2), Ok(BootAction::Revert));
+        assert_eq!(u.boot(&m2), Ok(BootAction::Revert));
+        assert_eq!(u.confirm(&m2), Err(UpdateError::InvalidState));
+        assert_eq!(u.boot(&m1), Ok(BootAction::Boot));
+        assert_eq!(u.revert(&m1), Err(UpdateError::InvalidState));
+        u.confirm(&m1).unwrap();
+
+        // Images other than the update or confirmed image are unknown
+        let m3 = manifest(Some(&key), &[3]);
+        assert_eq!(u.boot(&m3), Err(UpdateError::UnknownImage));
+
+        // Updates may be reverted by the application
+        u.stage(&m2).unwrap();
+        assert_eq!(u.boot(&m2), Ok(BootAction::Trial));
+        u.revert(&m2).unwrap();
+        assert_eq!(u.status().unwrap().map(|s| s.state), Some(UpdateState::Reverted));
+        assert_eq!(u.boot(&m1), Ok(BootAction::Boot));
+    }
+
+    #[test]
+    fn file_store() {
+        let key = PrivateKey::generate(&mut OsRng{});
+        let keys = [PublicKey::from(&key)];
+        let m = manifest(Some(&key), &[]);
+
+        let path = temp_path("update");
+        let _ = std::fs::remove_file(&path);
+
+        let mut u = Update::new(&keys, UpdateFile::new(&path));
+        assert_eq!(u.status(), Ok(None));
+        u.stage(&m).unwrap();
+        assert_eq!(u.boot(&m), Ok(BootAction::Trial));
+
+        // State persists across resets
+        let mut u = Update::new(&keys, UpdateFile::new(&path));
+        assert_eq!(u.status().unwrap(), Some(UpdateStatus{ digest: m.signed_digest().unwrap(), state: UpdateState::Trial, confirmed: None }));
+        assert_eq!(u.boot(&m), Ok(BootAction::Revert));
+
+        std::fs::write(&path, [0u8; 10]).unwrap();
+        assert_eq!(u.status(), Err(UpdateError::InvalidStatus));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
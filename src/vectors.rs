@@ -0,0 +1,262 @@
+//! Conformance test vectors, for checking third-party verifiers against this implementation
+//!
+//! Vectors are attached packages generated deterministically from a seed, covering valid
+//! packages in each supported layout and signature mode as well as packages with bad signatures,
+//! untrusted keys, wrong lengths, flipped checksum bits, and unknown flags or versions.
+//!
+//! A conforming verifier accepts a vector only where the package parses, the app and metadata
+//! match the manifest, the signature is valid, and the signing key is one of [Vectors::keys].
+
+use encdec::Encode;
+use rand_core::{CryptoRng, RngCore};
+use sha2::{Digest, Sha512};
+use strum::{Display, EnumString};
+
+use crate::{
+    types::{PrivateKey, PublicKey},
+    Flags, Layout, Manifest, ManifestBuilder, ManifestError, MetadataFormat, Package, PackageOpts, SigMode,
+};
+
+/// Expected verification result for a test vector
+#[derive(Copy, Clone, Debug, PartialEq, Display, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum Expected {
+    /// Package must be accepted
+    Valid,
+    /// Package must be rejected
+    Invalid,
+}
+
+/// Conformance test vector
+#[derive(Clone, Debug, PartialEq)]
+pub struct Vector {
+    /// Vector name (unique within a set)
+    pub name: &'static str,
+    /// Vector description
+    pub description: &'static str,
+    /// Expected verification result
+    pub expected: Expected,
+    /// Attached package data
+    pub data: Vec<u8>,
+}
+
+/// Set of conformance test vectors
+#[derive(Clone, Debug, PartialEq)]
+pub struct Vectors {
+    /// Trusted public keys for verification
+    pub keys: Vec<PublicKey>,
+    /// Test vectors
+    pub vectors: Vec<Vector>,
+}
+
+impl Vector {
+    /// Verify the vector package using this implementation against the provided trusted keys
+    pub fn verify(&self, keys: &[PublicKey]) -> Expected {
+        let r = Package::parse(&self.data)
+            .and_then(|p| {
+                p.check().map_err(|_| ManifestError::VerificationFailed)?;
+                p.manifest.verify(keys)
+            });
+
+        match r {
+            Ok(_) => Expected::Valid,
+            Err(_) => Expected::Invalid,
+        }
+    }
+}
+
+/// Derive a deterministic signing key from the seed and key index
+pub fn seeded_key(seed: &[u8], index: u8) -> PrivateKey {
+    let h = Sha512::new()
+        .chain(b"fwsig-vectors-key")
+        .chain(seed)
+        .chain([index])
+        .finalize();
+
+    let k = ed25519_dalek::SecretKey::from_bytes(&h[..32])
+        .expect("32-byte secret keys are always valid");
+    PrivateKey::from(k)
+}
+
+/// Generate deterministic data of the specified length from the seed and label
+fn seeded_data(seed: &[u8], label: &[u8], len: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(len);
+    let mut i = 0u32;
+
+    while out.len() < len {
+        let h = Sha512::new()
+            .chain(b"fwsig-vectors-data")
+            .chain(seed)
+            .chain(label)
+            .chain(i.to_le_bytes())
+            .finalize();
+        out.extend_from_slice(&h);
+        i += 1;
+    }
+
+    out.truncate(len);
+    out
+}
+
+/// Placeholder RNG for [ManifestBuilder::build] and [Manifest::sign], which only draw
+/// randomness to generate transient keys and are always called with a seeded key here
+#[derive(Default)]
+struct NoRng;
+
+impl RngCore for NoRng {
+    fn next_u32(&mut self) -> u32 {
+        unreachable!("vectors are signed with seeded keys")
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        unreachable!("vectors are signed with seeded keys")
+    }
+
+    fn fill_bytes(&mut self, _dest: &mut [u8]) {
+        unreachable!("vectors are signed with seeded keys")
+    }
+
+    fn try_fill_bytes(&mut self, _dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        unreachable!("vectors are signed with seeded keys")
+    }
+}
+
+impl CryptoRng for NoRng {}
+
+/// Generate the conformance vector set from the provided seed
+///
+/// All vectors are signed with seeded keys, so outputs are identical for a given seed.
+pub fn generate(seed: &[u8]) -> Result<Vectors, ManifestError> {
+    let key = seeded_key(seed, 0);
+    let untrusted_key = seeded_key(seed, 1);
+
+    let app = seeded_data(seed, b"app", 1000);
+    let meta = seeded_data(seed, b"meta", 64);
+    let opts = PackageOpts::default();
+
+    // Helper to build and encode packages with the provided builder configuration
+    let package = |key: &PrivateKey, app: &[u8], meta: &[u8], f: &dyn Fn(&mut ManifestBuilder)| -> Result<(Manifest, Vec<u8>), ManifestError> {
+        let mut b = ManifestBuilder::new();
+        b.name("vectors")?.version("1.0.0")?;
+        f(&mut b);
+        let m = b.app_bin(app)?
+            .meta_bin(MetadataFormat::Binary, meta)?
+            .build::<NoRng>(Some(key.clone()))?;
+
+        let p = Package::encode(app, meta, &m, &opts)?;
+        Ok((m, p))
+    };
+
+    // Helper to re-encode a package after modifying (and optionally re-signing) the manifest
+    let modify = |m: &Manifest, resign: bool, f: &dyn Fn(&mut Manifest)| -> Result<Vec<u8>, ManifestError> {
+        let mut m = m.clone();
+        f(&mut m);
+        if resign {
+            m.sign::<NoRng>(key.clone())?;
+        }
+
+        let mut p = app.clone();
+        p.extend_from_slice(&meta);
+        let n = p.len();
+        p.resize(n + m.encode_len()?, 0);
+        m.encode(&mut p[n..])?;
+        Ok(p)
+    };
+
+    let (m, valid) = package(&key, &app, &meta, &|_b| ())?;
+    let mut vectors = vec![];
+
+    let mut add = |name, description, expected, data| {
+        vectors.push(Vector { name, description, expected, data });
+    };
+
+    // Valid packages
+    add("valid", "Compact layout package with trailing manifest", Expected::Valid, valid.clone());
+    add("valid_large", "Large layout package", Expected::Valid,
+        package(&key, &app, &meta, &|b| { b.layout(Layout::Large); })?.1);
+    add("valid_header", "Header layout package (manifest first)", Expected::Valid,
+        package(&key, &app, &meta, &|b| { b.flags(Flags::HEADER); })?.1);
+    add("valid_aligned", "Package with metadata aligned to 256 bytes", Expected::Valid,
+        package(&key, &app, &meta, &|b| { b.meta_align(256).unwrap(); })?.1);
+    add("valid_no_meta", "Package without metadata", Expected::Valid,
+        package(&key, &app, &[], &|_b| ())?.1);
+    add("valid_legacy", "Ed25519ph signature without a context (legacy)", Expected::Valid,
+        package(&key, &app, &meta, &|b| { b.sig_mode(SigMode::Legacy); })?.1);
+    add("valid_ed25519", "Pure Ed25519 signature", Expected::Valid,
+        package(&key, &app, &meta, &|b| { b.sig_mode(SigMode::Ed25519); })?.1);
+
+    // Signature and key failures
+    let mut d = valid.clone();
+    let n = d.len();
+    d[n - 1] ^= 0x01;
+    add("bad_signature", "Signature with a flipped bit", Expected::Invalid, d);
+
+    add("bad_signature_key", "Manifest key replaced with a key other than the signer", Expected::Invalid,
+        modify(&m, false, &|m| m.key = PublicKey::from(&untrusted_key))?);
+    add("bad_signature_mode", "Signature mode changed without re-signing", Expected::Invalid,
+        modify(&m, false, &|m| m.flags = (m.flags & !Flags::SIG_MODE.bits()) | ((SigMode::Ed25519 as u16) << crate::SIG_MODE_SHIFT))?);
+    add("untrusted_key", "Valid package signed by an untrusted key", Expected::Invalid,
+        package(&untrusted_key, &app, &meta, &|_b| ())?.1);
+
+    // Length failures
+    let mut d = valid.clone();
+    d.remove(0);
+    add("truncated_app", "Package with the first application byte removed", Expected::Invalid, d);
+    add("app_length_unsigned", "Application length changed without re-signing", Expected::Invalid,
+        modify(&m, false, &|m| m.app_len -= 1)?);
+    add("app_length_signed", "Application length changed and re-signed", Expected::Invalid,
+        modify(&m, true, &|m| m.app_len -= 1)?);
+    add("meta_length_signed", "Metadata length changed and re-signed", Expected::Invalid,
+        modify(&m, true, &|m| m.meta_len += 1)?);
+
+    // Checksum failures
+    let mut d = valid.clone();
+    d[100] ^= 0x01;
+    add("app_bit_flip", "Application with a flipped bit", Expected::Invalid, d);
+    let mut d = valid.clone();
+    d[app.len() + 10] ^= 0x01;
+    add("meta_bit_flip", "Metadata with a flipped bit", Expected::Invalid, d);
+    add("app_checksum_unsigned", "Application checksum with a flipped bit, not re-signed", Expected::Invalid,
+        modify(&m, false, &|m| m.app_csum.0[0] ^= 0x01)?);
+    add("app_checksum_signed", "Application checksum with a flipped bit, re-signed", Expected::Invalid,
+        modify(&m, true, &|m| m.app_csum.0[0] ^= 0x01)?);
+    add("meta_checksum_signed", "Metadata checksum with a flipped bit, re-signed", Expected::Invalid,
+        modify(&m, true, &|m| m.meta_csum.0[31] ^= 0x80)?);
+
+    // Unknown flags and versions
+    add("unknown_flags", "Reserved flag bit set, re-signed", Expected::Invalid,
+        modify(&m, true, &|m| m.flags |= 1 << 5)?);
+    add("unknown_flags_high", "Reserved high flag bit set, re-signed", Expected::Invalid,
+        modify(&m, true, &|m| m.flags |= 1 << 15)?);
+
+    let mut d = valid;
+    let n = d.len() - m.encode_len()?;
+    d[n..][..2].copy_from_slice(&0x0003u16.to_le_bytes());
+    add("unknown_version", "Unsupported manifest version", Expected::Invalid, d);
+
+    Ok(Vectors { keys: vec![PublicKey::from(&key)], vectors })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generate_deterministic() {
+        let v1 = generate(b"seed").unwrap();
+        let v2 = generate(b"seed").unwrap();
+        assert_eq!(v1, v2);
+
+        let v3 = generate(b"other").unwrap();
+        assert_ne!(v1.vectors[0].data, v3.vectors[0].data);
+    }
+
+    #[test]
+    fn vectors_conform() {
+        let v = generate(b"seed").unwrap();
+
+        for t in &v.vectors {
+            assert_eq!(t.verify(&v.keys), t.expected, "vector: {}", t.name);
+        }
+    }
+}
@@ -0,0 +1,68 @@
+#!/usr/bin/env python3
+"""Generate the fixture key and app, and MCUboot ed25519 images in the layout produced by `imgtool sign`.
+
+This is a fallback for environments without imgtool, images should be regenerated with
+`regen.sh` (which records the imgtool version) where imgtool is available.
+
+Images are signed with the RFC 8032 test vector 1 key (`key.pem`), equivalent to:
+
+    imgtool sign --key key.pem --header-size 0x200 --pad-header --slot-size 0x10000 \
+        --version 1.2.3+4 --public-key-format hash app.bin ed25519-keyhash.bin
+    imgtool sign --key key.pem --header-size 0x200 --pad-header --slot-size 0x10000 \
+        --version 1.2.3+4 --public-key-format full app.bin ed25519-pubkey.bin
+
+ed25519 signatures are deterministic so imgtool output should match byte-for-byte.
+"""
+
+import hashlib
+import os
+import struct
+
+from cryptography.hazmat.primitives import serialization
+from cryptography.hazmat.primitives.asymmetric.ed25519 import Ed25519PrivateKey
+
+SEED = bytes.fromhex("9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60")
+
+IMAGE_MAGIC = 0x96F3B83D
+TLV_INFO_MAGIC = 0x6907
+HEADER_SIZE = 0x200
+
+TLV_KEYHASH = 0x01
+TLV_PUBKEY = 0x02
+TLV_SHA256 = 0x10
+TLV_ED25519 = 0x24
+
+
+def sign(key, app, full_key):
+    header = struct.pack("<IIHHIIBBHII", IMAGE_MAGIC, 0, HEADER_SIZE, 0, len(app), 0, 1, 2, 3, 4, 0)
+    payload = header.ljust(HEADER_SIZE, b"\x00") + app
+    digest = hashlib.sha256(payload).digest()
+
+    pub = key.public_key().public_bytes(serialization.Encoding.DER, serialization.PublicFormat.SubjectPublicKeyInfo)
+    tlvs = [(TLV_SHA256, digest)]
+    tlvs.append((TLV_PUBKEY, pub) if full_key else (TLV_KEYHASH, hashlib.sha256(pub).digest()))
+    tlvs.append((TLV_ED25519, key.sign(digest)))
+
+    body = b"".join(struct.pack("<HH", k, len(v)) + v for k, v in tlvs)
+    return payload + struct.pack("<HH", TLV_INFO_MAGIC, 4 + len(body)) + body
+
+
+def main():
+    d = os.path.dirname(os.path.abspath(__file__))
+    key = Ed25519PrivateKey.from_private_bytes(SEED)
+    app = bytes(i & 0xFF for i in range(1000))
+
+    pem = key.private_bytes(serialization.Encoding.PEM, serialization.PrivateFormat.PKCS8, serialization.NoEncryption())
+    files = {
+        "key.pem": pem,
+        "app.bin": app,
+        "ed25519-keyhash.bin": sign(key, app, False),
+        "ed25519-pubkey.bin": sign(key, app, True),
+    }
+    for name, data in files.items():
+        with open(os.path.join(d, name), "wb") as f:
+            f.write(data)
+
+
+if __name__ == "__main__":
+    main()
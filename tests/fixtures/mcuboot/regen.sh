@@ -0,0 +1,18 @@
+#!/bin/sh
+# Regenerate MCUboot ed25519 test images with imgtool (`pip install imgtool`),
+# recording the imgtool version used in IMGTOOL_VERSION
+set -e
+
+cd "$(dirname "$0")"
+
+for format in hash full; do
+    case $format in
+        hash) out=ed25519-keyhash.bin ;;
+        full) out=ed25519-pubkey.bin ;;
+    esac
+
+    imgtool sign --key key.pem --header-size 0x200 --pad-header --slot-size 0x10000 \
+        --version 1.2.3+4 --public-key-format $format app.bin $out
+done
+
+imgtool version > IMGTOOL_VERSION